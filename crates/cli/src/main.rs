@@ -1,13 +1,233 @@
 use clap::Parser;
-use rtsp::Server;
+use rtsp::client::ClientTransport;
+use rtsp::mount::{PublishPolicy, RtpCodec};
+use rtsp::protocol::{AccessPolicy, AccessRule, AuthSettings, IpNet, Method, Principal};
+use rtsp::transport::relay::{RelayConfig, RelayMode};
+use rtsp::{Server, ServerConfig};
+use std::collections::HashMap;
 use std::io;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
-#[command(name = "rtsp-server", about = "Standalone RTSP server for H.264 streams")]
+#[command(
+    name = "rtsp-server",
+    about = "Standalone RTSP server for H.264 streams"
+)]
 struct Args {
     /// Bind address (host:port)
     #[arg(long, short, default_value = "0.0.0.0:8554")]
     bind: String,
+
+    /// UDP port range for server RTP/RTCP ports (min-max)
+    #[arg(long, default_value = "5000-65535", value_parser = parse_port_range)]
+    server_ports: RangeInclusive<u16>,
+
+    /// Seconds without client activity before a session is removed
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    session_timeout: u64,
+
+    /// Accept streams published with ANNOUNCE/RECORD on any path
+    #[arg(long, conflicts_with = "publish_path")]
+    allow_publish: bool,
+
+    /// Accept streams published with ANNOUNCE/RECORD on this path (repeatable)
+    #[arg(long, value_name = "PATH")]
+    publish_path: Vec<String>,
+
+    /// Serve plain RTP arriving on a UDP address at PATH (repeatable):
+    /// PATH=ADDR[,sdp=FILE][,pt=N][,codec=NAME[/CLOCK[/CHANNELS]]].
+    /// Without an SDP file the codec follows from a static payload type, or
+    /// is named with codec= for dynamic ones (pt defaults to 96)
+    #[arg(long, value_name = "SPEC", value_parser = parse_udp_source)]
+    udp_source: Vec<UdpSourceArg>,
+
+    /// Restream an upstream RTSP URL at PATH (repeatable): PATH=URL.
+    /// Upstream is pulled while viewers watch, unless --relay-always-on
+    #[arg(long, value_name = "PATH=URL", value_parser = parse_relay)]
+    relay: Vec<(String, String)>,
+
+    /// Keep relays connected to upstream even without viewers
+    #[arg(long)]
+    relay_always_on: bool,
+
+    /// Receive relayed streams interleaved on the RTSP connection instead
+    /// of over UDP
+    #[arg(long)]
+    relay_tcp: bool,
+
+    /// Require clients to log in as USER with PASSWORD (repeatable):
+    /// USER:PASSWORD
+    #[arg(long, value_name = "USER:PASSWORD", value_parser = parse_user)]
+    user: Vec<(String, String)>,
+
+    /// Realm shown to clients asked for credentials
+    #[arg(long, requires = "user")]
+    auth_realm: Option<String>,
+
+    /// Accept Basic authentication, which sends passwords in the clear,
+    /// besides Digest
+    #[arg(long, requires = "user")]
+    auth_basic: bool,
+
+    /// Let WHO use METHODS (default all) on mounts matching PATH
+    /// (repeatable, first match wins): PATH[:METHOD,...]=WHO[,WHO...].
+    /// PATH is a mount path, a prefix like cams/* or *; WHO is * for
+    /// anyone, + for any logged-in user, ~ for signed-URL holders, @ROLE
+    /// or a user name
+    #[arg(long, value_name = "RULE", value_parser = parse_access_rule)]
+    access: Vec<AccessRule>,
+
+    /// Make users members of ROLE (repeatable): ROLE=USER[,USER...]
+    #[arg(long, value_name = "ROLE=USERS", value_parser = parse_role)]
+    role: Vec<(String, Vec<String>)>,
+
+    /// Accept connections only from this address or network (repeatable)
+    #[arg(long, value_name = "CIDR")]
+    allow_ip: Vec<IpNet>,
+
+    /// Refuse connections from this address or network (repeatable)
+    #[arg(long, value_name = "CIDR")]
+    deny_ip: Vec<IpNet>,
+
+    /// Accept stream URLs signed with this secret (?exp=...&token=...) in
+    /// place of credentials, and refuse anonymous clients without one
+    #[arg(long, value_name = "SECRET")]
+    url_secret: Option<String>,
+}
+
+#[derive(Clone)]
+struct UdpSourceArg {
+    path: String,
+    bind: SocketAddr,
+    sdp: Option<PathBuf>,
+    payload_type: u8,
+    codec: Option<String>,
+}
+
+impl UdpSourceArg {
+    fn description(&self) -> Result<String, String> {
+        match &self.sdp {
+            Some(file) => std::fs::read_to_string(file)
+                .map_err(|e| format!("cannot read {}: {e}", file.display())),
+            None => {
+                let pt = self.payload_type;
+                let codec = match &self.codec {
+                    Some(codec) => RtpCodec::with_encoding(pt, codec)
+                        .ok_or_else(|| format!("invalid codec '{codec}'"))?,
+                    None => RtpCodec::from_payload_type(pt)
+                        .ok_or_else(|| format!("payload type {pt} needs codec=NAME or sdp=FILE"))?,
+                };
+                Ok(codec.media_description())
+            }
+        }
+    }
+}
+
+fn parse_udp_source(s: &str) -> Result<UdpSourceArg, String> {
+    let (path, rest) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected PATH=ADDR[,sdp=FILE][,pt=N][,codec=NAME], got '{s}'"))?;
+    let mut options = rest.split(',');
+    let bind = options.next().unwrap_or_default();
+    let mut source = UdpSourceArg {
+        path: path.trim_matches('/').to_string(),
+        bind: bind
+            .parse()
+            .map_err(|e| format!("invalid address '{bind}': {e}"))?,
+        sdp: None,
+        payload_type: 96,
+        codec: None,
+    };
+    for option in options {
+        match option.split_once('=') {
+            Some(("sdp", file)) => source.sdp = Some(PathBuf::from(file)),
+            Some(("codec", codec)) => source.codec = Some(codec.to_string()),
+            Some(("pt", pt)) => {
+                source.payload_type = pt
+                    .parse()
+                    .ok()
+                    .filter(|&pt| pt < 128)
+                    .ok_or_else(|| format!("invalid payload type '{pt}'"))?;
+            }
+            _ => {
+                return Err(format!(
+                    "unknown option '{option}', expected sdp=FILE, pt=N or codec=NAME"
+                ));
+            }
+        }
+    }
+    Ok(source)
+}
+
+fn parse_relay(s: &str) -> Result<(String, String), String> {
+    let (path, url) = s
+        .split_once('=')
+        .filter(|(_, url)| url.starts_with("rtsp://"))
+        .ok_or_else(|| format!("expected PATH=rtsp://..., got '{s}'"))?;
+    Ok((path.trim_matches('/').to_string(), url.to_string()))
+}
+
+fn parse_user(s: &str) -> Result<(String, String), String> {
+    let (user, password) = s
+        .split_once(':')
+        .filter(|(user, _)| !user.is_empty())
+        .ok_or_else(|| "expected USER:PASSWORD".to_string())?;
+    Ok((user.to_string(), password.to_string()))
+}
+
+fn parse_access_rule(s: &str) -> Result<AccessRule, String> {
+    let (target, who) = s
+        .split_once('=')
+        .filter(|(target, who)| !target.is_empty() && !who.is_empty())
+        .ok_or_else(|| format!("expected PATH[:METHOD,...]=WHO[,WHO...], got '{s}'"))?;
+    let mut rule = match target.split_once(':') {
+        Some((path, methods)) => {
+            let methods: Vec<Method> = methods
+                .split(',')
+                .map(|m| Method::from(m.trim().to_uppercase().as_str()))
+                .collect();
+            AccessRule::new(path).methods(&methods)
+        }
+        None => AccessRule::new(target),
+    };
+    for principal in who.split(',') {
+        rule = rule.allow(Principal::parse(principal));
+    }
+    Ok(rule)
+}
+
+fn parse_role(s: &str) -> Result<(String, Vec<String>), String> {
+    let (role, users) = s
+        .split_once('=')
+        .filter(|(role, _)| !role.is_empty())
+        .ok_or_else(|| format!("expected ROLE=USER[,USER...], got '{s}'"))?;
+    Ok((
+        role.to_string(),
+        users.split(',').map(|u| u.trim().to_string()).collect(),
+    ))
+}
+
+fn parse_port_range(s: &str) -> Result<RangeInclusive<u16>, String> {
+    let (min, max) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expected <min>-<max>, got '{s}'"))?;
+    let min: u16 = min
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid port '{min}': {e}"))?;
+    let max: u16 = max
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid port '{max}': {e}"))?;
+    if min >= max {
+        return Err(format!(
+            "range must hold at least one port pair, got {min}-{max}"
+        ));
+    }
+    Ok(min..=max)
 }
 
 fn main() {
@@ -15,16 +235,108 @@ fn main() {
 
     let args = Args::parse();
 
-    let mut server = Server::new(&args.bind);
+    let publish_policy = if args.allow_publish {
+        PublishPolicy::Any
+    } else if !args.publish_path.is_empty() {
+        PublishPolicy::Paths(args.publish_path)
+    } else {
+        PublishPolicy::Disabled
+    };
+
+    let config = ServerConfig {
+        server_port_range: args.server_ports,
+        session_timeout: Duration::from_secs(args.session_timeout),
+        publish_policy,
+        ..ServerConfig::default()
+    };
+    let mut server = Server::with_config(&args.bind, config);
+
+    if !args.user.is_empty() {
+        let mut settings = AuthSettings {
+            allow_basic: args.auth_basic,
+            ..AuthSettings::default()
+        };
+        if let Some(realm) = args.auth_realm {
+            settings.realm = realm;
+        }
+        server.set_authenticator(args.user.into_iter().collect::<HashMap<_, _>>(), settings);
+    }
+    let mut roles: HashMap<String, Vec<String>> = HashMap::new();
+    for (role, users) in args.role {
+        roles.entry(role).or_default().extend(users);
+    }
+    server.set_access_policy(AccessPolicy {
+        rules: args.access,
+        roles,
+        allow: args.allow_ip,
+        deny: args.deny_ip,
+    });
+    if let Some(secret) = &args.url_secret {
+        server.set_url_secret(secret);
+    }
 
     if let Err(e) = server.start() {
         eprintln!("Failed to start server: {}", e);
         return;
     }
 
+    let mut gateways = Vec::new();
+    for source in &args.udp_source {
+        let added = source.description().and_then(|sdp| {
+            server
+                .add_udp_source(&source.path, source.bind, &sdp)
+                .map_err(|e| e.to_string())
+        });
+        match added {
+            Ok(gateway) => {
+                println!(
+                    "RTP on {} served at /{}",
+                    gateway.local_addr(),
+                    gateway.path()
+                );
+                gateways.push(gateway);
+            }
+            Err(e) => {
+                eprintln!("Failed to add UDP source for /{}: {}", source.path, e);
+                server.stop();
+                return;
+            }
+        }
+    }
+
+    let relay_config = RelayConfig {
+        mode: if args.relay_always_on {
+            RelayMode::AlwaysOn
+        } else {
+            RelayMode::OnDemand
+        },
+        transport: if args.relay_tcp {
+            ClientTransport::Tcp
+        } else {
+            ClientTransport::Udp
+        },
+        ..RelayConfig::default()
+    };
+    let mut relays = Vec::new();
+    for (path, url) in &args.relay {
+        match server.add_relay(path, url, relay_config.clone()) {
+            Ok(relay) => {
+                println!("{} relayed at /{}", relay.url(), relay.path());
+                relays.push(relay);
+            }
+            Err(e) => {
+                eprintln!("Failed to add relay for /{}: {}", path, e);
+                server.stop();
+                return;
+            }
+        }
+    }
+
     println!("RTSP server on {} — press Enter to stop", args.bind);
     let mut input = String::new();
     io::stdin().read_line(&mut input).unwrap();
 
+    drop(relays);
+    drop(gateways);
     server.stop();
 }
@@ -29,15 +29,118 @@ pub enum RtspError {
     #[error("RTSP parse error: {kind}")]
     Parse { kind: ParseErrorKind },
 
-    #[error("port range exhausted (tried to allocate beyond u16 range)")]
+    #[error("no free server port pair in the configured range")]
     PortRangeExhausted,
+
+    #[error("invalid RTP packet")]
+    InvalidRtpPacket,
+
+    #[error("method not implemented: {0}")]
+    MethodNotImplemented(String),
+
+    #[error("method not valid in the session's state: {0}")]
+    MethodNotValidInState(String),
+
+    #[error("unsupported RTSP version: {0}")]
+    UnsupportedVersion(String),
+
+    #[error("no supported transport in: {0}")]
+    UnsupportedTransport(String),
+
+    #[error("unsupported option tags: {0}")]
+    OptionNotSupported(String),
+
+    #[error("publishing not allowed: {0}")]
+    PublishNotAllowed(String),
+
+    #[error("mount already in use: {0}")]
+    MountInUse(String),
+
+    #[error("mount not found: {0}")]
+    MountNotFound(String),
+
+    #[error("aggregate operation not allowed: {0}")]
+    AggregateNotAllowed(String),
+
+    #[error("authentication required")]
+    Unauthorized,
+
+    #[error("access denied: {0}")]
+    Forbidden(String),
+
+    #[error("invalid IP network: {0}")]
+    InvalidNetwork(String),
+
+    #[error("no such track in the session description: {0}")]
+    TrackNotFound(usize),
+
+    #[error("invalid RTSP URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("{method} failed: {status_code} {reason}")]
+    RequestFailed {
+        method: String,
+        status_code: u16,
+        reason: String,
+    },
+
+    #[error("connection closed by peer")]
+    ConnectionClosed,
+}
+
+impl RtspError {
+    /// RTSP status code reported to a client when a request fails with this
+    /// error (RFC 2326 §7.1.1).
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::Parse { kind } => kind.status_code(),
+            Self::SessionNotFound(_) => 454,
+            Self::TransportNotConfigured(_)
+            | Self::SessionNotPlaying(_)
+            | Self::MethodNotValidInState(_) => 455,
+            Self::MethodNotImplemented(_) => 501,
+            Self::UnsupportedVersion(_) => 505,
+            Self::UnsupportedTransport(_) => 461,
+            Self::OptionNotSupported(_) => 551,
+            Self::PublishNotAllowed(_) => 403,
+            Self::MountNotFound(_) => 404,
+            Self::MountInUse(_) => 403,
+            Self::AggregateNotAllowed(_) => 459,
+            Self::InvalidUrl(_) => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden(_) => 403,
+            Self::InvalidNetwork(_) => 400,
+            Self::TrackNotFound(_) => 404,
+            Self::RequestFailed { status_code, .. } => *status_code,
+            Self::PortRangeExhausted | Self::NotStarted => 503,
+            Self::InvalidRtpPacket => 400,
+            Self::Io(_) | Self::AlreadyRunning | Self::ConnectionClosed => 500,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ParseErrorKind {
     EmptyRequest,
     InvalidRequestLine,
+    InvalidStatusLine,
     InvalidHeader,
+    HeadersTooLarge,
+    TooManyHeaders,
+    BodyTooLarge,
+    InvalidContentLength,
+    MissingCSeq,
+    InvalidCSeq,
+    InvalidSdp,
+}
+
+impl ParseErrorKind {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::BodyTooLarge => 413,
+            _ => 400,
+        }
+    }
 }
 
 impl fmt::Display for ParseErrorKind {
@@ -45,9 +148,61 @@ impl fmt::Display for ParseErrorKind {
         match self {
             Self::EmptyRequest => write!(f, "empty request"),
             Self::InvalidRequestLine => write!(f, "invalid request line"),
+            Self::InvalidStatusLine => write!(f, "invalid status line"),
             Self::InvalidHeader => write!(f, "invalid header"),
+            Self::HeadersTooLarge => write!(f, "request headers too large"),
+            Self::TooManyHeaders => write!(f, "too many request headers"),
+            Self::BodyTooLarge => write!(f, "request body too large"),
+            Self::InvalidContentLength => write!(f, "invalid Content-Length"),
+            Self::InvalidSdp => write!(f, "invalid SDP session description"),
+            Self::MissingCSeq => write!(f, "missing CSeq"),
+            Self::InvalidCSeq => write!(f, "invalid CSeq"),
         }
     }
 }
 
 pub type Result<T> = std::result::Result<T, RtspError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_to_rtsp_status_codes() {
+        assert_eq!(RtspError::SessionNotFound("1".into()).status_code(), 454);
+        assert_eq!(
+            RtspError::MethodNotValidInState("PLAY".into()).status_code(),
+            455
+        );
+        assert_eq!(
+            RtspError::MethodNotImplemented("RECORD".into()).status_code(),
+            501
+        );
+        assert_eq!(
+            RtspError::UnsupportedVersion("RTSP/2.0".into()).status_code(),
+            505
+        );
+        assert_eq!(
+            RtspError::UnsupportedTransport("RTP/AVP/TCP".into()).status_code(),
+            461
+        );
+        assert_eq!(
+            RtspError::OptionNotSupported("play.basic".into()).status_code(),
+            551
+        );
+        assert_eq!(
+            RtspError::PublishNotAllowed("live".into()).status_code(),
+            403
+        );
+        assert_eq!(RtspError::MountInUse("live".into()).status_code(), 403);
+        assert_eq!(RtspError::PortRangeExhausted.status_code(), 503);
+        let body = RtspError::Parse {
+            kind: ParseErrorKind::BodyTooLarge,
+        };
+        assert_eq!(body.status_code(), 413);
+        let cseq = RtspError::Parse {
+            kind: ParseErrorKind::MissingCSeq,
+        };
+        assert_eq!(cseq.status_code(), 400);
+    }
+}
@@ -1,10 +1,13 @@
+pub mod client;
 pub mod error;
 pub mod media;
+pub mod mount;
 pub mod protocol;
+pub mod rtcp;
 pub mod server;
 pub mod session;
 pub mod transport;
 
 pub use error::{Result, RtspError};
 pub use media::Packetizer;
-pub use server::{Server, Viewer};
+pub use server::{Server, ServerConfig, Viewer};
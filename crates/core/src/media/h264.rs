@@ -1,8 +1,19 @@
+use std::collections::BTreeMap;
+
 use super::Packetizer;
-use super::rtp::RtpHeader;
+use super::jitter::{MAX_DROPOUT, extend_sequence};
+use super::rtp::{RtpHeader, RtpPacket};
 
 const DEFAULT_MTU: usize = 1400;
 
+/// Packets held waiting for a missing one before it is given up as lost.
+const DEFAULT_REORDER_WINDOW: usize = 32;
+
+/// NAL unit types of aggregation and fragmentation packets (RFC 6184 §5.2).
+const NAL_STAP_A: u8 = 24;
+const NAL_FU_A: u8 = 28;
+const NAL_IDR: u8 = 5;
+
 /// H.264 RTP packetizer (RFC 6184).
 ///
 /// Supports single NAL unit mode and FU-A fragmentation.
@@ -158,6 +169,10 @@ impl Packetizer for H264Packetizer {
         "H264"
     }
 
+    fn rtp_header(&self) -> &RtpHeader {
+        &self.header
+    }
+
     fn clock_rate(&self) -> u32 {
         90000
     }
@@ -171,6 +186,275 @@ impl Packetizer for H264Packetizer {
     }
 }
 
+/// One access unit (frame) reassembled from RTP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessUnit {
+    pub timestamp: u32,
+    /// NAL units in decoding order, without start codes.
+    pub nal_units: Vec<Vec<u8>>,
+    /// Packets of this access unit were lost or malformed, so some NAL
+    /// units may be missing or truncated.
+    pub corrupted: bool,
+}
+
+impl AccessUnit {
+    /// The access unit as an Annex B bitstream with 4-byte start codes.
+    pub fn to_annex_b(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.nal_units.iter().map(|n| n.len() + 4).sum());
+        for nal in &self.nal_units {
+            data.extend_from_slice(&[0, 0, 0, 1]);
+            data.extend_from_slice(nal);
+        }
+        data
+    }
+
+    /// Whether the access unit holds an IDR slice.
+    pub fn is_keyframe(&self) -> bool {
+        self.nal_units
+            .iter()
+            .any(|n| n.first().is_some_and(|h| h & 0x1f == NAL_IDR))
+    }
+}
+
+/// H.264 RTP depacketizer (RFC 6184), the inverse of [`H264Packetizer`].
+///
+/// Handles single NAL unit packets, STAP-A and FU-A. Packets are put back
+/// in sequence order first: one after a gap waits until `reorder_window`
+/// later packets arrived, then the gap is counted as loss. The first packet
+/// received starts the stream, and a jump too large to be loss restarts
+/// it. NAL units are grouped into access units by the marker bit, or by a
+/// timestamp change when the marker packet was lost. Access units that
+/// lost packets are flagged as corrupted, or dropped with
+/// [`Self::drop_corrupted`].
+#[derive(Debug)]
+pub struct H264Depacketizer {
+    reorder_window: usize,
+    drop_corrupted: bool,
+    /// Extended sequence number expected next.
+    next: Option<u64>,
+    held: BTreeMap<u64, Vec<u8>>,
+    current: Option<AccessUnit>,
+    /// FU-A fragments of the NAL unit being reassembled.
+    fragment: Option<Vec<u8>>,
+    lost: u64,
+    discarded: u64,
+}
+
+impl H264Depacketizer {
+    pub fn new() -> Self {
+        Self {
+            reorder_window: DEFAULT_REORDER_WINDOW,
+            drop_corrupted: false,
+            next: None,
+            held: BTreeMap::new(),
+            current: None,
+            fragment: None,
+            lost: 0,
+            discarded: 0,
+        }
+    }
+
+    /// Packets to hold waiting for a missing one. 0 disables reordering:
+    /// every gap counts as loss at once.
+    pub fn reorder_window(mut self, packets: usize) -> Self {
+        self.reorder_window = packets;
+        self
+    }
+
+    /// Drop corrupted access units instead of returning them flagged.
+    pub fn drop_corrupted(mut self, drop: bool) -> Self {
+        self.drop_corrupted = drop;
+        self
+    }
+
+    /// Packets never received.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Packets discarded as late, duplicate or not RTP.
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Add one RTP packet, returning the access units it completed.
+    pub fn push(&mut self, packet: &[u8]) -> Vec<AccessUnit> {
+        let Some(sequence) = RtpPacket::parse(packet).map(|rtp| rtp.sequence) else {
+            self.discarded += 1;
+            return Vec::new();
+        };
+        let mut units = Vec::new();
+        let next = *self.next.get_or_insert(extend_sequence(sequence, None));
+        let extended = extend_sequence(sequence, Some(next));
+
+        if (extended as i64 - next as i64).abs() > MAX_DROPOUT {
+            tracing::debug!(sequence, "RTP sequence jumped, restarting depacketizer");
+            self.drain_held(&mut units);
+            self.finish(&mut units);
+            self.next = Some(extend_sequence(sequence, None));
+            self.held
+                .insert(extend_sequence(sequence, None), packet.to_vec());
+        } else if extended < next || self.held.contains_key(&extended) {
+            self.discarded += 1;
+            return units;
+        } else {
+            self.held.insert(extended, packet.to_vec());
+        }
+
+        while let Some((&sequence, _)) = self.held.first_key_value() {
+            let in_order = Some(sequence) == self.next;
+            if !in_order && self.held.len() <= self.reorder_window {
+                break;
+            }
+            if let Some(packet) = self.held.remove(&sequence) {
+                self.release(sequence, &packet, &mut units);
+            }
+        }
+        units
+    }
+
+    /// Reassemble everything still held, e.g. at the end of a stream, and
+    /// return the access units left, including an unfinished last one.
+    pub fn flush(&mut self) -> Vec<AccessUnit> {
+        let mut units = Vec::new();
+        self.drain_held(&mut units);
+        self.finish(&mut units);
+        units
+    }
+
+    fn drain_held(&mut self, units: &mut Vec<AccessUnit>) {
+        for (sequence, packet) in std::mem::take(&mut self.held) {
+            self.release(sequence, &packet, units);
+        }
+    }
+
+    /// Depacketize the packet with extended sequence number `sequence`,
+    /// accounting for any packets skipped before it.
+    fn release(&mut self, sequence: u64, packet: &[u8], units: &mut Vec<AccessUnit>) {
+        let Some(rtp) = RtpPacket::parse(packet) else {
+            return;
+        };
+        let skipped = self.next.map_or(0, |next| sequence.saturating_sub(next));
+        self.next = Some(sequence + 1);
+
+        if let Some(au) = self.current.as_mut()
+            && au.timestamp != rtp.timestamp
+        {
+            // The previous access unit ended without a marker, so lost
+            // packets may have been its last ones.
+            au.corrupted |= skipped > 0;
+            self.finish(units);
+        }
+        let au = self.current.get_or_insert_with(|| AccessUnit {
+            timestamp: rtp.timestamp,
+            nal_units: Vec::new(),
+            corrupted: false,
+        });
+        if skipped > 0 {
+            tracing::trace!(skipped, "RTP packets lost");
+            self.lost += skipped;
+            au.corrupted = true;
+            // A fragment spanning the gap can't be completed.
+            self.fragment = None;
+        }
+
+        if let Err(reason) = depacketize(rtp.payload, au, &mut self.fragment) {
+            tracing::debug!(
+                sequence = rtp.sequence,
+                reason,
+                "malformed H.264 RTP payload"
+            );
+            au.corrupted = true;
+        }
+        if rtp.marker {
+            self.finish(units);
+        }
+    }
+
+    /// Complete the current access unit.
+    fn finish(&mut self, units: &mut Vec<AccessUnit>) {
+        let Some(mut au) = self.current.take() else {
+            return;
+        };
+        if self.fragment.take().is_some() {
+            au.corrupted = true;
+        }
+        if au.nal_units.is_empty() || (au.corrupted && self.drop_corrupted) {
+            tracing::trace!(timestamp = au.timestamp, "dropping corrupted access unit");
+            return;
+        }
+        units.push(au);
+    }
+}
+
+impl Default for H264Depacketizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Add the NAL units carried by one RTP payload to `au`, continuing or
+/// completing the FU-A `fragment` in progress.
+fn depacketize(
+    payload: &[u8],
+    au: &mut AccessUnit,
+    fragment: &mut Option<Vec<u8>>,
+) -> Result<(), &'static str> {
+    let header = *payload.first().ok_or("empty payload")?;
+    if header & 0x80 != 0 {
+        return Err("forbidden bit set");
+    }
+    match header & 0x1f {
+        1..=23 => {
+            if fragment.take().is_some() {
+                return Err("fragmented NAL unit interrupted");
+            }
+            au.nal_units.push(payload.to_vec());
+            Ok(())
+        }
+        NAL_STAP_A => {
+            let mut rest = &payload[1..];
+            while !rest.is_empty() {
+                let size = match rest {
+                    [high, low, ..] => u16::from_be_bytes([*high, *low]) as usize,
+                    _ => return Err("truncated STAP-A size"),
+                };
+                let nal = rest.get(2..2 + size).ok_or("truncated STAP-A unit")?;
+                if !nal.is_empty() {
+                    au.nal_units.push(nal.to_vec());
+                }
+                rest = &rest[2 + size..];
+            }
+            Ok(())
+        }
+        NAL_FU_A => {
+            let fu_header = *payload.get(1).ok_or("truncated FU-A header")?;
+            let (start, end) = (fu_header & 0x80 != 0, fu_header & 0x40 != 0);
+            let data = &payload[2..];
+            if start {
+                let interrupted = fragment.is_some();
+                let mut nal = Vec::with_capacity(1 + data.len());
+                nal.push((header & 0xe0) | (fu_header & 0x1f));
+                nal.extend_from_slice(data);
+                *fragment = Some(nal);
+                if interrupted {
+                    return Err("fragmented NAL unit interrupted");
+                }
+            } else {
+                fragment
+                    .as_mut()
+                    .ok_or("FU-A continuation without start")?
+                    .extend_from_slice(data);
+            }
+            if end && let Some(nal) = fragment.take() {
+                au.nal_units.push(nal);
+            }
+            Ok(())
+        }
+        _ => Err("unsupported packetization"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -287,4 +571,153 @@ mod tests {
         assert_eq!(p.clock_rate(), 90000);
         assert_eq!(p.payload_type(), 96);
     }
+
+    // --- Depacketization ---
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn depacketize_all(
+        depacketizer: &mut H264Depacketizer,
+        packets: &[Vec<u8>],
+    ) -> Vec<AccessUnit> {
+        let mut units: Vec<AccessUnit> =
+            packets.iter().flat_map(|p| depacketizer.push(p)).collect();
+        units.extend(depacketizer.flush());
+        units
+    }
+
+    /// A random access unit of 1-5 NAL units. Payload bytes are never zero
+    /// so no start code appears inside a NAL unit.
+    fn random_access_unit(rng: &mut StdRng) -> Vec<Vec<u8>> {
+        (0..rng.random_range(1..=5))
+            .map(|_| {
+                let header = [0x65, 0x41, 0x06, 0x67, 0x68][rng.random_range(0..5)];
+                let len = rng.random_range(1..4 * DEFAULT_MTU);
+                std::iter::once(header)
+                    .chain((0..len).map(|_| rng.random_range(1..=255u8)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn annex_b(nal_units: &[Vec<u8>]) -> Vec<u8> {
+        AccessUnit {
+            timestamp: 0,
+            nal_units: nal_units.to_vec(),
+            corrupted: false,
+        }
+        .to_annex_b()
+    }
+
+    #[test]
+    fn round_trips_single_and_fragmented_nals() {
+        let mut p = make_packetizer();
+        let mut idr = vec![0x65];
+        idr.extend(vec![0xAA; DEFAULT_MTU * 2]);
+        let frame = annex_b(&[vec![0x67, 0x42], vec![0x68, 0xCE], idr]);
+        let packets = p.packetize(&frame, 3000);
+        assert!(packets.len() > 3);
+
+        let units = depacketize_all(&mut H264Depacketizer::new(), &packets);
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].to_annex_b(), frame);
+        assert!(units[0].is_keyframe() && !units[0].corrupted);
+    }
+
+    #[test]
+    fn round_trip_property() {
+        let mut rng = StdRng::seed_from_u64(0x264);
+        for _ in 0..20 {
+            let mut p = H264Packetizer::new(96, rng.random());
+            let frames: Vec<Vec<Vec<u8>>> = (0..10).map(|_| random_access_unit(&mut rng)).collect();
+            let mut packets: Vec<Vec<u8>> = frames
+                .iter()
+                .flat_map(|nals| p.packetize(&annex_b(nals), 3000))
+                .collect();
+            // Swap neighbours to reorder within the window. The first
+            // packet received starts the stream, so it stays first.
+            for i in 2..packets.len() {
+                if rng.random_bool(0.2) {
+                    packets.swap(i - 1, i);
+                }
+            }
+
+            let mut depacketizer = H264Depacketizer::new();
+            let units = depacketize_all(&mut depacketizer, &packets);
+            assert_eq!(units.len(), frames.len());
+            for (i, (unit, nals)) in units.iter().zip(&frames).enumerate() {
+                assert_eq!(&unit.nal_units, nals);
+                assert_eq!(unit.timestamp, 3000 * i as u32);
+                assert!(!unit.corrupted);
+            }
+            assert_eq!(depacketizer.lost(), 0);
+        }
+    }
+
+    #[test]
+    fn unpacks_stap_a() {
+        let mut header = RtpHeader::new(96, 1);
+        let mut packet = header.write(true).to_vec();
+        packet.push(0x78); // STAP-A, NRI 3
+        for nal in [&[0x67, 0x42, 0x00][..], &[0x68, 0xCE]] {
+            packet.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+            packet.extend_from_slice(nal);
+        }
+        let units = H264Depacketizer::new().push(&packet);
+        assert_eq!(
+            units[0].nal_units,
+            [vec![0x67, 0x42, 0x00], vec![0x68, 0xCE]]
+        );
+
+        // A size running past the payload is malformed.
+        packet.truncate(packet.len() - 1);
+        let units = H264Depacketizer::new().push(&packet);
+        assert!(units[0].corrupted);
+    }
+
+    #[test]
+    fn flags_or_drops_access_units_with_loss() {
+        let mut p = make_packetizer();
+        let mut idr = vec![0x65];
+        idr.extend(vec![0xAA; DEFAULT_MTU * 3]);
+        let mut packets = p.packetize(&annex_b(&[vec![0x67, 0x42], idr]), 3000);
+        packets.extend(p.packetize(&annex_b(&[vec![0x41, 0x9A]]), 3000));
+        // Lose a middle FU-A fragment of the first access unit.
+        packets.remove(2);
+
+        let mut depacketizer = H264Depacketizer::new().reorder_window(0);
+        let units = depacketize_all(&mut depacketizer, &packets);
+        assert_eq!(units.len(), 2);
+        assert!(units[0].corrupted);
+        assert_eq!(units[0].nal_units, [vec![0x67, 0x42]]);
+        assert!(!units[1].corrupted);
+        assert_eq!(depacketizer.lost(), 1);
+
+        let mut depacketizer = H264Depacketizer::new()
+            .reorder_window(0)
+            .drop_corrupted(true);
+        let units = depacketize_all(&mut depacketizer, &packets);
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].nal_units, [vec![0x41, 0x9A]]);
+    }
+
+    #[test]
+    fn timestamp_change_ends_access_unit_without_marker() {
+        let mut p = make_packetizer();
+        let mut packets = p.packetize(&annex_b(&[vec![0x41, 0x01]]), 3000);
+        packets.extend(p.packetize(&annex_b(&[vec![0x41, 0x02]]), 3000));
+        packets[0][1] &= 0x7f; // clear the marker bit
+
+        let mut depacketizer = H264Depacketizer::new();
+        assert!(depacketizer.push(&packets[0]).is_empty());
+        let units = depacketizer.push(&packets[1]);
+        assert_eq!(units.len(), 2);
+        assert_eq!((units[0].timestamp, units[1].timestamp), (0, 3000));
+        assert!(!units[0].corrupted);
+
+        // Late duplicates are discarded.
+        assert!(depacketizer.push(&packets[0]).is_empty());
+        assert_eq!(depacketizer.discarded(), 1);
+    }
 }
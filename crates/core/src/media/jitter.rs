@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Default time a packet is held waiting for the ones before it.
+pub const DEFAULT_JITTER_LATENCY: Duration = Duration::from_millis(50);
+
+/// Packets held at most, even if they haven't waited `latency` yet.
+const MAX_HELD_PACKETS: usize = 512;
+
+/// Sequence jumps larger than this restart the buffer instead of waiting
+/// (RFC 3550 Appendix A.1).
+pub(crate) const MAX_DROPOUT: i64 = 3000;
+
+/// Puts RTP packets of one SSRC back in sequence order.
+///
+/// In-order packets are released at once. A packet after a gap waits up to
+/// `latency` for the missing ones, then the gap is given up as loss. Late
+/// packets that arrive after their place was released, and duplicates, are
+/// dropped.
+#[derive(Debug)]
+pub struct JitterBuffer {
+    latency: Duration,
+    /// Extended sequence number expected next.
+    next: Option<u64>,
+    held: BTreeMap<u64, (Instant, Vec<u8>)>,
+    dropped: u64,
+}
+
+impl JitterBuffer {
+    pub fn new(latency: Duration) -> Self {
+        Self {
+            latency,
+            next: None,
+            held: BTreeMap::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Packets dropped as late or duplicate.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Packets waiting for a gap to fill.
+    pub fn len(&self) -> usize {
+        self.held.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    /// Add a packet received at `now`, returning the packets now ready in
+    /// sequence order. Packets too short to be RTP are passed straight on.
+    pub fn push(&mut self, packet: Vec<u8>, now: Instant) -> Vec<Vec<u8>> {
+        if packet.len() < 12 {
+            return vec![packet];
+        }
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let next = *self.next.get_or_insert(extend_sequence(sequence, None));
+        let extended = extend_sequence(sequence, Some(next));
+
+        let mut ready = Vec::new();
+        let offset = extended as i64 - next as i64;
+        if offset.abs() > MAX_DROPOUT {
+            // The source restarted its numbering: flush and start over.
+            ready.extend(std::mem::take(&mut self.held).into_values().map(|(_, p)| p));
+            self.next = Some(extend_sequence(sequence, None));
+            self.held
+                .insert(extend_sequence(sequence, None), (now, packet));
+        } else if extended < next || self.held.contains_key(&extended) {
+            self.dropped += 1;
+            return ready;
+        } else {
+            self.held.insert(extended, (now, packet));
+        }
+
+        ready.extend(self.pop_ready(now));
+        ready
+    }
+
+    /// Release packets that are next in sequence or have waited out the
+    /// latency. Call periodically so a gap doesn't hold packets forever.
+    pub fn pop_ready(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        let mut ready = Vec::new();
+        while let Some((&sequence, (arrived, _))) = self.held.first_key_value() {
+            let in_order = Some(sequence) == self.next;
+            let expired = now.saturating_duration_since(*arrived) >= self.latency;
+            if !in_order && !expired && self.held.len() <= MAX_HELD_PACKETS {
+                break;
+            }
+            self.next = Some(sequence + 1);
+            if let Some((_, packet)) = self.held.remove(&sequence) {
+                ready.push(packet);
+            }
+        }
+        ready
+    }
+}
+
+/// Extend `sequence` to 64 bits, choosing the value closest to `reference`.
+/// Without a reference, numbering starts high enough never to underflow.
+pub(crate) fn extend_sequence(sequence: u16, reference: Option<u64>) -> u64 {
+    match reference {
+        None => (1 << 32) | sequence as u64,
+        Some(reference) => {
+            let delta = sequence.wrapping_sub(reference as u16) as i16;
+            reference.wrapping_add_signed(delta as i64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::rtp::RtpHeader;
+
+    fn sequence(packet: &[u8]) -> u16 {
+        u16::from_be_bytes([packet[2], packet[3]])
+    }
+
+    fn packets(count: usize) -> Vec<Vec<u8>> {
+        let mut header = RtpHeader::new(96, 1);
+        (0..count).map(|_| header.write(false).to_vec()).collect()
+    }
+
+    #[test]
+    fn reorders_within_latency() {
+        let mut buffer = JitterBuffer::new(Duration::from_millis(50));
+        let now = Instant::now();
+        let sent = packets(4);
+
+        assert_eq!(buffer.push(sent[0].clone(), now).len(), 1);
+        assert!(buffer.push(sent[2].clone(), now).is_empty());
+        assert!(buffer.push(sent[3].clone(), now).is_empty());
+        let ready = buffer.push(sent[1].clone(), now);
+        assert_eq!(
+            ready.iter().map(|p| sequence(p)).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        // Late and duplicate packets are dropped.
+        assert!(buffer.push(sent[1].clone(), now).is_empty());
+        assert_eq!(buffer.dropped(), 1);
+    }
+
+    #[test]
+    fn gives_up_on_gaps_after_latency() {
+        let mut buffer = JitterBuffer::new(Duration::from_millis(50));
+        let now = Instant::now();
+        let sent = packets(3);
+
+        buffer.push(sent[0].clone(), now);
+        assert!(buffer.push(sent[2].clone(), now).is_empty());
+        assert!(buffer.pop_ready(now + Duration::from_millis(10)).is_empty());
+        let ready = buffer.pop_ready(now + Duration::from_millis(50));
+        assert_eq!(ready.iter().map(|p| sequence(p)).collect::<Vec<_>>(), [2]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn follows_wraps_and_restarts() {
+        let mut buffer = JitterBuffer::new(Duration::from_millis(50));
+        let now = Instant::now();
+        let mut sent = packets(2);
+        sent[0][2..4].copy_from_slice(&u16::MAX.to_be_bytes());
+        sent[1][2..4].copy_from_slice(&0u16.to_be_bytes());
+        assert_eq!(buffer.push(sent[0].clone(), now).len(), 1);
+        assert_eq!(buffer.push(sent[1].clone(), now).len(), 1);
+
+        let mut restarted = sent[1].clone();
+        restarted[2..4].copy_from_slice(&20000u16.to_be_bytes());
+        let ready = buffer.push(restarted, now);
+        assert_eq!(
+            ready.iter().map(|p| sequence(p)).collect::<Vec<_>>(),
+            [20000]
+        );
+    }
+}
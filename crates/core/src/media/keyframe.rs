@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+
+/// Default minimum time between keyframe requests passed to the application.
+pub const DEFAULT_KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Why a keyframe was requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyframeReason {
+    /// RTCP Picture Loss Indication (RFC 4585 §6.3.1).
+    PictureLoss,
+    /// RTCP Full Intra Request (RFC 5104 §4.3.1).
+    FullIntraRequest,
+    /// A viewer started playing and needs a decodable picture.
+    NewViewer,
+}
+
+impl KeyframeReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PictureLoss => "pli",
+            Self::FullIntraRequest => "fir",
+            Self::NewViewer => "new-viewer",
+        }
+    }
+}
+
+/// A keyframe request delivered to the application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyframeRequest {
+    pub reason: KeyframeReason,
+    /// Session that triggered the request.
+    pub session_id: String,
+}
+
+type Callback = Box<dyn Fn(&KeyframeRequest) + Send + Sync>;
+
+/// Rate-limited channel of keyframe requests from viewers to the encoder.
+///
+/// Requests arriving faster than `min_interval` are coalesced into one
+/// that is delivered once the interval has passed, so a misbehaving client
+/// cannot force back-to-back IDRs. Applications either register a callback
+/// or poll with [`KeyframeRequests::poll`].
+pub struct KeyframeRequests {
+    min_interval: Duration,
+    state: Mutex<State>,
+    callback: RwLock<Option<Callback>>,
+}
+
+struct State {
+    pending: Option<KeyframeRequest>,
+    last_delivered: Option<Instant>,
+}
+
+impl KeyframeRequests {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            state: Mutex::new(State {
+                pending: None,
+                last_delivered: None,
+            }),
+            callback: RwLock::new(None),
+        }
+    }
+
+    /// Call `callback` for every delivered request, instead of queueing for [`Self::poll`].
+    pub fn set_callback(&self, callback: impl Fn(&KeyframeRequest) + Send + Sync + 'static) {
+        *self.callback.write() = Some(Box::new(callback));
+    }
+
+    /// Record a request; delivered now if a callback is set and the rate limit allows.
+    pub fn request(&self, reason: KeyframeReason, session_id: &str) {
+        tracing::debug!(session_id, reason = reason.as_str(), "keyframe requested");
+        self.state.lock().pending = Some(KeyframeRequest {
+            reason,
+            session_id: session_id.to_string(),
+        });
+        self.dispatch();
+    }
+
+    /// Deliver a deferred request to the callback once the rate limit allows.
+    pub fn dispatch(&self) {
+        let callback = self.callback.read();
+        let Some(callback) = callback.as_ref() else {
+            return;
+        };
+        if let Some(request) = self.take_ready() {
+            callback(&request);
+        }
+    }
+
+    /// Take the pending request if the rate limit allows delivering it now.
+    pub fn poll(&self) -> Option<KeyframeRequest> {
+        self.take_ready()
+    }
+
+    fn take_ready(&self) -> Option<KeyframeRequest> {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        let allowed = state
+            .last_delivered
+            .is_none_or(|last| now.duration_since(last) >= self.min_interval);
+        if !allowed {
+            return None;
+        }
+        let request = state.pending.take()?;
+        state.last_delivered = Some(now);
+        Some(request)
+    }
+}
+
+impl Default for KeyframeRequests {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYFRAME_REQUEST_INTERVAL)
+    }
+}
+
+impl std::fmt::Debug for KeyframeRequests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyframeRequests")
+            .field("min_interval", &self.min_interval)
+            .field("has_callback", &self.callback.read().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn poll_returns_latest_request_once() {
+        let requests = KeyframeRequests::new(Duration::ZERO);
+        assert!(requests.poll().is_none());
+
+        requests.request(KeyframeReason::NewViewer, "a");
+        requests.request(KeyframeReason::PictureLoss, "b");
+        let request = requests.poll().unwrap();
+        assert_eq!(request.reason, KeyframeReason::PictureLoss);
+        assert_eq!(request.session_id, "b");
+        assert!(requests.poll().is_none());
+    }
+
+    #[test]
+    fn rate_limit_defers_instead_of_dropping() {
+        let requests = KeyframeRequests::new(Duration::from_millis(50));
+        requests.request(KeyframeReason::PictureLoss, "a");
+        assert!(requests.poll().is_some());
+
+        requests.request(KeyframeReason::PictureLoss, "a");
+        assert!(requests.poll().is_none());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(requests.poll().is_some());
+    }
+
+    #[test]
+    fn callback_receives_requests() {
+        let requests = KeyframeRequests::new(Duration::from_secs(60));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        requests.set_callback(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        requests.request(KeyframeReason::FullIntraRequest, "a");
+        requests.request(KeyframeReason::FullIntraRequest, "a");
+        requests.dispatch();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
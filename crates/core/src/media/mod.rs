@@ -1,7 +1,10 @@
-pub mod rtp;
 pub mod h264;
 pub mod h265;
+pub mod jitter;
+pub mod keyframe;
 pub mod mjpeg;
+pub mod rewrite;
+pub mod rtp;
 
 /// Codec-specific RTP packetizer trait.
 ///
@@ -24,6 +27,9 @@ pub trait Packetizer: Send {
     /// RTP payload type number.
     fn payload_type(&self) -> u8;
 
+    /// Header state for the next packet, reported to clients in `RTP-Info`.
+    fn rtp_header(&self) -> &rtp::RtpHeader;
+
     /// SDP media-level attributes for this codec (without "a=" prefix).
     /// Example: `vec!["fmtp:96 packetization-mode=1"]`
     fn sdp_attributes(&self) -> Vec<String>;
@@ -0,0 +1,324 @@
+use std::time::Instant;
+
+/// Largest forward sequence jump still treated as loss rather than a
+/// restart of the source (RFC 3550 Appendix A.1).
+const MAX_DROPOUT: u16 = 3000;
+
+/// Largest backward sequence jump still treated as a late packet.
+const MAX_MISORDER: u16 = 100;
+
+/// Rewrites the SSRC and sequence numbers of relayed RTP packets, so each
+/// viewer of a relayed stream sees its own SSRC and a sequence continuing
+/// from the packet the `RTP-Info` header of its PLAY response described.
+///
+/// The sequence offset is fixed by that packet or by the first packet
+/// rewritten, so gaps and reordering in the source stay visible to the
+/// viewer's loss detection.
+#[derive(Debug, Clone)]
+pub struct RtpRewriter {
+    ssrc: u32,
+    first_sequence: u16,
+    sequence_offset: Option<u16>,
+    last_sequence: Option<u16>,
+}
+
+impl RtpRewriter {
+    pub fn new(ssrc: u32, first_sequence: u16) -> Self {
+        Self {
+            ssrc,
+            first_sequence,
+            sequence_offset: None,
+            last_sequence: None,
+        }
+    }
+
+    /// A rewriter with a random SSRC and initial sequence number
+    /// (RFC 3550 §5.1, §8.1).
+    pub fn random() -> Self {
+        Self::new(rand::random(), rand::random())
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Sequence number the next in-order packet will carry.
+    pub fn next_sequence(&self) -> u16 {
+        match self.last_sequence {
+            Some(last) => last.wrapping_add(1),
+            None => self.first_sequence,
+        }
+    }
+
+    /// The sequence number the source's `sequence` maps to. Before any
+    /// packet is rewritten this fixes the offset, so that the packet after
+    /// `sequence` carries the first sequence number.
+    pub fn sequence_for(&mut self, sequence: u16) -> u16 {
+        let first = self.first_sequence.wrapping_sub(1);
+        let offset = *self
+            .sequence_offset
+            .get_or_insert_with(|| first.wrapping_sub(sequence));
+        sequence.wrapping_add(offset)
+    }
+
+    /// Rewrite `packet` in place. Returns `false`, leaving it untouched, if
+    /// it is too short to be RTP.
+    pub fn rewrite(&mut self, packet: &mut [u8]) -> bool {
+        if packet.len() < 12 || packet[0] >> 6 != 2 {
+            return false;
+        }
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let offset = *self
+            .sequence_offset
+            .get_or_insert_with(|| self.first_sequence.wrapping_sub(sequence));
+        let rewritten = sequence.wrapping_add(offset);
+
+        // Only move forward; late packets keep their place in the sequence.
+        let ahead = self
+            .last_sequence
+            .is_none_or(|last| (rewritten.wrapping_sub(last) as i16) > 0);
+        if ahead {
+            self.last_sequence = Some(rewritten);
+        }
+
+        packet[2..4].copy_from_slice(&rewritten.to_be_bytes());
+        packet[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        true
+    }
+}
+
+/// Keeps RTP from an external source continuous across restarts.
+///
+/// Packets leave with one fixed SSRC. While the source keeps its own SSRC
+/// and numbering, sequence numbers and timestamps pass through unchanged.
+/// When it restarts, with a new SSRC or a sequence jump too large to be
+/// loss, both are re-anchored: the sequence carries on from the last packet
+/// and the timestamp advances by the wall-clock time since it was sent.
+#[derive(Debug, Clone)]
+pub struct RtpContinuity {
+    ssrc: u32,
+    clock_rate: u32,
+    anchor: Option<Anchor>,
+    last: Option<Sent>,
+}
+
+/// How the current run of the source maps onto the output.
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    source_ssrc: u32,
+    sequence_offset: u16,
+    timestamp_offset: u32,
+    highest_sequence: u16,
+}
+
+/// The newest packet rewritten.
+#[derive(Debug, Clone, Copy)]
+struct Sent {
+    sequence: u16,
+    timestamp: u32,
+    at: Instant,
+}
+
+impl RtpContinuity {
+    pub fn new(ssrc: u32, clock_rate: u32) -> Self {
+        Self {
+            ssrc,
+            clock_rate,
+            anchor: None,
+            last: None,
+        }
+    }
+
+    /// Continuity with a random output SSRC (RFC 3550 §8.1).
+    pub fn random(clock_rate: u32) -> Self {
+        Self::new(rand::random(), clock_rate)
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Rewrite `packet`, received at `now`, in place. Returns `false`,
+    /// leaving it untouched, if it is too short to be RTP.
+    pub fn rewrite(&mut self, packet: &mut [u8], now: Instant) -> bool {
+        if packet.len() < 12 || packet[0] >> 6 != 2 {
+            return false;
+        }
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let timestamp = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+        let source_ssrc = u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]);
+
+        let anchor = match self.anchor {
+            Some(anchor)
+                if anchor.source_ssrc == source_ssrc
+                    && in_run(anchor.highest_sequence, sequence) =>
+            {
+                anchor
+            }
+            previous => {
+                let anchor = self.reanchor(source_ssrc, sequence, timestamp, now);
+                if previous.is_some() {
+                    tracing::debug!(
+                        ssrc = format_args!("{:#010X}", source_ssrc),
+                        sequence,
+                        "RTP source restarted, re-anchoring"
+                    );
+                }
+                anchor
+            }
+        };
+
+        let rewritten_sequence = sequence.wrapping_add(anchor.sequence_offset);
+        let rewritten_timestamp = timestamp.wrapping_add(anchor.timestamp_offset);
+        let ahead = (sequence.wrapping_sub(anchor.highest_sequence) as i16) >= 0;
+        if ahead {
+            self.anchor = Some(Anchor {
+                highest_sequence: sequence,
+                ..anchor
+            });
+            self.last = Some(Sent {
+                sequence: rewritten_sequence,
+                timestamp: rewritten_timestamp,
+                at: now,
+            });
+        }
+
+        packet[2..4].copy_from_slice(&rewritten_sequence.to_be_bytes());
+        packet[4..8].copy_from_slice(&rewritten_timestamp.to_be_bytes());
+        packet[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        true
+    }
+
+    /// Start a new run of the source at `sequence`/`timestamp`, continuing
+    /// from the last packet sent, if any.
+    fn reanchor(
+        &mut self,
+        source_ssrc: u32,
+        sequence: u16,
+        timestamp: u32,
+        now: Instant,
+    ) -> Anchor {
+        let (sequence_offset, timestamp_offset) = match self.last {
+            Some(last) => {
+                let elapsed = now.saturating_duration_since(last.at);
+                let ticks = (elapsed.as_secs_f64() * self.clock_rate as f64) as u64;
+                let next_timestamp = last.timestamp.wrapping_add(ticks.max(1) as u32);
+                (
+                    last.sequence.wrapping_add(1).wrapping_sub(sequence),
+                    next_timestamp.wrapping_sub(timestamp),
+                )
+            }
+            None => (0, 0),
+        };
+        let anchor = Anchor {
+            source_ssrc,
+            sequence_offset,
+            timestamp_offset,
+            // One behind, so the packet that started the run counts as ahead.
+            highest_sequence: sequence.wrapping_sub(1),
+        };
+        self.anchor = Some(anchor);
+        anchor
+    }
+}
+
+/// Whether `sequence` belongs to the run whose highest sequence number is
+/// `highest`: a forward step short of a dropout, or a slightly late packet.
+fn in_run(highest: u16, sequence: u16) -> bool {
+    let delta = sequence.wrapping_sub(highest);
+    !(MAX_DROPOUT..=u16::MAX - MAX_MISORDER).contains(&delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::rtp::{RtpHeader, RtpPacket};
+    use std::time::Duration;
+
+    fn packets(ssrc: u32, count: usize) -> Vec<Vec<u8>> {
+        let mut header = RtpHeader::new(96, ssrc);
+        (0..count).map(|_| header.write(false).to_vec()).collect()
+    }
+
+    #[test]
+    fn rewrites_ssrc_and_sequence() {
+        let mut rewriter = RtpRewriter::new(0xAABBCCDD, 1000);
+        assert_eq!(rewriter.next_sequence(), 1000);
+
+        let mut sent = packets(0x11111111, 3);
+        // Lose the second packet, then deliver it late.
+        for index in [0, 2, 1] {
+            assert!(rewriter.rewrite(&mut sent[index]));
+        }
+        let rewritten: Vec<RtpPacket> = sent.iter().map(|p| RtpPacket::parse(p).unwrap()).collect();
+        assert_eq!(
+            rewritten.iter().map(|p| p.sequence).collect::<Vec<_>>(),
+            [1000, 1001, 1002]
+        );
+        assert!(rewritten.iter().all(|p| p.ssrc == 0xAABBCCDD));
+        assert_eq!(rewriter.next_sequence(), 1003);
+    }
+
+    #[test]
+    fn reported_sequence_precedes_the_first_packet() {
+        let mut rewriter = RtpRewriter::new(1, 1000);
+        let mut sent = packets(2, 2);
+        let source = RtpPacket::parse(&sent[0]).unwrap().sequence;
+        assert_eq!(rewriter.sequence_for(source), 999);
+
+        assert!(rewriter.rewrite(&mut sent[1]));
+        assert_eq!(RtpPacket::parse(&sent[1]).unwrap().sequence, 1000);
+        assert_eq!(rewriter.sequence_for(source.wrapping_add(1)), 1000);
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        let mut rewriter = RtpRewriter::new(1, u16::MAX);
+        for mut packet in packets(2, 2) {
+            rewriter.rewrite(&mut packet);
+        }
+        assert_eq!(rewriter.next_sequence(), 1);
+        assert!(!rewriter.rewrite(&mut [0x80, 96]));
+    }
+
+    #[test]
+    fn continuity_passes_through_a_steady_source() {
+        let mut continuity = RtpContinuity::new(0xAABBCCDD, 90000);
+        let mut header = RtpHeader::new(96, 0x11111111);
+        header.advance_timestamp(5000);
+        let mut packet = header.write(false).to_vec();
+        assert!(continuity.rewrite(&mut packet, Instant::now()));
+
+        let rewritten = RtpPacket::parse(&packet).unwrap();
+        assert_eq!((rewritten.sequence, rewritten.timestamp), (0, 5000));
+        assert_eq!(rewritten.ssrc, 0xAABBCCDD);
+    }
+
+    #[test]
+    fn continuity_survives_source_restart() {
+        let mut continuity = RtpContinuity::new(0xAABBCCDD, 90000);
+        let start = Instant::now();
+
+        let mut first = RtpHeader::new(96, 0x11111111);
+        first.advance_timestamp(1_000_000);
+        for _ in 0..3 {
+            let mut packet = first.write(false).to_vec();
+            continuity.rewrite(&mut packet, start);
+        }
+
+        // The encoder restarts with a new SSRC and numbering 100 ms later.
+        let mut second = RtpHeader::new(96, 0x22222222);
+        let mut packet = second.write(false).to_vec();
+        continuity.rewrite(&mut packet, start + Duration::from_millis(100));
+        let rewritten = RtpPacket::parse(&packet).unwrap();
+        assert_eq!(rewritten.sequence, 3);
+        assert_eq!(rewritten.timestamp, 1_000_000 + 9000);
+        assert_eq!(rewritten.ssrc, 0xAABBCCDD);
+
+        // A sequence jump beyond the dropout limit is a restart too.
+        let mut packet = second.write(false).to_vec();
+        packet[2..4].copy_from_slice(&40000u16.to_be_bytes());
+        continuity.rewrite(&mut packet, start + Duration::from_millis(100));
+        assert_eq!(RtpPacket::parse(&packet).unwrap().sequence, 4);
+    }
+}
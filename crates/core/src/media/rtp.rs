@@ -59,6 +59,57 @@ impl RtpHeader {
     }
 }
 
+/// Read-only view of a received or already-built RTP packet (RFC 3550 §5.1).
+#[derive(Debug, Clone, Copy)]
+pub struct RtpPacket<'a> {
+    pub marker: bool,
+    pub pt: u8,
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    /// Payload with CSRCs, header extension and padding stripped.
+    pub payload: &'a [u8],
+}
+
+impl<'a> RtpPacket<'a> {
+    /// Parse the fixed header, CSRC list, extension and padding.
+    /// Returns `None` for anything that is not a well-formed version 2 packet.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < 12 || data[0] >> 6 != 2 {
+            return None;
+        }
+
+        let padding = data[0] & 0x20 != 0;
+        let extension = data[0] & 0x10 != 0;
+        let csrc_count = (data[0] & 0x0f) as usize;
+
+        let mut offset = 12 + csrc_count * 4;
+        if extension {
+            let ext = data.get(offset..offset + 4)?;
+            let words = u16::from_be_bytes([ext[2], ext[3]]) as usize;
+            offset += 4 + words * 4;
+        }
+
+        let mut end = data.len();
+        if padding {
+            let pad = *data.last()? as usize;
+            end = end.checked_sub(pad)?;
+        }
+        if offset > end {
+            return None;
+        }
+
+        Some(Self {
+            marker: data[1] & 0x80 != 0,
+            pt: data[1] & 0x7f,
+            sequence: u16::from_be_bytes([data[2], data[3]]),
+            timestamp: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            ssrc: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+            payload: &data[offset..end],
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,4 +185,26 @@ mod tests {
         let h2 = RtpHeader::with_random_ssrc(96);
         assert_ne!(h1.ssrc, h2.ssrc);
     }
+
+    #[test]
+    fn parse_roundtrip() {
+        let mut h = make_header();
+        h.advance_timestamp(3000);
+        let mut packet = h.write(true).to_vec();
+        packet.extend_from_slice(&[1, 2, 3]);
+
+        let parsed = RtpPacket::parse(&packet).unwrap();
+        assert!(parsed.marker);
+        assert_eq!(parsed.pt, 96);
+        assert_eq!(parsed.sequence, 0);
+        assert_eq!(parsed.timestamp, 3000);
+        assert_eq!(parsed.ssrc, 0xAABBCCDD);
+        assert_eq!(parsed.payload, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn parse_rejects_short_or_wrong_version() {
+        assert!(RtpPacket::parse(&[0x80; 11]).is_none());
+        assert!(RtpPacket::parse(&[0x40; 12]).is_none());
+    }
 }
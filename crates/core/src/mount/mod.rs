@@ -0,0 +1,516 @@
+//! Mounts: streams served at a path whose media arrives as ready-made RTP,
+//! such as an encoder publishing with ANNOUNCE and RECORD, packets the
+//! application pushes through an [`RtpSource`], or a stream relayed from
+//! another RTSP server.
+//!
+//! Each mount keeps the session description its source announced and
+//! relays every packet it receives to the viewers playing that path,
+//! rewriting SSRC and sequence numbers per viewer.
+
+mod source;
+
+pub use source::{RtpCodec, RtpSource};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::error::{Result, RtspError};
+use crate::media::rewrite::RtpRewriter;
+use crate::media::rtp::RtpPacket;
+use crate::protocol::sdp::{MediaDescription, SessionDescription};
+use crate::session::Session;
+use crate::transport::udp;
+
+/// Who may publish streams with ANNOUNCE and RECORD.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PublishPolicy {
+    /// Publishing is refused with 403 Forbidden.
+    #[default]
+    Disabled,
+    /// Clients may publish to any path that isn't already in use.
+    Any,
+    /// Clients may publish only to these paths, e.g. `live/cam1`.
+    Paths(Vec<String>),
+}
+
+impl PublishPolicy {
+    pub fn allows(&self, path: &str) -> bool {
+        match self {
+            Self::Disabled => false,
+            Self::Any => true,
+            Self::Paths(paths) => paths.iter().any(|p| p.trim_matches('/') == path),
+        }
+    }
+}
+
+/// Where a mount's packets come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MountSource {
+    /// Published by the RTSP client on this connection with ANNOUNCE and
+    /// RECORD.
+    Record { connection_id: u64 },
+    /// Pushed by the application through an [`RtpSource`].
+    Application,
+    /// Pulled from an upstream RTSP server at `url`.
+    Relay { url: String },
+}
+
+/// One media stream of a mount.
+#[derive(Debug, Clone)]
+pub struct MountTrack {
+    /// Control path relative to the mount, e.g. `streamid=0`. Empty when the
+    /// source gave none, in which case the mount URL itself selects it.
+    pub control: String,
+    pub media: MediaDescription,
+}
+
+/// A stream served at `path`, fed by its [`MountSource`].
+#[derive(Debug)]
+pub struct Mount {
+    path: String,
+    sdp: String,
+    tracks: Vec<MountTrack>,
+    source: MountSource,
+    created: Instant,
+    closed: AtomicBool,
+    state: Mutex<MountState>,
+}
+
+#[derive(Debug)]
+struct MountState {
+    /// Session delivering the media, once the source has set one up.
+    publisher: Option<String>,
+    outputs: Vec<Output>,
+    /// Sequence number and RTP timestamp of the latest packet per track.
+    last_packets: Vec<Option<(u16, u32)>>,
+}
+
+/// A viewer session receiving one track of the mount.
+#[derive(Debug)]
+struct Output {
+    session: Arc<Session>,
+    track: usize,
+    rewriter: RtpRewriter,
+}
+
+impl Mount {
+    /// A mount serving the streams described by `sdp`.
+    pub fn new(path: &str, sdp: &str, source: MountSource) -> Result<Self> {
+        let path = path.trim_matches('/').to_string();
+        let description = SessionDescription::parse(sdp)?;
+        let tracks: Vec<MountTrack> = description
+            .media
+            .into_iter()
+            .map(|media| MountTrack {
+                control: relative_control(&path, media.control().unwrap_or_default()),
+                media,
+            })
+            .collect();
+
+        Ok(Self {
+            state: Mutex::new(MountState {
+                publisher: None,
+                outputs: Vec::new(),
+                last_packets: vec![None; tracks.len()],
+            }),
+            path,
+            sdp: sdp.to_string(),
+            tracks,
+            source,
+            created: Instant::now(),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The session description served to viewers on DESCRIBE.
+    pub fn sdp(&self) -> &str {
+        &self.sdp
+    }
+
+    pub fn tracks(&self) -> &[MountTrack] {
+        &self.tracks
+    }
+
+    pub fn source(&self) -> &MountSource {
+        &self.source
+    }
+
+    /// Time since the mount was created, reported to viewers as NPT.
+    pub fn age(&self) -> Duration {
+        self.created.elapsed()
+    }
+
+    /// Whether the mount was removed; its receivers stop once it is.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    /// Index of the track with control path `control`.
+    pub fn track_for_control(&self, control: &str) -> Option<usize> {
+        let control = control.trim_matches('/');
+        self.tracks.iter().position(|t| t.control == control)
+    }
+
+    /// Session delivering this mount's media.
+    pub fn publisher(&self) -> Option<String> {
+        self.state.lock().publisher.clone()
+    }
+
+    pub fn set_publisher(&self, session_id: &str) {
+        self.state.lock().publisher = Some(session_id.to_string());
+    }
+
+    /// Start relaying `track` to `session` once it plays, replacing any
+    /// track it received before.
+    pub fn add_output(&self, session: Arc<Session>, track: usize) {
+        let mut state = self.state.lock();
+        state.outputs.retain(|o| o.session.id != session.id);
+        tracing::debug!(path = %self.path, session_id = %session.id, track, "viewer attached to mount");
+        state.outputs.push(Output {
+            session,
+            track,
+            rewriter: RtpRewriter::random(),
+        });
+    }
+
+    /// Stop relaying to `session_id`. Returns `false` if it wasn't a viewer.
+    pub fn remove_output(&self, session_id: &str) -> bool {
+        let mut state = self.state.lock();
+        let before = state.outputs.len();
+        state.outputs.retain(|o| o.session.id != session_id);
+        state.outputs.len() != before
+    }
+
+    /// Track relayed to `session_id`.
+    pub fn output_track(&self, session_id: &str) -> Option<usize> {
+        let state = self.state.lock();
+        state
+            .outputs
+            .iter()
+            .find(|o| o.session.id == session_id)
+            .map(|o| o.track)
+    }
+
+    /// Sequence number and RTP timestamp of the latest packet of the viewer
+    /// `session_id`'s track, as numbered for that viewer, for `RTP-Info`.
+    /// Before the track's first packet, the sequence number the viewer will
+    /// start at and no timestamp.
+    pub fn output_position(&self, session_id: &str) -> Option<(u16, Option<u32>)> {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+        let output = state
+            .outputs
+            .iter_mut()
+            .find(|o| o.session.id == session_id)?;
+        Some(match state.last_packets[output.track] {
+            Some((sequence, timestamp)) => {
+                (output.rewriter.sequence_for(sequence), Some(timestamp))
+            }
+            None => (output.rewriter.next_sequence(), None),
+        })
+    }
+
+    /// Sessions receiving this mount.
+    pub fn viewer_ids(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .outputs
+            .iter()
+            .map(|o| o.session.id.clone())
+            .collect()
+    }
+
+    /// Relay one RTP packet of `track` to every playing viewer of it.
+    /// Returns the number of viewers it was sent to.
+    pub fn forward(&self, track: usize, packet: &[u8]) -> usize {
+        let Some(rtp) = RtpPacket::parse(packet) else {
+            tracing::trace!(path = %self.path, len = packet.len(), "dropping non-RTP packet");
+            return 0;
+        };
+        let mut state = self.state.lock();
+        let Some(last_packet) = state.last_packets.get_mut(track) else {
+            return 0;
+        };
+        *last_packet = Some((rtp.sequence, rtp.timestamp));
+
+        let mut sent = 0;
+        let mut buf = Vec::with_capacity(packet.len());
+        for output in state.outputs.iter_mut().filter(|o| o.track == track) {
+            if !output.session.is_playing() {
+                continue;
+            }
+            buf.clear();
+            buf.extend_from_slice(packet);
+            output.rewriter.rewrite(&mut buf);
+            match udp::send_rtp(&output.session, &buf) {
+                Ok(_) => sent += 1,
+                Err(e) => tracing::debug!(
+                    path = %self.path,
+                    session_id = %output.session.id,
+                    error = %e,
+                    "failed to relay RTP packet"
+                ),
+            }
+        }
+        sent
+    }
+}
+
+/// Mounts by path, and the policy deciding which paths clients may publish.
+#[derive(Debug, Default)]
+pub struct MountRegistry {
+    mounts: RwLock<HashMap<String, Arc<Mount>>>,
+    policy: RwLock<PublishPolicy>,
+}
+
+impl MountRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_publish_policy(&self, policy: PublishPolicy) {
+        *self.policy.write() = policy;
+    }
+
+    pub fn publish_policy(&self) -> PublishPolicy {
+        self.policy.read().clone()
+    }
+
+    /// Whether clients may publish to `path`.
+    pub fn may_publish(&self, path: &str) -> bool {
+        self.policy.read().allows(path)
+    }
+
+    /// Add `mount`, unless its path is already in use.
+    pub fn insert(&self, mount: Mount) -> Result<Arc<Mount>> {
+        let mut mounts = self.mounts.write();
+        if mounts.contains_key(mount.path()) {
+            return Err(RtspError::MountInUse(mount.path().to_string()));
+        }
+        let mount = Arc::new(mount);
+        mounts.insert(mount.path().to_string(), mount.clone());
+        tracing::info!(path = %mount.path(), tracks = mount.tracks().len(), "mount created");
+        Ok(mount)
+    }
+
+    pub fn get(&self, path: &str) -> Option<Arc<Mount>> {
+        self.mounts.read().get(path.trim_matches('/')).cloned()
+    }
+
+    /// Remove and close the mount at `path`.
+    pub fn remove(&self, path: &str) -> Option<Arc<Mount>> {
+        let mount = self.mounts.write().remove(path.trim_matches('/'))?;
+        mount.close();
+        tracing::info!(path = %mount.path(), "mount removed");
+        Some(mount)
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.mounts.read().keys().cloned().collect()
+    }
+
+    /// The mount a request URI refers to, with the track it selects;
+    /// `None` for the mount's aggregate URL.
+    pub fn resolve(&self, uri: &str) -> Option<(Arc<Mount>, Option<usize>)> {
+        let path = path_of(uri);
+        let mounts = self.mounts.read();
+        if let Some(mount) = mounts.get(path.as_ref()) {
+            return Some((mount.clone(), None));
+        }
+        // A track URL: the longest mount path followed by a control path.
+        let (mount, control) = mounts
+            .values()
+            .filter_map(|m| {
+                let control = path.strip_prefix(m.path())?.strip_prefix('/')?;
+                Some((m, control))
+            })
+            .max_by_key(|(m, _)| m.path().len())?;
+        let track = mount.track_for_control(control)?;
+        Some((mount.clone(), Some(track)))
+    }
+}
+
+/// The path of a request URI without scheme, authority, query or
+/// surrounding slashes: `rtsp://host:8554/live/cam1/?token=x` → `live/cam1`.
+/// Some clients append track controls after the query of the URL they
+/// were given, as in `rtsp://host/cam1?token=x/track1`; the control is
+/// kept.
+pub fn path_of(uri: &str) -> Cow<'_, str> {
+    let path = match uri.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+        None => uri,
+    };
+    match path.split_once('?') {
+        Some((path, query)) => match query.split_once('/') {
+            Some((_, control)) => {
+                Cow::Owned(format!("{path}/{control}").trim_matches('/').to_string())
+            }
+            None => Cow::Borrowed(path.trim_matches('/')),
+        },
+        None => Cow::Borrowed(path.trim_matches('/')),
+    }
+}
+
+/// The query of a request URI without the leading `?`, or empty.
+pub fn query_of(uri: &str) -> &str {
+    uri.split_once('?').map_or("", |(_, query)| {
+        query.split_once('/').map_or(query, |(query, _)| query)
+    })
+}
+
+/// `uri` up to its query, e.g. for a `Content-Base` that track controls
+/// can be appended to.
+pub fn without_query(uri: &str) -> &str {
+    uri.split_once('?').map_or(uri, |(uri, _)| uri)
+}
+
+/// A track's `a=control` value relative to the mount at `path`. Absolute
+/// URLs are reduced to the part after the mount path, `*` to empty.
+fn relative_control(path: &str, control: &str) -> String {
+    let full;
+    let control = if control.contains("://") {
+        full = path_of(control);
+        full.strip_prefix(path).unwrap_or(&full)
+    } else {
+        control
+    };
+    match control.trim_matches('/') {
+        "*" => String::new(),
+        control => control.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=No Name\r\nt=0 0\r\n\
+                       m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:streamid=0\r\n\
+                       m=audio 0 RTP/AVP 0\r\na=control:rtsp://10.0.0.5/live/cam1/streamid=1\r\n";
+
+    fn registry() -> MountRegistry {
+        let registry = MountRegistry::new();
+        let source = MountSource::Record { connection_id: 1 };
+        registry
+            .insert(Mount::new("/live/cam1", SDP, source).unwrap())
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn paths_of_uris() {
+        assert_eq!(path_of("rtsp://host:8554/live/cam1/"), "live/cam1");
+        assert_eq!(path_of("rtsp://host:8554"), "");
+        assert_eq!(path_of("/live/cam1"), "live/cam1");
+        assert_eq!(path_of("rtsp://host/live/cam1?token=x&exp=1"), "live/cam1");
+        assert_eq!(
+            path_of("rtsp://host/live/cam1?token=x&exp=1/track2"),
+            "live/cam1/track2"
+        );
+        assert_eq!(
+            query_of("rtsp://host/live/cam1?token=x&exp=1/track2"),
+            "token=x&exp=1"
+        );
+        assert_eq!(query_of("rtsp://host/live/cam1"), "");
+    }
+
+    #[test]
+    fn resolves_mount_and_track_urls() {
+        let registry = registry();
+        let (mount, track) = registry.resolve("rtsp://server/live/cam1").unwrap();
+        assert_eq!(mount.path(), "live/cam1");
+        assert_eq!(track, None);
+
+        assert_eq!(
+            registry
+                .resolve("rtsp://server/live/cam1/streamid=0")
+                .unwrap()
+                .1,
+            Some(0)
+        );
+        assert_eq!(
+            registry
+                .resolve("rtsp://server/live/cam1/streamid=1")
+                .unwrap()
+                .1,
+            Some(1)
+        );
+        assert!(
+            registry
+                .resolve("rtsp://server/live/cam1/streamid=2")
+                .is_none()
+        );
+        assert!(registry.resolve("rtsp://server/live/cam2").is_none());
+    }
+
+    #[test]
+    fn paths_are_unique_until_removed() {
+        let registry = registry();
+        let source = MountSource::Record { connection_id: 2 };
+        let again = Mount::new("live/cam1", SDP, source.clone()).unwrap();
+        assert!(matches!(
+            registry.insert(again),
+            Err(RtspError::MountInUse(_))
+        ));
+
+        let removed = registry.remove("live/cam1").unwrap();
+        assert!(removed.is_closed());
+        assert!(
+            registry
+                .insert(Mount::new("live/cam1", SDP, source).unwrap())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn publish_policy() {
+        assert!(!PublishPolicy::Disabled.allows("live/cam1"));
+        assert!(PublishPolicy::Any.allows("live/cam1"));
+        let paths = PublishPolicy::Paths(vec!["/live/cam1".into()]);
+        assert!(paths.allows("live/cam1"));
+        assert!(!paths.allows("live/cam2"));
+    }
+
+    #[test]
+    fn outputs_track_position() {
+        let mount = Mount::new("live", SDP, MountSource::Record { connection_id: 1 }).unwrap();
+        let session = Arc::new(Session::new("rtsp://server/live/streamid=0"));
+        mount.add_output(session.clone(), 0);
+        assert_eq!(mount.output_track(&session.id), Some(0));
+
+        let (_, rtptime) = mount.output_position(&session.id).unwrap();
+        assert_eq!(rtptime, None);
+        let mut header = crate::media::rtp::RtpHeader::new(96, 7);
+        header.advance_timestamp(3000);
+        // Not playing yet: the packet is tracked but nothing is sent.
+        let packet = header.write(true);
+        assert_eq!(mount.forward(0, &packet), 0);
+        let (seq, rtptime) = mount.output_position(&session.id).unwrap();
+        assert_eq!(rtptime, Some(3000));
+
+        // The viewer's first packet follows the one RTP-Info described.
+        let mut rewriter = mount.state.lock().outputs[0].rewriter.clone();
+        let mut next = header.write(false).to_vec();
+        rewriter.rewrite(&mut next);
+        assert_eq!(
+            crate::media::rtp::RtpPacket::parse(&next).unwrap().sequence,
+            seq.wrapping_add(1)
+        );
+
+        assert!(mount.remove_output(&session.id));
+        assert!(mount.viewer_ids().is_empty());
+    }
+}
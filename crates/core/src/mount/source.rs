@@ -0,0 +1,340 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::error::{ParseErrorKind, Result, RtspError};
+use crate::media::rewrite::RtpContinuity;
+use crate::media::rtp::RtpPacket;
+use crate::mount::{Mount, MountSource};
+use crate::protocol::sdp;
+use crate::session::SessionManager;
+
+/// Codec parameters of a pre-packetized stream, for sources that have no
+/// SDP of their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpCodec {
+    /// Media type, e.g. `video` or `audio`.
+    pub media: String,
+    pub payload_type: u8,
+    /// Encoding name as in `a=rtpmap`, e.g. `H264`.
+    pub encoding: String,
+    pub clock_rate: u32,
+    pub channels: Option<u8>,
+    /// Value of `a=fmtp` after the payload type, e.g.
+    /// `packetization-mode=1;sprop-parameter-sets=...`.
+    pub fmtp: Option<String>,
+}
+
+impl RtpCodec {
+    /// Codec parameters implied by `payload_type` alone: the static
+    /// assignments of RFC 3551 §6. `None` for dynamic types, whose codec
+    /// must be named with [`Self::with_encoding`], and for static types
+    /// this server can't name.
+    pub fn from_payload_type(payload_type: u8) -> Option<Self> {
+        let (media, encoding, clock_rate, channels) = match payload_type {
+            0 => ("audio", "PCMU", 8000, Some(1)),
+            8 => ("audio", "PCMA", 8000, Some(1)),
+            9 => ("audio", "G722", 8000, Some(1)),
+            10 => ("audio", "L16", 44100, Some(2)),
+            11 => ("audio", "L16", 44100, Some(1)),
+            14 => ("audio", "MPA", 90000, None),
+            26 => ("video", "JPEG", 90000, None),
+            32 => ("video", "MPV", 90000, None),
+            33 => ("video", "MP2T", 90000, None),
+            _ => return None,
+        };
+        Some(Self {
+            media: media.to_string(),
+            payload_type,
+            encoding: encoding.to_string(),
+            clock_rate,
+            channels,
+            fmtp: None,
+        })
+    }
+
+    /// Codec parameters for `payload_type` carrying `encoding`, written as
+    /// in `a=rtpmap`: `NAME[/CLOCK[/CHANNELS]]`, e.g. `H264` or
+    /// `opus/48000/2`. The clock rate may be left out for H.264, H.265,
+    /// VP8, VP9, AV1 and Opus. `None` if it's missing for other encodings
+    /// or the rates don't parse.
+    pub fn with_encoding(payload_type: u8, encoding: &str) -> Option<Self> {
+        let mut parts = encoding.trim().split('/');
+        let name = parts.next().filter(|name| !name.is_empty())?;
+        let known = match name.to_ascii_uppercase().as_str() {
+            "H264" | "H265" | "VP8" | "VP9" | "AV1" => Some(("video", 90000, None)),
+            "OPUS" => Some(("audio", 48000, Some(2))),
+            _ => None,
+        };
+        let clock_rate = match parts.next() {
+            Some(rate) => rate.parse().ok()?,
+            None => known?.1,
+        };
+        let channels = match parts.next() {
+            Some(channels) => Some(channels.parse().ok()?),
+            None => known.and_then(|(_, _, channels)| channels),
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        let media = match known {
+            Some((media, _, _)) => media,
+            None if channels.is_some() || clock_rate != 90000 => "audio",
+            None => "video",
+        };
+        Some(Self {
+            media: media.to_string(),
+            payload_type,
+            encoding: name.to_string(),
+            clock_rate,
+            channels,
+            fmtp: name
+                .eq_ignore_ascii_case("H264")
+                .then(|| "packetization-mode=1".to_string()),
+        })
+    }
+
+    /// The `m=` section describing this stream, to pass as the SDP of an
+    /// [`RtpSource`].
+    pub fn media_description(&self) -> String {
+        let pt = self.payload_type;
+        let mut sdp = format!("m={} 0 RTP/AVP {pt}\r\n", self.media);
+        match self.channels {
+            Some(channels) => sdp.push_str(&format!(
+                "a=rtpmap:{pt} {}/{}/{channels}\r\n",
+                self.encoding, self.clock_rate
+            )),
+            None => sdp.push_str(&format!(
+                "a=rtpmap:{pt} {}/{}\r\n",
+                self.encoding, self.clock_rate
+            )),
+        }
+        if let Some(fmtp) = &self.fmtp {
+            sdp.push_str(&format!("a=fmtp:{pt} {fmtp}\r\n"));
+        }
+        sdp
+    }
+}
+
+/// A mount fed with RTP packets the application already formed, e.g. by
+/// `rtph264pay` or an encoder with RTP output.
+///
+/// Packets are checked against the payload types of the mount's SDP and
+/// routed to the track carrying theirs. Each track leaves with one SSRC and
+/// continuous sequence numbers and timestamps, even when the source behind
+/// it restarts. Dropping the source removes the mount and ends its viewers'
+/// sessions.
+pub struct RtpSource {
+    mount: Arc<Mount>,
+    tracks: Vec<SourceTrack>,
+    session_manager: SessionManager,
+}
+
+#[derive(Debug)]
+struct SourceTrack {
+    payload_types: Vec<u8>,
+    continuity: Mutex<RtpContinuity>,
+}
+
+impl RtpSource {
+    /// Serve the streams described by `sdp` at `path`, fed by the
+    /// application. `sdp` may be a full description or just its `m=`
+    /// sections (see [`sdp::complete_fragment`]).
+    ///
+    /// Fails if a track lacks RTP payload types or a clock rate for them,
+    /// or if `path` is already in use.
+    pub(crate) fn new(path: &str, sdp: &str, session_manager: SessionManager) -> Result<Self> {
+        Self::with_source(path, sdp, MountSource::Application, session_manager)
+    }
+
+    /// Like [`RtpSource::new`], for a mount fed by `source` on the
+    /// application's behalf.
+    pub(crate) fn with_source(
+        path: &str,
+        sdp: &str,
+        source: MountSource,
+        session_manager: SessionManager,
+    ) -> Result<Self> {
+        let invalid = || RtspError::Parse {
+            kind: ParseErrorKind::InvalidSdp,
+        };
+        let mount = Mount::new(path, &sdp::complete_fragment(sdp), source)?;
+        let tracks = mount
+            .tracks()
+            .iter()
+            .map(|track| {
+                let payload_types: Vec<u8> = track
+                    .media
+                    .formats
+                    .iter()
+                    .map(|f| f.parse().map_err(|_| invalid()))
+                    .collect::<Result<_>>()?;
+                let clock_rate = payload_types
+                    .first()
+                    .and_then(|&pt| track.media.clock_rate(pt))
+                    .ok_or_else(invalid)?;
+                Ok(SourceTrack {
+                    payload_types,
+                    continuity: Mutex::new(RtpContinuity::random(clock_rate)),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let mount = session_manager.mounts().insert(mount)?;
+        Ok(Self {
+            mount,
+            tracks,
+            session_manager,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        self.mount.path()
+    }
+
+    pub(crate) fn mount(&self) -> &Arc<Mount> {
+        &self.mount
+    }
+
+    /// Relay one RTP packet to the viewers of the track carrying its
+    /// payload type. Returns the number of viewers it was sent to.
+    ///
+    /// Fails with [`RtspError::InvalidRtpPacket`] for malformed packets and
+    /// payload types the SDP doesn't list, and with
+    /// [`RtspError::MountNotFound`] once the mount was removed.
+    pub fn push(&self, packet: &[u8]) -> Result<usize> {
+        if self.mount.is_closed() {
+            return Err(RtspError::MountNotFound(self.mount.path().to_string()));
+        }
+        let rtp = RtpPacket::parse(packet).ok_or(RtspError::InvalidRtpPacket)?;
+        let Some(track) = self
+            .tracks
+            .iter()
+            .position(|t| t.payload_types.contains(&rtp.pt))
+        else {
+            tracing::debug!(path = %self.mount.path(), pt = rtp.pt, "RTP payload type not in SDP");
+            return Err(RtspError::InvalidRtpPacket);
+        };
+        self.push_to(track, packet)
+    }
+
+    /// Relay one RTP packet to the viewers of `track`, whatever its
+    /// payload type, for sources that already know the track.
+    pub(crate) fn push_to(&self, track: usize, packet: &[u8]) -> Result<usize> {
+        if self.mount.is_closed() {
+            return Err(RtspError::MountNotFound(self.mount.path().to_string()));
+        }
+        if track >= self.tracks.len() || RtpPacket::parse(packet).is_none() {
+            return Err(RtspError::InvalidRtpPacket);
+        }
+
+        let mut packet = packet.to_vec();
+        self.tracks[track]
+            .continuity
+            .lock()
+            .rewrite(&mut packet, Instant::now());
+        Ok(self.mount.forward(track, &packet))
+    }
+}
+
+impl std::fmt::Debug for RtpSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RtpSource")
+            .field("mount", &self.mount)
+            .field("tracks", &self.tracks)
+            .finish()
+    }
+}
+
+impl Drop for RtpSource {
+    fn drop(&mut self) {
+        if !self.mount.is_closed() {
+            self.session_manager.remove_mount(self.mount.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::rtp::RtpHeader;
+
+    fn source(manager: &SessionManager, sdp: &str) -> Result<RtpSource> {
+        RtpSource::new("cam", sdp, manager.clone())
+    }
+
+    #[test]
+    fn codec_parameters_describe_the_stream() {
+        let codec = RtpCodec {
+            media: "audio".into(),
+            payload_type: 97,
+            encoding: "opus".into(),
+            clock_rate: 48000,
+            channels: Some(2),
+            fmtp: Some("stereo=1".into()),
+        };
+        assert_eq!(
+            codec.media_description(),
+            "m=audio 0 RTP/AVP 97\r\na=rtpmap:97 opus/48000/2\r\na=fmtp:97 stereo=1\r\n"
+        );
+
+        let pcma = RtpCodec::from_payload_type(8).unwrap();
+        assert_eq!((pcma.encoding.as_str(), pcma.clock_rate), ("PCMA", 8000));
+        assert!(RtpCodec::from_payload_type(96).is_none());
+        assert!(RtpCodec::from_payload_type(72).is_none());
+
+        let h264 = RtpCodec::with_encoding(96, "H264").unwrap();
+        assert_eq!((h264.media.as_str(), h264.clock_rate), ("video", 90000));
+        assert_eq!(h264.fmtp.as_deref(), Some("packetization-mode=1"));
+        let opus = RtpCodec::with_encoding(111, "opus").unwrap();
+        assert_eq!((opus.clock_rate, opus.channels), (48000, Some(2)));
+        let aac = RtpCodec::with_encoding(97, "MPEG4-GENERIC/44100/2").unwrap();
+        assert_eq!(aac.media, "audio");
+        assert!(RtpCodec::with_encoding(97, "MPEG4-GENERIC").is_none());
+        assert!(RtpCodec::with_encoding(97, "H264/fast").is_none());
+
+        let manager = SessionManager::new();
+        let audio = source(&manager, &codec.media_description()).unwrap();
+        assert!(matches!(
+            source(&manager, "m=audio 0 RTP/AVP 0\r\n"),
+            Err(RtspError::MountInUse(_))
+        ));
+        drop(audio);
+        // A dynamic payload type needs an rtpmap for its clock rate.
+        assert!(source(&manager, "m=video 0 RTP/AVP 96\r\n").is_err());
+        assert!(manager.mounts().get("cam").is_none());
+    }
+
+    #[test]
+    fn validates_packets() {
+        let manager = SessionManager::new();
+        let source = source(
+            &manager,
+            "m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n",
+        )
+        .unwrap();
+
+        let mut header = RtpHeader::new(96, 1);
+        assert_eq!(source.push(&header.write(true)).unwrap(), 0);
+        assert!(matches!(
+            source.push(&[0x80, 96, 0]),
+            Err(RtspError::InvalidRtpPacket)
+        ));
+        let mut other = RtpHeader::new(8, 1);
+        assert!(matches!(
+            source.push(&other.write(true)),
+            Err(RtspError::InvalidRtpPacket)
+        ));
+    }
+
+    #[test]
+    fn dropping_the_source_removes_the_mount() {
+        let manager = SessionManager::new();
+        let source = source(&manager, "m=audio 0 RTP/AVP 0\r\n").unwrap();
+        assert_eq!(source.path(), "cam");
+        assert!(manager.mounts().get("cam").is_some());
+        drop(source);
+        assert!(manager.mounts().get("cam").is_none());
+    }
+}
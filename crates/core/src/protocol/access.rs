@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use parking_lot::RwLock;
+
+use crate::error::RtspError;
+use crate::protocol::method::Method;
+use crate::protocol::token::{self, TokenError, UrlSigner};
+
+/// An IP network in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`. A bare
+/// address is a network of one host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, RtspError> {
+        let addr = addr.to_canonical();
+        if prefix > max_prefix(addr) {
+            return Err(RtspError::InvalidNetwork(format!("{addr}/{prefix}")));
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether `ip` is in this network. IPv4-mapped IPv6 addresses, as
+    /// reported by dual-stack sockets, match IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    if addr.is_ipv4() { 32 } else { 128 }
+}
+
+impl FromStr for IpNet {
+    type Err = RtspError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RtspError::InvalidNetwork(s.to_string());
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None => max_prefix(addr.to_canonical()),
+        };
+        Self::new(addr, prefix)
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Who an [`AccessRule`] lets through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// Every client, authenticated or not.
+    Anyone,
+    /// Every authenticated user.
+    Authenticated,
+    User(String),
+    /// Members of a role in [`AccessPolicy::roles`].
+    Role(String),
+    /// Clients presenting a valid signed URL for the mount.
+    TokenHolder,
+}
+
+impl Principal {
+    /// Parse the short form used on the command line: `*` for anyone, `+`
+    /// for any authenticated user, `~` for holders of a signed URL, `@name`
+    /// for a role, else a user name.
+    pub fn parse(s: &str) -> Self {
+        match s.trim() {
+            "*" => Self::Anyone,
+            "+" => Self::Authenticated,
+            "~" => Self::TokenHolder,
+            s => match s.strip_prefix('@') {
+                Some(role) => Self::Role(role.to_string()),
+                None => Self::User(s.to_string()),
+            },
+        }
+    }
+}
+
+/// Who may use which methods on the mounts matching a path pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessRule {
+    /// A mount path such as `lobby`, a prefix such as `cams/*`, or `*` for
+    /// every path.
+    pub path: String,
+    /// Methods the rule applies to; empty for all of them.
+    pub methods: Vec<Method>,
+    pub allow: Vec<Principal>,
+}
+
+impl AccessRule {
+    /// A rule for every method on `path` that lets nobody through until
+    /// principals are added with [`Self::allow`].
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.trim_matches('/').to_string(),
+            methods: Vec::new(),
+            allow: Vec::new(),
+        }
+    }
+
+    /// Restrict the rule to `methods`.
+    pub fn methods(mut self, methods: &[Method]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    pub fn allow(mut self, principal: Principal) -> Self {
+        self.allow.push(principal);
+        self
+    }
+
+    pub fn matches(&self, path: &str, method: &Method) -> bool {
+        let path_matches = match self.path.as_str() {
+            "*" => true,
+            pattern => match pattern.strip_suffix("/*") {
+                Some(prefix) => path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/')),
+                None => path == pattern,
+            },
+        };
+        path_matches && (self.methods.is_empty() || self.methods.contains(method))
+    }
+}
+
+/// Who may connect, and who may do what on which mount.
+///
+/// Connections from `deny` networks, or from outside non-empty `allow`
+/// networks, are refused when accepted, before any request is read. Each
+/// request is then checked against the first rule matching its mount path
+/// and method: principals it lists get through, others get 401 when they
+/// haven't authenticated and 403 when they have. Requests no rule matches
+/// only need to authenticate, if authentication is enabled. OPTIONS is
+/// always answered.
+#[derive(Debug, Clone, Default)]
+pub struct AccessPolicy {
+    pub rules: Vec<AccessRule>,
+    /// Members of each role, by role name.
+    pub roles: HashMap<String, Vec<String>>,
+    pub allow: Vec<IpNet>,
+    pub deny: Vec<IpNet>,
+}
+
+impl AccessPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether connections from `ip` are accepted.
+    pub fn admits(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip)))
+    }
+
+    /// The first rule for `method` requests on the mount at `path`.
+    pub fn rule_for(&self, path: &str, method: &Method) -> Option<&AccessRule> {
+        self.rules.iter().find(|rule| rule.matches(path, method))
+    }
+
+    /// Whether `rule` lets `user` through; `None` for clients that haven't
+    /// authenticated. `signed` says the client presented a valid signed URL.
+    pub fn permits(&self, rule: &AccessRule, user: Option<&str>, signed: bool) -> bool {
+        rule.allow.iter().any(|principal| match (principal, user) {
+            (Principal::Anyone, _) => true,
+            (Principal::Authenticated, user) => user.is_some(),
+            (Principal::TokenHolder, _) => signed,
+            (Principal::User(name), Some(user)) => name == user,
+            (Principal::Role(role), Some(user)) => self
+                .roles
+                .get(role)
+                .is_some_and(|members| members.iter().any(|m| m == user)),
+            (_, None) => false,
+        })
+    }
+}
+
+/// What a request may do under the current [`AccessPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// A rule lets the client through.
+    Allowed,
+    /// No rule applies; the request only needs to authenticate.
+    Unruled,
+    /// A rule applies that doesn't list the client.
+    Denied,
+}
+
+/// The access policy shared by every connection of a server, and the
+/// signer of URLs that grant access without credentials.
+#[derive(Debug, Default)]
+pub struct AccessControl {
+    policy: RwLock<AccessPolicy>,
+    signer: RwLock<Option<UrlSigner>>,
+}
+
+impl AccessControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_policy(&self, policy: AccessPolicy) {
+        *self.policy.write() = policy;
+    }
+
+    pub fn policy(&self) -> AccessPolicy {
+        self.policy.read().clone()
+    }
+
+    pub fn admits(&self, ip: IpAddr) -> bool {
+        self.policy.read().admits(ip)
+    }
+
+    /// Accept URLs signed by `signer`, and refuse clients that neither
+    /// present one nor authenticate, unless a rule lets anyone through.
+    pub fn set_signer(&self, signer: Option<UrlSigner>) {
+        *self.signer.write() = signer;
+    }
+
+    pub fn signs_urls(&self) -> bool {
+        self.signer.read().is_some()
+    }
+
+    /// Check the signed URL token in `query` for the mount at `path`,
+    /// giving its expiry in Unix seconds; `None` when the query carries no
+    /// token or URLs aren't signed.
+    pub fn check_token(
+        &self,
+        path: &str,
+        query: &str,
+        peer: IpAddr,
+    ) -> Option<Result<u64, TokenError>> {
+        let signer = self.signer.read();
+        let signer = signer.as_ref().filter(|_| token::has_token(query))?;
+        Some(signer.verify(path, query, peer))
+    }
+
+    pub fn check(&self, path: &str, method: &Method, user: Option<&str>, signed: bool) -> Verdict {
+        let policy = self.policy.read();
+        match policy.rule_for(path, method) {
+            None => Verdict::Unruled,
+            Some(rule) if policy.permits(rule, user, signed) => Verdict::Allowed,
+            Some(_) => Verdict::Denied,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn networks_contain_their_addresses() {
+        let lan: IpNet = "192.168.1.0/24".parse().unwrap();
+        assert!(lan.contains("192.168.1.77".parse().unwrap()));
+        assert!(!lan.contains("192.168.2.1".parse().unwrap()));
+        assert!(lan.contains("::ffff:192.168.1.5".parse().unwrap()));
+
+        let host: IpNet = "10.0.0.5".parse().unwrap();
+        assert_eq!(host.prefix(), 32);
+        assert!(!host.contains("10.0.0.6".parse().unwrap()));
+
+        let v6: IpNet = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("10.0.0.1".parse().unwrap()));
+
+        let all: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains("8.8.8.8".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("camera".parse::<IpNet>().is_err());
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let policy = AccessPolicy {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: vec!["10.0.9.0/24".parse().unwrap()],
+            ..AccessPolicy::default()
+        };
+        assert!(policy.admits("10.1.2.3".parse().unwrap()));
+        assert!(!policy.admits("10.0.9.1".parse().unwrap()));
+        assert!(!policy.admits("192.168.1.1".parse().unwrap()));
+        assert!(AccessPolicy::default().admits("192.168.1.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let access = AccessControl::new();
+        access.set_policy(AccessPolicy {
+            rules: vec![
+                AccessRule::new("*")
+                    .methods(&[Method::Announce, Method::Record])
+                    .allow(Principal::Role("admin".into())),
+                AccessRule::new("lobby").allow(Principal::Anyone),
+                AccessRule::new("store/*")
+                    .allow(Principal::Role("security".into()))
+                    .allow(Principal::TokenHolder),
+            ],
+            roles: HashMap::from([
+                ("admin".to_string(), vec!["root".to_string()]),
+                ("security".to_string(), vec!["bob".to_string()]),
+            ]),
+            ..AccessPolicy::default()
+        });
+
+        assert_eq!(
+            access.check("lobby", &Method::Play, None, false),
+            Verdict::Allowed
+        );
+        assert_eq!(
+            access.check("lobby", &Method::Record, Some("bob"), false),
+            Verdict::Denied
+        );
+        assert_eq!(
+            access.check("lobby", &Method::Record, Some("root"), false),
+            Verdict::Allowed
+        );
+        assert_eq!(
+            access.check("store/till1", &Method::Describe, None, false),
+            Verdict::Denied
+        );
+        assert_eq!(
+            access.check("store/till1", &Method::Describe, Some("bob"), false),
+            Verdict::Allowed
+        );
+        assert_eq!(
+            access.check("store/till1", &Method::Describe, Some("root"), false),
+            Verdict::Denied
+        );
+        assert_eq!(
+            access.check("store/till1", &Method::Describe, None, true),
+            Verdict::Allowed
+        );
+        assert_eq!(
+            access.check("store/till1", &Method::Record, None, true),
+            Verdict::Denied
+        );
+        assert_eq!(
+            access.check("storefront", &Method::Describe, None, false),
+            Verdict::Unruled
+        );
+    }
+}
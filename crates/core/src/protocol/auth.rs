@@ -0,0 +1,675 @@
+//! Client authentication with the Basic (RFC 7617) and Digest (RFC 7616)
+//! schemes of `Authorization` / `WWW-Authenticate` (RFC 2326 §12.5).
+//!
+//! The server asks an [`Authenticator`] for user passwords; Digest nonces
+//! are issued per challenge and expire after
+//! [`AuthSettings::nonce_lifetime`]. Digest responses must name the
+//! request's URI and, with `qop=auth`, count up with each use of a nonce;
+//! without `qop` a nonce is good for one request. Either way captured
+//! headers can't be replayed.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use md5::Md5;
+use parking_lot::{Mutex, RwLock};
+use sha2::{Digest, Sha256};
+
+use crate::protocol::headers::{Authorization, parse_auth_params};
+
+/// Realm announced in challenges unless configured otherwise.
+pub const DEFAULT_REALM: &str = "RTSP Server";
+
+/// How long a Digest nonce is accepted after it was issued.
+pub const DEFAULT_NONCE_LIFETIME: Duration = Duration::from_secs(300);
+
+/// Nonces remembered at most; the oldest are forgotten first.
+const MAX_NONCES: usize = 4096;
+
+/// Hash algorithm of the Digest scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha256 => "SHA-256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "MD5" => Some(Self::Md5),
+            "SHA-256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Lowercase hex digest of `data`.
+    pub fn hash(&self, data: &str) -> String {
+        match self {
+            Self::Md5 => hex(&Md5::digest(data.as_bytes())),
+            Self::Sha256 => hex(&Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// The user store the server checks credentials against.
+///
+/// Implemented for closures and for a `HashMap` of usernames to
+/// passwords.
+pub trait Authenticator: Send + Sync {
+    /// Password of `username`, or `None` if there is no such user.
+    fn password(&self, username: &str) -> Option<String>;
+}
+
+impl<F> Authenticator for F
+where
+    F: Fn(&str) -> Option<String> + Send + Sync,
+{
+    fn password(&self, username: &str) -> Option<String> {
+        self(username)
+    }
+}
+
+impl Authenticator for HashMap<String, String> {
+    fn password(&self, username: &str) -> Option<String> {
+        self.get(username).cloned()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthSettings {
+    /// Protection space shown to users and part of Digest hashes.
+    pub realm: String,
+    /// Accept Basic credentials, which send the password in the clear.
+    pub allow_basic: bool,
+    /// Digest algorithms offered, most preferred first. Older clients
+    /// only know MD5.
+    pub algorithms: Vec<DigestAlgorithm>,
+    pub nonce_lifetime: Duration,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            realm: DEFAULT_REALM.to_string(),
+            allow_basic: false,
+            algorithms: vec![DigestAlgorithm::Sha256, DigestAlgorithm::Md5],
+            nonce_lifetime: DEFAULT_NONCE_LIFETIME,
+        }
+    }
+}
+
+/// Why a request's credentials were refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// No `Authorization` header.
+    Missing,
+    /// Unknown user, wrong password or malformed credentials.
+    Invalid,
+    /// Right password, but the Digest nonce expired: the client may retry
+    /// with a fresh one without asking the user (RFC 7616 §3.3).
+    Stale,
+}
+
+/// Authentication shared by every connection of a server. Disabled until
+/// an [`Authenticator`] is set.
+#[derive(Default)]
+pub struct Auth {
+    authenticator: RwLock<Option<Arc<dyn Authenticator>>>,
+    settings: RwLock<AuthSettings>,
+    nonces: Mutex<HashMap<String, IssuedNonce>>,
+}
+
+/// A nonce handed out in a challenge.
+#[derive(Debug, Clone, Copy)]
+struct IssuedNonce {
+    issued: Instant,
+    /// Highest `nc` accepted with the nonce so far.
+    count: u32,
+}
+
+impl Auth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require every request but OPTIONS to authenticate against
+    /// `authenticator`.
+    pub fn enable(&self, authenticator: impl Authenticator + 'static, settings: AuthSettings) {
+        *self.settings.write() = settings;
+        *self.authenticator.write() = Some(Arc::new(authenticator));
+    }
+
+    pub fn disable(&self) {
+        *self.authenticator.write() = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.authenticator.read().is_some()
+    }
+
+    pub fn settings(&self) -> AuthSettings {
+        self.settings.read().clone()
+    }
+
+    /// Check the credentials of a `method` request for `uri`. Returns the
+    /// authenticated user, or `None` while authentication is disabled.
+    pub fn verify(
+        &self,
+        method: &str,
+        uri: &str,
+        authorization: Option<&Authorization>,
+    ) -> Result<Option<String>, Rejection> {
+        let Some(authenticator) = self.authenticator.read().clone() else {
+            return Ok(None);
+        };
+        let authorization = authorization.ok_or(Rejection::Missing)?;
+        let settings = self.settings.read().clone();
+
+        if authorization.scheme.eq_ignore_ascii_case("Basic") && settings.allow_basic {
+            return verify_basic(&*authenticator, &authorization.credentials).map(Some);
+        }
+        if authorization.scheme.eq_ignore_ascii_case("Digest") {
+            let params: HashMap<String, String> = authorization.params().into_iter().collect();
+            return self
+                .verify_digest(&*authenticator, &settings, method, uri, &params)
+                .map(Some);
+        }
+        Err(Rejection::Invalid)
+    }
+
+    /// `WWW-Authenticate` values for a 401 response, each Digest one with
+    /// a fresh nonce. `stale` tells clients only the nonce expired.
+    pub fn challenges(&self, stale: bool) -> Vec<String> {
+        let settings = self.settings.read().clone();
+        let nonce = self.issue_nonce(settings.nonce_lifetime);
+        let realm = quote(&settings.realm);
+        let mut challenges: Vec<String> = settings
+            .algorithms
+            .iter()
+            .map(|algorithm| {
+                let mut challenge = format!(
+                    "Digest realm={realm}, nonce=\"{nonce}\", algorithm={}, qop=\"auth\"",
+                    algorithm.as_str()
+                );
+                if stale {
+                    challenge.push_str(", stale=true");
+                }
+                challenge
+            })
+            .collect();
+        if settings.allow_basic {
+            challenges.push(format!("Basic realm={realm}"));
+        }
+        challenges
+    }
+
+    fn issue_nonce(&self, lifetime: Duration) -> String {
+        let nonce = hex(&rand::random::<[u8; 16]>());
+        let now = Instant::now();
+        let mut nonces = self.nonces.lock();
+        nonces.retain(|_, n| now.duration_since(n.issued) < lifetime);
+        if nonces.len() >= MAX_NONCES
+            && let Some(oldest) = nonces
+                .iter()
+                .min_by_key(|(_, n)| n.issued)
+                .map(|(n, _)| n.clone())
+        {
+            nonces.remove(&oldest);
+        }
+        nonces.insert(
+            nonce.clone(),
+            IssuedNonce {
+                issued: now,
+                count: 0,
+            },
+        );
+        nonce
+    }
+
+    fn verify_digest(
+        &self,
+        authenticator: &dyn Authenticator,
+        settings: &AuthSettings,
+        method: &str,
+        request_uri: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<String, Rejection> {
+        let param = |name: &str| {
+            params
+                .get(name)
+                .map(String::as_str)
+                .ok_or(Rejection::Invalid)
+        };
+        let username = param("username")?;
+        let nonce = param("nonce")?;
+        let algorithm = match params.get("algorithm") {
+            Some(name) => DigestAlgorithm::from_name(name).ok_or(Rejection::Invalid)?,
+            None => DigestAlgorithm::Md5,
+        };
+        let uri = param("uri")?;
+        if param("realm")? != settings.realm
+            || !settings.algorithms.contains(&algorithm)
+            || uri != request_uri
+        {
+            return Err(Rejection::Invalid);
+        }
+        let password = authenticator.password(username).ok_or(Rejection::Invalid)?;
+
+        let qop = match params.get("qop").map(String::as_str) {
+            None => None,
+            Some("auth") => Some((param("nc")?, param("cnonce")?)),
+            Some(_) => return Err(Rejection::Invalid),
+        };
+        let expected = digest_response(
+            algorithm,
+            DigestInput {
+                username,
+                realm: &settings.realm,
+                password: &password,
+                method,
+                uri,
+                nonce,
+                qop,
+            },
+        );
+        if !constant_time_eq(
+            expected.as_bytes(),
+            param("response")?.to_ascii_lowercase().as_bytes(),
+        ) {
+            return Err(Rejection::Invalid);
+        }
+
+        let mut nonces = self.nonces.lock();
+        let issued = nonces
+            .get_mut(nonce)
+            .filter(|n| n.issued.elapsed() < settings.nonce_lifetime)
+            .ok_or(Rejection::Stale)?;
+        match qop {
+            Some((nc, _)) => {
+                let nc = u32::from_str_radix(nc, 16).map_err(|_| Rejection::Invalid)?;
+                if nc <= issued.count {
+                    return Err(Rejection::Invalid);
+                }
+                issued.count = nc;
+            }
+            // Without a count to check, the nonce can't be used again.
+            None => {
+                nonces.remove(nonce);
+            }
+        }
+        Ok(username.to_string())
+    }
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Auth")
+            .field("enabled", &self.is_enabled())
+            .field("settings", &*self.settings.read())
+            .finish()
+    }
+}
+
+fn verify_basic(authenticator: &dyn Authenticator, credentials: &str) -> Result<String, Rejection> {
+    let decoded = BASE64
+        .decode(credentials.trim())
+        .map_err(|_| Rejection::Invalid)?;
+    let decoded = String::from_utf8(decoded).map_err(|_| Rejection::Invalid)?;
+    let (username, password) = decoded.split_once(':').ok_or(Rejection::Invalid)?;
+    match authenticator.password(username) {
+        Some(expected) if constant_time_eq(expected.as_bytes(), password.as_bytes()) => {
+            Ok(username.to_string())
+        }
+        _ => Err(Rejection::Invalid),
+    }
+}
+
+/// The values a Digest `response` is computed from.
+struct DigestInput<'a> {
+    username: &'a str,
+    realm: &'a str,
+    password: &'a str,
+    method: &'a str,
+    uri: &'a str,
+    nonce: &'a str,
+    /// `nc` and `cnonce` with `qop=auth`; `None` for RFC 2069 clients.
+    qop: Option<(&'a str, &'a str)>,
+}
+
+fn digest_response(algorithm: DigestAlgorithm, input: DigestInput<'_>) -> String {
+    let ha1 = algorithm.hash(&format!(
+        "{}:{}:{}",
+        input.username, input.realm, input.password
+    ));
+    let ha2 = algorithm.hash(&format!("{}:{}", input.method, input.uri));
+    match input.qop {
+        Some((nc, cnonce)) => {
+            algorithm.hash(&format!("{ha1}:{}:{nc}:{cnonce}:auth:{ha2}", input.nonce))
+        }
+        None => algorithm.hash(&format!("{ha1}:{}:{ha2}", input.nonce)),
+    }
+}
+
+/// Credentials a client answers challenges with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// The Digest challenge answered last, reused for later requests.
+    challenge: Option<DigestChallenge>,
+    /// Requests sent with the current nonce.
+    nonce_count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    algorithm: DigestAlgorithm,
+    qop: bool,
+    opaque: Option<String>,
+}
+
+impl Credentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+            challenge: None,
+            nonce_count: 0,
+        }
+    }
+
+    /// Pick the strongest of the `WWW-Authenticate` values of a 401
+    /// response to answer. Returns `false` if none is usable.
+    pub fn accept_challenge<'a>(&mut self, challenges: impl IntoIterator<Item = &'a str>) -> bool {
+        let mut basic = false;
+        let mut best: Option<DigestChallenge> = None;
+        for challenge in challenges {
+            let Some((scheme, params)) = challenge.trim().split_once(' ') else {
+                continue;
+            };
+            if scheme.eq_ignore_ascii_case("Basic") {
+                basic = true;
+                continue;
+            }
+            if !scheme.eq_ignore_ascii_case("Digest") {
+                continue;
+            }
+            let params: HashMap<String, String> = parse_auth_params(params).into_iter().collect();
+            let algorithm = match params.get("algorithm") {
+                Some(name) => match DigestAlgorithm::from_name(name) {
+                    Some(algorithm) => algorithm,
+                    None => continue,
+                },
+                None => DigestAlgorithm::Md5,
+            };
+            let (Some(realm), Some(nonce)) = (params.get("realm"), params.get("nonce")) else {
+                continue;
+            };
+            let candidate = DigestChallenge {
+                realm: realm.clone(),
+                nonce: nonce.clone(),
+                algorithm,
+                qop: params
+                    .get("qop")
+                    .is_some_and(|qop| qop.split(',').any(|q| q.trim() == "auth")),
+                opaque: params.get("opaque").cloned(),
+            };
+            if best.as_ref().is_none_or(|b| {
+                b.algorithm == DigestAlgorithm::Md5 && algorithm == DigestAlgorithm::Sha256
+            }) {
+                best = Some(candidate);
+            }
+        }
+
+        match best {
+            Some(challenge) => {
+                self.challenge = Some(challenge);
+                self.nonce_count = 0;
+                true
+            }
+            None if basic => {
+                self.challenge = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `Authorization` value for a `method` request to `uri`, answering the
+    /// accepted challenge; Basic if it offered no usable Digest.
+    pub fn authorization(&mut self, method: &str, uri: &str) -> String {
+        let Some(challenge) = &self.challenge else {
+            let encoded = BASE64.encode(format!("{}:{}", self.username, self.password));
+            return format!("Basic {encoded}");
+        };
+        self.nonce_count += 1;
+        let nc = format!("{:08x}", self.nonce_count);
+        let cnonce = hex(&rand::random::<[u8; 8]>());
+        let response = digest_response(
+            challenge.algorithm,
+            DigestInput {
+                username: &self.username,
+                realm: &challenge.realm,
+                password: &self.password,
+                method,
+                uri,
+                nonce: &challenge.nonce,
+                qop: challenge.qop.then_some((nc.as_str(), cnonce.as_str())),
+            },
+        );
+
+        let mut value = format!(
+            "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response=\"{response}\"",
+            quote(&self.username),
+            quote(&challenge.realm),
+            quote(&challenge.nonce),
+            quote(uri),
+            challenge.algorithm.as_str(),
+        );
+        if challenge.qop {
+            let _ = write!(value, ", qop=auth, nc={nc}, cnonce=\"{cnonce}\"");
+        }
+        if let Some(opaque) = &challenge.opaque {
+            let _ = write!(value, ", opaque={}", quote(opaque));
+        }
+        value
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        })
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth(allow_basic: bool) -> Auth {
+        let auth = Auth::new();
+        let users = HashMap::from([("Mufasa".to_string(), "Circle of Life".to_string())]);
+        auth.enable(
+            users,
+            AuthSettings {
+                realm: "http-auth@example.org".into(),
+                allow_basic,
+                ..AuthSettings::default()
+            },
+        );
+        auth
+    }
+
+    fn header(value: &str) -> Authorization {
+        Authorization::parse(value).unwrap()
+    }
+
+    #[test]
+    fn digest_matches_rfc_7616_examples() {
+        // RFC 7616 §3.9.1, MD5 and SHA-256.
+        let input = || DigestInput {
+            username: "Mufasa",
+            realm: "http-auth@example.org",
+            password: "Circle of Life",
+            method: "GET",
+            uri: "/dir/index.html",
+            nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+            qop: Some(("00000001", "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ")),
+        };
+        assert_eq!(
+            digest_response(DigestAlgorithm::Md5, input()),
+            "8ca523f5e9506fed4657c9700eebdbec"
+        );
+        assert_eq!(
+            digest_response(DigestAlgorithm::Sha256, input()),
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+    }
+
+    #[test]
+    fn client_answers_server_challenge() {
+        let auth = auth(false);
+        assert_eq!(
+            auth.verify("DESCRIBE", "rtsp://host/live", None),
+            Err(Rejection::Missing)
+        );
+
+        let challenges = auth.challenges(false);
+        assert_eq!(challenges.len(), 2);
+        assert!(challenges[0].contains("algorithm=SHA-256"));
+
+        let mut credentials = Credentials::new("Mufasa", "Circle of Life");
+        assert!(credentials.accept_challenge(challenges.iter().map(String::as_str)));
+        let value = credentials.authorization("DESCRIBE", "rtsp://host/live");
+        assert!(value.contains("algorithm=SHA-256"));
+        assert_eq!(
+            auth.verify("DESCRIBE", "rtsp://host/live", Some(&header(&value))),
+            Ok(Some("Mufasa".into()))
+        );
+        // The response covers the method.
+        let value = credentials.authorization("DESCRIBE", "rtsp://host/live");
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&value))),
+            Err(Rejection::Invalid)
+        );
+
+        let mut wrong = Credentials::new("Mufasa", "Hakuna Matata");
+        wrong.accept_challenge(challenges.iter().map(String::as_str));
+        let value = wrong.authorization("DESCRIBE", "rtsp://host/live");
+        assert_eq!(
+            auth.verify("DESCRIBE", "rtsp://host/live", Some(&header(&value))),
+            Err(Rejection::Invalid)
+        );
+    }
+
+    #[test]
+    fn digest_is_bound_to_uri_and_nonce_count() {
+        let auth = auth(false);
+        let mut credentials = Credentials::new("Mufasa", "Circle of Life");
+        credentials.accept_challenge(auth.challenges(false).iter().map(String::as_str));
+
+        // A response for one URI can't be used for another.
+        let value = credentials.authorization("PLAY", "rtsp://host/other");
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&value))),
+            Err(Rejection::Invalid)
+        );
+
+        let first = credentials.authorization("PLAY", "rtsp://host/live");
+        let second = credentials.authorization("PLAY", "rtsp://host/live");
+        assert!(first.contains("nc=00000002") && second.contains("nc=00000003"));
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&second))),
+            Ok(Some("Mufasa".into()))
+        );
+        // Replayed, or with a count that didn't go up.
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&second))),
+            Err(Rejection::Invalid)
+        );
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&first))),
+            Err(Rejection::Invalid)
+        );
+    }
+
+    #[test]
+    fn digest_without_qop_uses_its_nonce_once() {
+        let auth = auth(false);
+        let mut credentials = Credentials::new("Mufasa", "Circle of Life");
+        let challenges = auth.challenges(false);
+        credentials.accept_challenge([challenges[0].replace(", qop=\"auth\"", "").as_str()]);
+        let value = credentials.authorization("PLAY", "rtsp://host/live");
+        assert!(!value.contains("qop"));
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&value))),
+            Ok(Some("Mufasa".into()))
+        );
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&value))),
+            Err(Rejection::Stale)
+        );
+    }
+
+    #[test]
+    fn expired_nonces_are_stale() {
+        let auth = auth(false);
+        let mut credentials = Credentials::new("Mufasa", "Circle of Life");
+        credentials.accept_challenge([
+            "Digest realm=\"http-auth@example.org\", nonce=\"unknown\", algorithm=MD5",
+        ]);
+        let value = credentials.authorization("SETUP", "rtsp://host/live");
+        assert!(!value.contains("qop"));
+        assert_eq!(
+            auth.verify("SETUP", "rtsp://host/live", Some(&header(&value))),
+            Err(Rejection::Stale)
+        );
+        assert!(auth.challenges(true)[0].ends_with("stale=true"));
+    }
+
+    #[test]
+    fn basic_only_when_allowed() {
+        let value = format!("Basic {}", BASE64.encode("Mufasa:Circle of Life"));
+        assert_eq!(
+            auth(false).verify("PLAY", "rtsp://host/live", Some(&header(&value))),
+            Err(Rejection::Invalid)
+        );
+        let auth = auth(true);
+        assert_eq!(
+            auth.verify("PLAY", "rtsp://host/live", Some(&header(&value))),
+            Ok(Some("Mufasa".into()))
+        );
+        assert!(auth.challenges(false).last().unwrap().starts_with("Basic "));
+
+        let mut credentials = Credentials::new("Mufasa", "Circle of Life");
+        assert!(credentials.accept_challenge(["Basic realm=\"x\""]));
+        assert_eq!(credentials.authorization("PLAY", "rtsp://host/live"), value);
+
+        auth.disable();
+        assert_eq!(auth.verify("PLAY", "rtsp://host/live", None), Ok(None));
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+
+use parking_lot::RwLock;
+
+/// Option tag a client requires in the SETUP that creates a session to let
+/// another connection take the session over by naming its ID, for HTTP
+/// tunnelling and reconnects. Such sessions are left to expire instead of
+/// being removed when their connection drops. Always supported.
+pub const SESSION_HANDOFF: &str = "rtsp.session-handoff";
+
+/// Option tags (RFC 2326 §3.8) the server understands in `Require` and
+/// `Proxy-Require`.
+///
+/// RTSP/1.0 defines no tags of its own, so the registry starts empty.
+/// Integrations that implement an extension, such as an ONVIF feature,
+/// register its tag; requests requiring anything else are answered with
+/// 551 Option Not Supported.
+#[derive(Debug, Default)]
+pub struct FeatureTags {
+    tags: RwLock<BTreeSet<String>>,
+}
+
+impl FeatureTags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark `tag` as supported. Returns `false` if it already was.
+    pub fn register(&self, tag: &str) -> bool {
+        self.tags.write().insert(tag.to_string())
+    }
+
+    /// Stop accepting `tag`. Returns `false` if it wasn't registered.
+    pub fn unregister(&self, tag: &str) -> bool {
+        self.tags.write().remove(tag)
+    }
+
+    pub fn is_supported(&self, tag: &str) -> bool {
+        self.tags.read().contains(tag)
+    }
+
+    /// Registered tags, in sorted order.
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.read().iter().cloned().collect()
+    }
+
+    /// The tags in `required` that aren't registered, in request order and
+    /// without duplicates, for the `Unsupported` header (RFC 2326 §12.42).
+    pub fn unsupported<'a>(&self, required: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+        let tags = self.tags.read();
+        let mut unsupported: Vec<String> = Vec::new();
+        for tag in required {
+            if !tags.contains(tag) && !unsupported.contains(tag) {
+                unsupported.push(tag.clone());
+            }
+        }
+        unsupported
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_tags_are_unsupported() {
+        let features = FeatureTags::new();
+        assert!(features.register("com.example.trick-play"));
+        assert!(!features.register("com.example.trick-play"));
+
+        let required = ["play.basic", "com.example.trick-play", "play.basic"].map(String::from);
+        assert_eq!(features.unsupported(&required), ["play.basic"]);
+    }
+
+    #[test]
+    fn unregistered_tags_stop_being_supported() {
+        let features = FeatureTags::new();
+        features.register("onvif-replay");
+        assert!(features.is_supported("onvif-replay"));
+        assert!(features.unregister("onvif-replay"));
+        assert!(!features.is_supported("onvif-replay"));
+        assert!(features.tags().is_empty());
+    }
+}
@@ -1,12 +1,52 @@
+use crate::error::RtspError;
+use crate::media::Packetizer;
+use crate::media::keyframe::KeyframeReason;
+use crate::mount::{self, Mount, MountSource};
+use crate::protocol::access::Verdict;
+use crate::protocol::auth::Rejection;
+use crate::protocol::features::SESSION_HANDOFF;
+use crate::protocol::headers::{NptTime, Range, RtpInfo};
+use crate::protocol::method::Method;
+use crate::protocol::parameters::{self, ParameterError, ParameterRequest};
 use crate::protocol::request::RtspRequest;
 use crate::protocol::response::RtspResponse;
 use crate::protocol::sdp;
-use crate::media::Packetizer;
-use crate::session::{SessionManager, SessionState, Transport};
-use crate::session::transport::TransportHeader;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use crate::protocol::token;
+use crate::session::transport::{Delivery, LowerTransport, Profile, TransportMode, TransportSpec};
+use crate::session::{Session, SessionManager, SessionState, Transport};
+use crate::transport::{ConnectionWriter, SessionSockets, ingest, rtcp};
 use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+
+/// Methods this server implements, advertised in `Public` and `Allow`.
+const SUPPORTED_METHODS: &[Method] = &[
+    Method::Options,
+    Method::Describe,
+    Method::Announce,
+    Method::Setup,
+    Method::Play,
+    Method::Pause,
+    Method::Record,
+    Method::Teardown,
+    Method::GetParameter,
+    Method::SetParameter,
+];
+
+/// Methods a signed URL grants on its own, where no access rule applies.
+const SIGNED_METHODS: &[Method] = &[
+    Method::Describe,
+    Method::Setup,
+    Method::Play,
+    Method::Pause,
+    Method::Teardown,
+    Method::GetParameter,
+];
+
+static CONNECTION_COUNTER: AtomicU64 = AtomicU64::new(1);
 
 /// Handles RTSP method requests for a single TCP connection.
 ///
@@ -18,6 +58,29 @@ pub struct MethodHandler {
     packetizer: Arc<Mutex<Box<dyn Packetizer>>>,
     /// Session IDs created during this connection, for cleanup on disconnect.
     session_ids: Vec<String>,
+    /// Identifies this connection as the owner of the sessions it creates.
+    connection_id: u64,
+    /// Mount paths this connection announced, removed on disconnect if
+    /// their publisher never set up a session.
+    announced: Vec<String>,
+    /// Tracks published over interleaved TCP, by RTP channel.
+    interleaved: HashMap<u8, InterleavedTrack>,
+    /// User this connection authenticated as.
+    user: Option<String>,
+    /// Mount paths this connection presented a valid signed URL for, with
+    /// when each URL expires in Unix seconds.
+    signed_paths: HashMap<String, u64>,
+    /// The connection's sending half, for viewers that set up over TCP.
+    writer: Option<Arc<ConnectionWriter>>,
+    /// Sessions playing over interleaved TCP, by RTCP channel.
+    viewer_rtcp: HashMap<u8, Arc<Session>>,
+}
+
+/// A mount track whose RTP the publisher sends on an interleaved channel.
+struct InterleavedTrack {
+    mount: Arc<Mount>,
+    track: usize,
+    session: Arc<Session>,
 }
 
 impl MethodHandler {
@@ -31,216 +94,1562 @@ impl MethodHandler {
             client_addr,
             packetizer,
             session_ids: Vec::new(),
+            connection_id: CONNECTION_COUNTER.fetch_add(1, Ordering::Relaxed),
+            announced: Vec::new(),
+            interleaved: HashMap::new(),
+            user: None,
+            signed_paths: HashMap::new(),
+            writer: None,
+            viewer_rtcp: HashMap::new(),
         }
     }
 
+    /// Let viewers set up media interleaved on the RTSP connection, sent
+    /// through `writer`.
+    pub fn set_writer(&mut self, writer: Arc<ConnectionWriter>) {
+        self.writer = Some(writer);
+    }
+
     /// Returns session IDs owned by this connection (for cleanup on disconnect).
     pub fn session_ids(&self) -> &[String] {
         &self.session_ids
     }
 
+    /// Sessions to remove when this connection drops: those it still owns
+    /// and that can't be handed off to a reconnecting client.
+    pub fn sessions_to_clean_up(&self) -> Vec<String> {
+        self.session_ids
+            .iter()
+            .filter(|id| {
+                self.session_manager
+                    .get_session(id)
+                    .is_some_and(|s| s.owner() == Some(self.connection_id) && !s.allows_handoff())
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Remove mounts this connection announced but never published to.
+    /// Mounts with a publishing session end with that session instead.
+    pub fn release_announced_mounts(&mut self) {
+        let mounts = self.session_manager.mounts();
+        for path in self.announced.drain(..) {
+            if let Some(mount) = mounts.get(&path)
+                && mount.publisher().is_none()
+            {
+                mounts.remove(&path);
+            }
+        }
+    }
+
+    /// Relay an RTP packet the publisher sent on an interleaved channel, or
+    /// ingest RTCP a TCP viewer sent on its RTCP channel. Packets on other
+    /// channels, such as a publisher's RTCP, are dropped.
+    pub fn handle_interleaved(&self, channel: u8, data: &[u8]) {
+        if let Some(session) = self.viewer_rtcp.get(&channel) {
+            rtcp::handle_report(&self.session_manager, session, data, Instant::now());
+            return;
+        }
+        match self.interleaved.get(&channel) {
+            Some(target) if !target.mount.is_closed() => {
+                target.session.touch();
+                target.mount.forward(target.track, data);
+            }
+            _ => tracing::trace!(
+                peer = %self.client_addr,
+                channel,
+                len = data.len(),
+                "dropping interleaved frame"
+            ),
+        }
+    }
+
     pub fn handle(&mut self, request: &RtspRequest) -> RtspResponse {
-        let cseq = request.cseq().unwrap_or("0");
-
-        match request.method.as_str() {
-            "OPTIONS" => self.handle_options(cseq),
-            "DESCRIBE" => self.handle_describe(cseq, &request.uri),
-            "SETUP" => self.handle_setup(cseq, request),
-            "PLAY" => self.handle_play(cseq, request),
-            "PAUSE" => self.handle_pause(cseq, request),
-            "TEARDOWN" => self.handle_teardown(cseq, request),
-            "GET_PARAMETER" => self.handle_get_parameter(cseq, request),
+        // The reader rejects requests without a CSeq before they get here.
+        let cseq = request.cseq().unwrap_or_default();
+        self.touch_sessions();
+
+        if !SUPPORTED_METHODS.contains(&request.method) {
+            tracing::warn!(method = %request.method, %cseq, "unsupported RTSP method");
+            return RtspResponse::from_error(&RtspError::MethodNotImplemented(
+                request.method.to_string(),
+            ))
+            .cseq(cseq);
+        }
+
+        if let Err(response) = self.check_access(cseq, request) {
+            return response;
+        }
+
+        if let Err(response) = self.check_required_features(cseq, request) {
+            return response;
+        }
+
+        let session = match self.check_state(cseq, request) {
+            Ok(session) => session,
+            Err(response) => return response,
+        };
+
+        // `check_state` guarantees a session for methods invalid in Init.
+        match (&request.method, session) {
+            (Method::Options, _) => self.handle_options(cseq),
+            (Method::Describe, _) => self.handle_describe(cseq, &request.uri),
+            (Method::Announce, _) => self.handle_announce(cseq, request),
+            (Method::Setup, session) => self.handle_setup(cseq, request, session),
+            (Method::Play, Some(session)) => self.handle_play(cseq, &session),
+            (Method::Pause, Some(session)) => self.handle_pause(cseq, &session),
+            (Method::Record, Some(session)) => self.handle_record(cseq, &session),
+            (Method::Teardown, session) => self.handle_teardown(cseq, session),
+            (Method::GetParameter, session) => self.handle_get_parameter(cseq, request, session),
+            (Method::SetParameter, session) => self.handle_set_parameter(cseq, request, session),
+            _ => RtspResponse::status(500).cseq(cseq),
+        }
+    }
+
+    /// Authenticate the request's credentials or signed URL and check the
+    /// access policy for its mount path. Clients that haven't authenticated
+    /// are challenged where the policy or enabled authentication requires a
+    /// user; others the policy doesn't list get 403, as do anonymous clients
+    /// of a server signing URLs. Where no rule applies, a signed URL grants
+    /// playback methods only, until it expires. OPTIONS is always answered,
+    /// and a connection stays authorized for clients that send no
+    /// credentials or token on later requests.
+    fn check_access(&mut self, cseq: u32, request: &RtspRequest) -> Result<(), RtspResponse> {
+        if request.method == Method::Options {
+            return Ok(());
+        }
+        let path = match self.session_manager.mounts().resolve(&request.uri) {
+            Some((mount, _)) => mount.path().to_string(),
+            None => mount::path_of(&request.uri).to_string(),
+        };
+        let access = self.session_manager.access();
+        match access.check_token(&path, mount::query_of(&request.uri), self.client_addr.ip()) {
+            Some(Ok(expires)) => {
+                self.signed_paths.insert(path.clone(), expires);
+            }
+            Some(Err(e)) => {
+                tracing::warn!(%cseq, method = %request.method, path, peer = %self.client_addr, error = %e, "signed URL refused");
+                return Err(RtspResponse::from_error(&RtspError::Forbidden(path)).cseq(cseq));
+            }
+            None => {}
+        }
+        let now = token::unix_time(SystemTime::now());
+        self.signed_paths.retain(|_, expires| now <= *expires);
+        // Keepalives may name the server rather than the mount.
+        let signed =
+            self.signed_paths.contains_key(&path) || (path == "*" && !self.signed_paths.is_empty());
+
+        let auth = self.session_manager.auth();
+        let authorization = request.authorization().ok().flatten();
+        if authorization.is_some() || self.user.is_none() {
+            match auth.verify(
+                request.method.as_str(),
+                &request.uri,
+                authorization.as_ref(),
+            ) {
+                Ok(user) => {
+                    if user.is_some() {
+                        self.user = user;
+                    }
+                }
+                Err(Rejection::Missing) => {}
+                Err(rejection) => {
+                    tracing::warn!(%cseq, method = %request.method, peer = %self.client_addr, ?rejection, "authentication failed");
+                    return Err(self.challenge(cseq, rejection));
+                }
+            }
+        }
+
+        match access.check(&path, &request.method, self.user.as_deref(), signed) {
+            Verdict::Allowed => Ok(()),
+            Verdict::Unruled if signed && SIGNED_METHODS.contains(&request.method) => Ok(()),
+            _ if self.user.is_none() && auth.is_enabled() => {
+                Err(self.challenge(cseq, Rejection::Missing))
+            }
+            Verdict::Unruled if self.user.is_some() || !access.signs_urls() => Ok(()),
             _ => {
-                tracing::warn!(method = %request.method, %cseq, "unsupported RTSP method");
-                RtspResponse::new(501, "Not Implemented").add_header("CSeq", cseq)
+                tracing::warn!(%cseq, method = %request.method, path, peer = %self.client_addr, user = ?self.user, "access denied");
+                Err(RtspResponse::from_error(&RtspError::Forbidden(path)).cseq(cseq))
             }
         }
     }
 
-    fn handle_options(&self, cseq: &str) -> RtspResponse {
+    /// A 401 response asking for credentials.
+    fn challenge(&self, cseq: u32, rejection: Rejection) -> RtspResponse {
+        let mut response = RtspResponse::from_error(&RtspError::Unauthorized).cseq(cseq);
+        for challenge in self
+            .session_manager
+            .auth()
+            .challenges(rejection == Rejection::Stale)
+        {
+            response = response.add_header("WWW-Authenticate", &challenge);
+        }
+        response
+    }
+
+    /// Reject requests whose `Require` or `Proxy-Require` names an option
+    /// tag the server doesn't support (RFC 2326 §12.32). SETUP may always
+    /// require [`SESSION_HANDOFF`].
+    fn check_required_features(
+        &self,
+        cseq: u32,
+        request: &RtspRequest,
+    ) -> Result<(), RtspResponse> {
+        let mut required = request.require();
+        if request.method == Method::Setup {
+            required.retain(|tag| tag != SESSION_HANDOFF);
+        }
+        let proxy_required = request.proxy_require();
+        let unsupported = self
+            .session_manager
+            .features()
+            .unsupported(required.iter().chain(&proxy_required));
+        if unsupported.is_empty() {
+            return Ok(());
+        }
+
+        tracing::warn!(%cseq, method = %request.method, tags = ?unsupported, "unsupported option tags required");
+        let error = RtspError::OptionNotSupported(unsupported.join(", "));
+        Err(RtspResponse::from_error(&error)
+            .cseq(cseq)
+            .unsupported(&unsupported))
+    }
+
+    /// Resolve the session named by the request, check this connection may
+    /// use it, and check the method is valid in its state (RFC 2326
+    /// Appendix A). Requests without a Session header are in the Init state.
+    fn check_state(
+        &mut self,
+        cseq: u32,
+        request: &RtspRequest,
+    ) -> Result<Option<Arc<Session>>, RtspResponse> {
+        let session = match self.extract_session_id(request) {
+            Some(id) => match self.session_manager.get_session(&id) {
+                Some(session) if self.claim(&session) => {
+                    session.touch();
+                    Some(session)
+                }
+                Some(_) => {
+                    tracing::warn!(
+                        session_id = id,
+                        peer = %self.client_addr,
+                        method = %request.method,
+                        "session belongs to another connection"
+                    );
+                    return Err(
+                        RtspResponse::from_error(&RtspError::SessionNotFound(id)).cseq(cseq)
+                    );
+                }
+                None => {
+                    tracing::warn!(session_id = id, method = %request.method, "unknown session");
+                    return Err(
+                        RtspResponse::from_error(&RtspError::SessionNotFound(id)).cseq(cseq)
+                    );
+                }
+            },
+            None => None,
+        };
+
+        let (state, mode) = session
+            .as_ref()
+            .map_or((SessionState::Init, TransportMode::Play), |s| {
+                (s.get_state(), s.mode())
+            });
+        if state.next(&request.method, mode).is_none() {
+            tracing::warn!(method = %request.method, ?state, %cseq, "method not valid in session state");
+            let error = RtspError::MethodNotValidInState(request.method.to_string());
+            return Err(RtspResponse::from_error(&error)
+                .cseq(cseq)
+                .allow(&state.allowed_methods(SUPPORTED_METHODS, mode)));
+        }
+
+        Ok(session)
+    }
+
+    /// Whether this connection may use `session`. A session open to
+    /// hand-off is taken over by the connection naming it.
+    fn claim(&mut self, session: &Session) -> bool {
+        match session.owner() {
+            Some(owner) if owner == self.connection_id => true,
+            None => true,
+            Some(_) if session.allows_handoff() => {
+                tracing::info!(session_id = %session.id, peer = %self.client_addr, "session handed off");
+                session.set_owner(self.connection_id);
+                self.session_ids.push(session.id.clone());
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn handle_options(&self, cseq: u32) -> RtspResponse {
         tracing::debug!(%cseq, "OPTIONS");
-        RtspResponse::ok()
-            .add_header("CSeq", cseq)
-            .add_header(
-                "Public",
-                "OPTIONS, DESCRIBE, SETUP, PLAY, PAUSE, TEARDOWN, GET_PARAMETER",
-            )
+        RtspResponse::ok().cseq(cseq).public(SUPPORTED_METHODS)
     }
 
-    fn handle_describe(&self, cseq: &str, uri: &str) -> RtspResponse {
+    fn handle_describe(&self, cseq: u32, uri: &str) -> RtspResponse {
         tracing::debug!(%cseq, uri, "DESCRIBE");
 
         if uri.is_empty() || uri == "*" {
             tracing::warn!(uri, "DESCRIBE for invalid URI");
-            return RtspResponse::not_found().add_header("CSeq", cseq);
+            return RtspResponse::not_found().cseq(cseq);
+        }
+
+        if let Some((mount, _)) = self.session_manager.mounts().resolve(uri) {
+            // Relative track controls resolve against a base ending in '/',
+            // without any query such as a signed URL's token.
+            let base = format!("{}/", mount::without_query(uri).trim_end_matches('/'));
+            return RtspResponse::ok()
+                .cseq(cseq)
+                .content_type("application/sdp")
+                .add_header("Content-Base", &base)
+                .with_body(mount.sdp().to_string());
         }
 
         let sdp = {
             let guard = self.packetizer.lock();
-            sdp::generate_sdp(&**guard)
+            sdp::generate_sdp(
+                &**guard,
+                self.session_manager.rtcp_settings().retransmission,
+            )
         };
 
         RtspResponse::ok()
-            .add_header("CSeq", cseq)
-            .add_header("Content-Type", "application/sdp")
-            .add_header("Content-Base", uri)
+            .cseq(cseq)
+            .content_type("application/sdp")
+            .add_header("Content-Base", mount::without_query(uri))
             .with_body(sdp)
     }
 
-    fn handle_setup(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let transport_header = match request.get_header("Transport") {
-            Some(t) => t,
-            None => {
+    fn handle_setup(
+        &mut self,
+        cseq: u32,
+        request: &RtspRequest,
+        session: Option<Arc<Session>>,
+    ) -> RtspResponse {
+        let client_transport = match request.transport() {
+            Ok(Some(t)) => t,
+            Ok(None) => {
                 tracing::warn!(%cseq, "SETUP missing Transport header");
-                return RtspResponse::bad_request().add_header("CSeq", cseq);
+                return RtspResponse::bad_request().cseq(cseq);
+            }
+            Err(e) => {
+                tracing::warn!(%cseq, transport_header = request.get_header("Transport"), "SETUP invalid Transport header");
+                return RtspResponse::from_error(&e).cseq(cseq);
             }
         };
 
-        let client_transport = match TransportHeader::parse(transport_header) {
-            Some(t) => t,
-            None => {
-                tracing::warn!(%cseq, transport_header, "SETUP invalid Transport header");
-                return RtspResponse::bad_request().add_header("CSeq", cseq);
-            }
+        let Some(spec) = client_transport
+            .select(|spec| self.supports_transport(spec) || self.supports_record_transport(spec))
+        else {
+            tracing::warn!(%cseq, transport_header = %client_transport, "no supported transport offered");
+            let error = RtspError::UnsupportedTransport(client_transport.to_string());
+            return RtspResponse::from_error(&error).cseq(cseq);
         };
+        if spec.mode == Some(TransportMode::Record) {
+            return self.handle_setup_record(cseq, request, session, spec);
+        }
 
-        let (server_rtp_port, server_rtcp_port) = match self.session_manager.allocate_server_ports()
-        {
-            Ok(ports) => ports,
-            Err(e) => {
-                tracing::error!(error = %e, "failed to allocate server ports");
-                return RtspResponse::new(500, "Internal Server Error").add_header("CSeq", cseq);
-            }
+        let target = match self.resolve_mount_track(&request.uri) {
+            Ok(target) => target,
+            Err(e) => return RtspResponse::from_error(&e).cseq(cseq),
         };
 
-        let session = self.session_manager.create_session(&request.uri);
+        if let Some(session) = session {
+            let same_track = match &target {
+                Some((mount, track)) => {
+                    session.mount().as_deref() == Some(mount.path())
+                        && mount.output_track(&session.id) == Some(*track)
+                }
+                None => session.mount().is_none(),
+            };
+            if !same_track {
+                tracing::warn!(session_id = %session.id, uri = %request.uri, "SETUP of a second stream in one session");
+                let error = RtspError::AggregateNotAllowed(request.uri.clone());
+                return RtspResponse::from_error(&error).cseq(cseq);
+            }
+            return self.handle_resetup(cseq, &session, spec);
+        }
+
+        let session = match (spec.interleaved, &self.writer) {
+            (Some(channels), Some(writer)) => {
+                let session = self.session_manager.create_session(&request.uri);
+                session.set_transport(Transport {
+                    profile: spec.profile,
+                    client_rtp_port: 0,
+                    client_rtcp_port: 0,
+                    server_rtp_port: 0,
+                    server_rtcp_port: 0,
+                    client_addr: self.client_addr,
+                    interleaved: Some(channels),
+                });
+                session.set_writer(writer.clone());
+                self.viewer_rtcp.insert(channels.1, session.clone());
+                session
+            }
+            _ => {
+                let Some((client_rtp_port, client_rtcp_port)) = spec.client_port else {
+                    return RtspResponse::bad_request().cseq(cseq);
+                };
+                let (server_rtp_port, server_rtcp_port) =
+                    match self.session_manager.allocate_server_ports() {
+                        Ok(ports) => ports,
+                        Err(e) => {
+                            tracing::error!(error = %e, "failed to allocate server ports");
+                            return RtspResponse::from_error(&e).cseq(cseq);
+                        }
+                    };
+                let sockets = match SessionSockets::bind(server_rtp_port, server_rtcp_port) {
+                    Ok(sockets) => sockets,
+                    Err(e) => {
+                        tracing::error!(error = %e, server_rtp_port, "failed to bind server ports");
+                        self.session_manager.release_server_ports(server_rtp_port);
+                        return RtspResponse::from_error(&e).cseq(cseq);
+                    }
+                };
+                let session = self.session_manager.create_session(&request.uri);
+                session.set_transport(Transport {
+                    profile: spec.profile,
+                    client_rtp_port,
+                    client_rtcp_port,
+                    server_rtp_port,
+                    server_rtcp_port,
+                    client_addr: SocketAddr::new(self.client_addr.ip(), client_rtp_port),
+                    interleaved: None,
+                });
+                session.set_sockets(sockets);
+                session
+            }
+        };
+        session.set_owner(self.connection_id);
+        session.set_handoff(Self::requests_handoff(request));
         let session_id = session.id.clone();
-        let client_rtp_addr =
-            SocketAddr::new(self.client_addr.ip(), client_transport.client_rtp_port);
-
-        session.set_transport(Transport {
-            client_rtp_port: client_transport.client_rtp_port,
-            client_rtcp_port: client_transport.client_rtcp_port,
-            server_rtp_port,
-            server_rtcp_port,
-            client_addr: client_rtp_addr,
-        });
+        if let Some((mount, track)) = target {
+            session.set_mount(mount.path());
+            mount.add_output(session.clone(), track);
+        }
 
         self.session_ids.push(session_id.clone());
 
         tracing::info!(
             session_id,
             uri = %request.uri,
-            client_rtp = %client_rtp_addr,
-            server_rtp_port,
+            transport = %spec,
             "session created via SETUP"
         );
 
-        let transport_response = format!(
-            "RTP/AVP;unicast;client_port={}-{};server_port={}-{}",
-            client_transport.client_rtp_port,
-            client_transport.client_rtcp_port,
-            server_rtp_port,
-            server_rtcp_port
-        );
-
-        RtspResponse::ok()
-            .add_header("CSeq", cseq)
-            .add_header("Transport", &transport_response)
-            .add_header("Session", &session.session_header_value())
+        Self::setup_response(cseq, &session)
     }
 
-    fn handle_play(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let session_id = match self.extract_session_id(request) {
-            Some(id) => id,
-            None => {
-                tracing::warn!(%cseq, "PLAY missing Session header");
-                return RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq);
+    /// SETUP on an existing session in Ready: update the client side of the
+    /// transport, keeping the session's server ports. A session stays on
+    /// the lower transport it was created with.
+    fn handle_resetup(
+        &mut self,
+        cseq: u32,
+        session: &Arc<Session>,
+        spec: &TransportSpec,
+    ) -> RtspResponse {
+        let not_configured = || {
+            RtspResponse::from_error(&RtspError::TransportNotConfigured(session.id.clone()))
+                .cseq(cseq)
+        };
+        let Some(mut transport) = session.get_transport() else {
+            return not_configured();
+        };
+        transport.profile = spec.profile;
+        if let Some((old_rtp, old_rtcp)) = transport.interleaved {
+            let Some((rtp_channel, rtcp_channel)) = spec.interleaved else {
+                return not_configured();
+            };
+            if (rtp_channel, rtcp_channel) != (old_rtp, old_rtcp) {
+                self.viewer_rtcp.remove(&old_rtcp);
+                self.viewer_rtcp.insert(rtcp_channel, session.clone());
             }
+            transport.interleaved = Some((rtp_channel, rtcp_channel));
+            session.set_transport(transport);
+            tracing::info!(session_id = %session.id, "session transport updated via SETUP");
+            return Self::setup_response(cseq, session);
+        }
+        let Some((client_rtp_port, client_rtcp_port)) = spec.client_port else {
+            return not_configured();
+        };
+        transport.client_rtp_port = client_rtp_port;
+        transport.client_rtcp_port = client_rtcp_port;
+        transport.client_addr = SocketAddr::new(self.client_addr.ip(), client_rtp_port);
+        session.set_transport(transport);
+
+        tracing::info!(session_id = %session.id, "session transport updated via SETUP");
+        Self::setup_response(cseq, session)
+    }
+
+    /// Whether the client creating a session lets other connections take
+    /// it over.
+    fn requests_handoff(request: &RtspRequest) -> bool {
+        request.require().iter().any(|tag| tag == SESSION_HANDOFF)
+    }
+
+    /// The mount track a viewer's SETUP URI selects, or `None` for the
+    /// application's own stream. A mount's aggregate URL selects its only
+    /// track; with several tracks it must name one.
+    fn resolve_mount_track(&self, uri: &str) -> Result<Option<(Arc<Mount>, usize)>, RtspError> {
+        let Some((mount, track)) = self.session_manager.mounts().resolve(uri) else {
+            return Ok(None);
         };
+        match track {
+            Some(track) => Ok(Some((mount, track))),
+            None if mount.tracks().len() == 1 => Ok(Some((mount, 0))),
+            None => Err(RtspError::AggregateNotAllowed(uri.to_string())),
+        }
+    }
+
+    /// ANNOUNCE creates a mount from the SDP body, for the client to publish
+    /// to with SETUP (mode=record) and RECORD (RFC 2326 §10.3).
+    fn handle_announce(&mut self, cseq: u32, request: &RtspRequest) -> RtspResponse {
+        let path = mount::path_of(&request.uri).to_string();
+        tracing::debug!(%cseq, path, "ANNOUNCE");
+
+        if path.is_empty() {
+            return RtspResponse::bad_request().cseq(cseq);
+        }
+        if !self.session_manager.mounts().may_publish(&path) {
+            tracing::warn!(peer = %self.client_addr, path, "publishing refused by policy");
+            return RtspResponse::from_error(&RtspError::PublishNotAllowed(path)).cseq(cseq);
+        }
+        if request
+            .get_header("Content-Type")
+            .is_none_or(|t| !t.trim().eq_ignore_ascii_case("application/sdp"))
+        {
+            return RtspResponse::status(415).cseq(cseq);
+        }
 
-        match self.session_manager.get_session(&session_id) {
-            Some(session) => {
-                session.set_state(SessionState::Playing);
-                tracing::info!(session_id, "session started playing");
-                RtspResponse::ok()
-                    .add_header("CSeq", cseq)
-                    .add_header("Session", &session.session_header_value())
-                    .add_header("Range", "npt=0.000-")
+        let sdp = String::from_utf8_lossy(&request.body);
+        let source = MountSource::Record {
+            connection_id: self.connection_id,
+        };
+        let inserted = Mount::new(&path, &sdp, source)
+            .and_then(|mount| self.session_manager.mounts().insert(mount));
+        match inserted {
+            Ok(mount) => {
+                tracing::info!(peer = %self.client_addr, path, tracks = mount.tracks().len(), "stream announced");
+                self.announced.push(path);
+                RtspResponse::ok().cseq(cseq)
             }
-            None => {
-                tracing::warn!(session_id, "PLAY for unknown session");
-                RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq)
+            Err(e) => {
+                tracing::warn!(peer = %self.client_addr, path, error = %e, "ANNOUNCE rejected");
+                RtspResponse::from_error(&e).cseq(cseq)
             }
         }
     }
 
-    fn handle_pause(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let session_id = match self.extract_session_id(request) {
-            Some(id) => id,
-            None => {
-                tracing::warn!(%cseq, "PAUSE missing Session header");
-                return RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq);
+    /// SETUP with mode=record: receive one track of a mount this connection
+    /// announced, over UDP or interleaved on the RTSP connection.
+    fn handle_setup_record(
+        &mut self,
+        cseq: u32,
+        request: &RtspRequest,
+        session: Option<Arc<Session>>,
+        spec: &TransportSpec,
+    ) -> RtspResponse {
+        let source = MountSource::Record {
+            connection_id: self.connection_id,
+        };
+        let (mount, track) = match self.resolve_mount_track(&request.uri) {
+            Ok(Some((mount, track))) if *mount.source() == source => (mount, track),
+            Ok(_) => {
+                tracing::warn!(%cseq, uri = %request.uri, "SETUP for record without ANNOUNCE");
+                let error = RtspError::MountNotFound(request.uri.clone());
+                return RtspResponse::from_error(&error).cseq(cseq);
             }
+            Err(e) => return RtspResponse::from_error(&e).cseq(cseq),
         };
+        if let Some(session) = &session
+            && (session.mode() != TransportMode::Record
+                || session.mount().as_deref() != Some(mount.path()))
+        {
+            let error = RtspError::AggregateNotAllowed(request.uri.clone());
+            return RtspResponse::from_error(&error).cseq(cseq);
+        }
 
-        match self.session_manager.get_session(&session_id) {
-            Some(session) => {
-                session.set_state(SessionState::Paused);
-                tracing::info!(session_id, "session paused");
-                RtspResponse::ok()
-                    .add_header("CSeq", cseq)
-                    .add_header("Session", &session.session_header_value())
-            }
-            None => {
-                tracing::warn!(session_id, "PAUSE for unknown session");
-                RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq)
-            }
+        // One session publishes the mount; a second would feed its tracks
+        // alongside the first.
+        if session.is_none() && mount.publisher().is_some() {
+            tracing::warn!(%cseq, path = %mount.path(), "SETUP for record on a mount already being published");
+            let error = RtspError::MountInUse(mount.path().to_string());
+            return RtspResponse::from_error(&error).cseq(cseq);
         }
+
+        let session = session.unwrap_or_else(|| {
+            let session = self.session_manager.create_session(&request.uri);
+            session.set_owner(self.connection_id);
+            session.set_handoff(Self::requests_handoff(request));
+            session.set_mode(TransportMode::Record);
+            session.set_mount(mount.path());
+            self.session_ids.push(session.id.clone());
+            session
+        });
+        mount.set_publisher(&session.id);
+
+        let mut reply = TransportSpec {
+            profile: spec.profile,
+            lower_transport: spec.lower_transport,
+            delivery: Some(Delivery::Unicast),
+            mode: Some(TransportMode::Record),
+            ..TransportSpec::default()
+        };
+
+        if let Some((rtp_channel, rtcp_channel)) = spec.interleaved {
+            self.interleaved.insert(
+                rtp_channel,
+                InterleavedTrack {
+                    mount: mount.clone(),
+                    track,
+                    session: session.clone(),
+                },
+            );
+            reply.interleaved = Some((rtp_channel, rtcp_channel));
+        } else if let Some(transport) = session.track_transport(track) {
+            // The track already has a receiver; point the client at it again.
+            reply.client_port = Some((transport.client_rtp_port, transport.client_rtcp_port));
+            reply.server_port = Some((transport.server_rtp_port, transport.server_rtcp_port));
+        } else {
+            let (server_rtp_port, server_rtcp_port) =
+                match self.session_manager.allocate_server_ports() {
+                    Ok(ports) => ports,
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to allocate server ports");
+                        return RtspResponse::from_error(&e).cseq(cseq);
+                    }
+                };
+            let sockets = match SessionSockets::bind(server_rtp_port, server_rtcp_port) {
+                Ok(sockets) => sockets,
+                Err(e) => {
+                    tracing::error!(error = %e, server_rtp_port, "failed to bind server ports");
+                    self.session_manager.release_server_ports(server_rtp_port);
+                    return RtspResponse::from_error(&e).cseq(cseq);
+                }
+            };
+            let (client_rtp_port, client_rtcp_port) = spec.client_port.unwrap_or_default();
+            session.set_track_transport(
+                track,
+                Transport {
+                    profile: spec.profile,
+                    client_rtp_port,
+                    client_rtcp_port,
+                    server_rtp_port,
+                    server_rtcp_port,
+                    client_addr: SocketAddr::new(self.client_addr.ip(), client_rtp_port),
+                    interleaved: None,
+                },
+            );
+            ingest::spawn_udp_receiver(
+                sockets,
+                self.client_addr.ip(),
+                mount.clone(),
+                track,
+                session.clone(),
+            );
+            reply.client_port = spec.client_port;
+            reply.server_port = Some((server_rtp_port, server_rtcp_port));
+        }
+
+        tracing::info!(
+            session_id = %session.id,
+            path = %mount.path(),
+            track,
+            transport = %reply,
+            "publisher track set up"
+        );
+        RtspResponse::ok()
+            .cseq(cseq)
+            .transport(&reply)
+            .session(&session.session_header())
     }
 
-    fn handle_teardown(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let session_id = match self.extract_session_id(request) {
-            Some(id) => id,
-            None => {
-                tracing::warn!(%cseq, "TEARDOWN missing Session header");
-                return RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq);
-            }
+    /// RECORD starts accepting the publisher's media (RFC 2326 §10.11).
+    fn handle_record(&mut self, cseq: u32, session: &Session) -> RtspResponse {
+        let mount = session
+            .mount()
+            .and_then(|path| self.session_manager.mounts().get(&path));
+        let Some(mount) = mount.filter(|m| m.publisher().as_deref() == Some(session.id.as_str()))
+        else {
+            tracing::warn!(session_id = %session.id, "RECORD on a session that isn't publishing");
+            let allowed: Vec<Method> = session
+                .get_state()
+                .allowed_methods(SUPPORTED_METHODS, session.mode())
+                .into_iter()
+                .filter(|m| *m != Method::Record)
+                .collect();
+            let error = RtspError::MethodNotValidInState(Method::Record.to_string());
+            return RtspResponse::from_error(&error).cseq(cseq).allow(&allowed);
         };
 
-        match self.session_manager.remove_session(&session_id) {
-            Some(_) => {
-                self.session_ids.retain(|id| id != &session_id);
-                tracing::info!(session_id, "session terminated via TEARDOWN");
-                RtspResponse::ok().add_header("CSeq", cseq)
-            }
-            None => {
-                tracing::warn!(session_id, "TEARDOWN for unknown session");
-                RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq)
-            }
+        session.set_state(SessionState::Recording);
+        tracing::info!(session_id = %session.id, path = %mount.path(), "publisher started recording");
+        RtspResponse::ok()
+            .cseq(cseq)
+            .session(&session.session_header())
+    }
+
+    /// Whether SETUP can use `spec` to publish: unicast RTP from the client
+    /// over UDP, or interleaved on the RTSP connection.
+    fn supports_record_transport(&self, spec: &TransportSpec) -> bool {
+        let lower = match spec.lower_transport {
+            LowerTransport::Udp => spec.client_port.is_some(),
+            LowerTransport::Tcp => spec.interleaved.is_some(),
+        };
+        matches!(spec.profile, Profile::Avp | Profile::Avpf)
+            && spec.delivery != Some(Delivery::Multicast)
+            && spec.mode == Some(TransportMode::Record)
+            && lower
+    }
+
+    /// Whether SETUP can use `spec`: unicast RTP to the requesting client
+    /// over UDP, or interleaved on the RTSP connection, for playback.
+    fn supports_transport(&self, spec: &TransportSpec) -> bool {
+        let lower = match spec.lower_transport {
+            LowerTransport::Udp => spec.client_port.is_some(),
+            LowerTransport::Tcp => spec.interleaved.is_some() && self.writer.is_some(),
+        };
+        matches!(spec.profile, Profile::Avp | Profile::Avpf)
+            && spec.delivery != Some(Delivery::Multicast)
+            && lower
+            && spec.mode != Some(TransportMode::Record)
+            // Don't let a client direct the stream at a third party.
+            && spec
+                .destination
+                .as_deref()
+                .is_none_or(|d| d.parse::<IpAddr>().is_ok_and(|ip| ip == self.client_addr.ip()))
+    }
+
+    fn setup_response(cseq: u32, session: &Session) -> RtspResponse {
+        let mut response = RtspResponse::ok().cseq(cseq);
+        if let Some(transport) = session.get_transport() {
+            response = response.transport(&transport.spec());
         }
+        response.session(&session.session_header())
     }
 
-    /// GET_PARAMETER is used by clients (e.g. VLC) as a keepalive (RFC 2326 §10.8).
-    fn handle_get_parameter(&self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        tracing::trace!(%cseq, "GET_PARAMETER keepalive");
+    fn handle_play(&mut self, cseq: u32, session: &Session) -> RtspResponse {
+        if session.get_transport().is_none() {
+            tracing::warn!(session_id = %session.id, "PLAY before transport was configured");
+            return RtspResponse::from_error(&RtspError::TransportNotConfigured(
+                session.id.clone(),
+            ))
+            .cseq(cseq)
+            .allow(&[Method::Options, Method::Setup, Method::Teardown]);
+        }
 
-        let mut resp = RtspResponse::ok().add_header("CSeq", cseq);
+        let mount = session
+            .mount()
+            .and_then(|path| self.session_manager.mounts().get(&path));
+        if let Some(mount) = mount {
+            session.set_state(SessionState::Playing);
+            tracing::info!(session_id = %session.id, path = %mount.path(), "session started playing mount");
+            let (seq, rtptime) = mount.output_position(&session.id).unzip();
+            let rtp_info = RtpInfo {
+                url: session.uri.clone(),
+                seq,
+                rtptime: rtptime.flatten(),
+            };
+            return RtspResponse::ok()
+                .cseq(cseq)
+                .session(&session.session_header())
+                .range(&Range::npt_from(NptTime::Seconds(
+                    mount.age().as_secs_f64(),
+                )))
+                .rtp_info(&[rtp_info]);
+        }
 
-        if let Some(id) = self.extract_session_id(request) {
-            if self.session_manager.get_session(&id).is_some() {
-                resp = resp.add_header("Session", &id);
-            }
+        let previous = session.get_state();
+        session.set_state(SessionState::Playing);
+        tracing::info!(session_id = %session.id, "session started playing");
+        if previous != SessionState::Playing {
+            // The viewer can't decode anything until the next keyframe.
+            self.session_manager
+                .keyframes()
+                .request(KeyframeReason::NewViewer, &session.id);
         }
+        let (rtp_info, position) = self.stream_position(session);
+        RtspResponse::ok()
+            .cseq(cseq)
+            .session(&session.session_header())
+            .range(&Range::npt_from(NptTime::Seconds(position)))
+            .rtp_info(&[rtp_info])
+    }
+
+    /// Where the live stream is: the sequence number and RTP timestamp of
+    /// the next packet the packetizer writes, and the stream's NPT in
+    /// seconds since its first frame.
+    fn stream_position(&self, session: &Session) -> (RtpInfo, f64) {
+        let packetizer = self.packetizer.lock();
+        let header = packetizer.rtp_header();
+        let rtp_info = RtpInfo {
+            url: session.uri.clone(),
+            seq: Some(header.sequence()),
+            rtptime: Some(header.timestamp() as u32),
+        };
+        let position = header.timestamp() as f64 / packetizer.clock_rate() as f64;
+        (rtp_info, position)
+    }
 
-        resp
+    fn handle_pause(&mut self, cseq: u32, session: &Session) -> RtspResponse {
+        session.set_state(SessionState::Ready);
+        tracing::info!(session_id = %session.id, "session paused");
+        RtspResponse::ok()
+            .cseq(cseq)
+            .session(&session.session_header())
+    }
+
+    fn handle_teardown(&mut self, cseq: u32, session: Option<Arc<Session>>) -> RtspResponse {
+        let Some(session) = session else {
+            tracing::debug!(%cseq, "TEARDOWN without a session");
+            return RtspResponse::ok().cseq(cseq);
+        };
+
+        self.session_manager.remove_session(&session.id);
+        self.session_ids.retain(|id| id != &session.id);
+        self.viewer_rtcp.retain(|_, s| s.id != session.id);
+        tracing::info!(session_id = %session.id, "session terminated via TEARDOWN");
+        RtspResponse::ok().cseq(cseq)
+    }
+
+    /// GET_PARAMETER returns application parameters named in a
+    /// `text/parameters` body (RFC 2326 §10.8). Clients such as VLC send it
+    /// without a body as a keepalive.
+    fn handle_get_parameter(
+        &self,
+        cseq: u32,
+        request: &RtspRequest,
+        session: Option<Arc<Session>>,
+    ) -> RtspResponse {
+        let query = match Self::parameter_request(cseq, request, session.as_deref()) {
+            Ok(query) => query,
+            Err(response) => return response,
+        };
+        if query.parameters.is_empty() {
+            tracing::trace!(%cseq, "GET_PARAMETER keepalive");
+            return Self::with_session(RtspResponse::ok().cseq(cseq), session.as_deref());
+        }
+
+        let response = match self.session_manager.parameters().get(&query) {
+            Ok(values) => RtspResponse::ok()
+                .cseq(cseq)
+                .content_type(parameters::TEXT_PARAMETERS)
+                .with_body(parameters::format(&values)),
+            Err(error) => Self::parameter_error(cseq, &error),
+        };
+        Self::with_session(response, session.as_deref())
+    }
+
+    /// SET_PARAMETER hands `text/parameters` name/value pairs to the
+    /// application (RFC 2326 §10.9). Without a body it is a keepalive.
+    fn handle_set_parameter(
+        &self,
+        cseq: u32,
+        request: &RtspRequest,
+        session: Option<Arc<Session>>,
+    ) -> RtspResponse {
+        let update = match Self::parameter_request(cseq, request, session.as_deref()) {
+            Ok(update) => update,
+            Err(response) => return response,
+        };
+        if update.parameters.is_empty() {
+            tracing::trace!(%cseq, "SET_PARAMETER keepalive");
+            return Self::with_session(RtspResponse::ok().cseq(cseq), session.as_deref());
+        }
+
+        tracing::debug!(%cseq, count = update.parameters.len(), "SET_PARAMETER");
+        let response = match self.session_manager.parameters().set(&update) {
+            Ok(()) => RtspResponse::ok().cseq(cseq),
+            Err(error) => Self::parameter_error(cseq, &error),
+        };
+        Self::with_session(response, session.as_deref())
+    }
+
+    /// Parameters named in the request body, rejecting bodies that aren't
+    /// `text/parameters` with 415.
+    fn parameter_request(
+        cseq: u32,
+        request: &RtspRequest,
+        session: Option<&Session>,
+    ) -> Result<ParameterRequest, RtspResponse> {
+        if !request.body.is_empty()
+            && let Some(content_type) = request.get_header("Content-Type")
+            && !content_type
+                .trim()
+                .eq_ignore_ascii_case(parameters::TEXT_PARAMETERS)
+        {
+            tracing::warn!(%cseq, content_type, "unsupported parameter body");
+            return Err(RtspResponse::status(415).cseq(cseq));
+        }
+        Ok(ParameterRequest {
+            session_id: session.map(|s| s.id.clone()),
+            parameters: parameters::parse(&String::from_utf8_lossy(&request.body)),
+        })
+    }
+
+    /// 451, 458 or 400 listing the parameters that failed.
+    fn parameter_error(cseq: u32, error: &ParameterError) -> RtspResponse {
+        tracing::debug!(%cseq, ?error, "parameter request refused");
+        let names: String = error
+            .names()
+            .iter()
+            .map(|name| format!("{}\r\n", name))
+            .collect();
+        RtspResponse::status(error.status_code())
+            .cseq(cseq)
+            .content_type(parameters::TEXT_PARAMETERS)
+            .with_body(names)
+    }
+
+    fn with_session(response: RtspResponse, session: Option<&Session>) -> RtspResponse {
+        match session {
+            Some(session) => response.add_header("Session", &session.id),
+            None => response,
+        }
+    }
+
+    /// Any request keeps alive every session owned by this connection, so
+    /// OPTIONS keepalives without a Session header count too. The session a
+    /// request names is touched once ownership is checked.
+    fn touch_sessions(&self) {
+        for id in &self.session_ids {
+            if let Some(session) = self.session_manager.get_session(id) {
+                session.touch();
+            }
+        }
     }
 
     /// Extract session ID from the Session header.
     /// Handles timeout suffix: "SESSIONID;timeout=60" → "SESSIONID"
     fn extract_session_id(&self, request: &RtspRequest) -> Option<String> {
-        request.get_header("Session").map(|s| {
-            s.split(';').next().unwrap_or(s).trim().to_string()
-        })
+        request.session().ok().flatten().map(|s| s.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::h264::H264Packetizer;
+    use crate::protocol::headers::SessionHeader;
+    use crate::rtcp::packet::{ReceiverReport, ReportBlock, RtcpPacket, serialize_compound};
+    use crate::server::Viewer;
+
+    fn handler() -> MethodHandler {
+        MethodHandler::new(
+            SessionManager::new(),
+            "127.0.0.1:50000".parse().unwrap(),
+            Arc::new(Mutex::new(Box::new(H264Packetizer::new(96, 1)))),
+        )
+    }
+
+    fn request(method: &str, session: Option<&str>) -> RtspRequest {
+        let mut raw = format!("{method} rtsp://localhost/stream RTSP/1.0\r\nCSeq: 2\r\n");
+        if let Some(id) = session {
+            raw.push_str(&format!("Session: {id}\r\n"));
+        }
+        RtspRequest::parse(&raw).unwrap()
+    }
+
+    fn header<'a>(response: &'a RtspResponse, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn challenges_until_authenticated() {
+        let mut handler = handler();
+        let users = HashMap::from([("admin".to_string(), "secret".to_string())]);
+        handler
+            .session_manager
+            .auth()
+            .enable(users, crate::protocol::AuthSettings::default());
+
+        assert_eq!(handler.handle(&request("OPTIONS", None)).status_code, 200);
+        let response = handler.handle(&request("DESCRIBE", None));
+        assert_eq!(response.status_code, 401);
+        let challenges: Vec<&str> = response
+            .headers
+            .iter()
+            .filter(|(n, _)| n == "WWW-Authenticate")
+            .map(|(_, v)| v.as_str())
+            .collect();
+        assert_eq!(challenges.len(), 2);
+
+        let mut credentials = crate::protocol::auth::Credentials::new("admin", "secret");
+        assert!(credentials.accept_challenge(challenges));
+        let authorization = credentials.authorization("DESCRIBE", "rtsp://localhost/stream");
+        let describe = request("DESCRIBE", None).add_header("Authorization", &authorization);
+        assert_eq!(handler.handle(&describe).status_code, 200);
+        // The connection stays authenticated.
+        assert_eq!(handler.handle(&request("DESCRIBE", None)).status_code, 200);
+    }
+
+    #[test]
+    fn access_rules_answer_401_or_403() {
+        use crate::protocol::{AccessPolicy, AccessRule, Principal};
+
+        let mut handler = handler();
+        let users = HashMap::from([
+            ("staff".to_string(), "secret".to_string()),
+            ("guest".to_string(), "guest".to_string()),
+        ]);
+        let settings = crate::protocol::AuthSettings {
+            allow_basic: true,
+            ..Default::default()
+        };
+        handler.session_manager.auth().enable(users, settings);
+        let policy = AccessPolicy {
+            rules: vec![AccessRule::new("stream").allow(Principal::User("staff".into()))],
+            ..AccessPolicy::default()
+        };
+        handler.session_manager.access().set_policy(policy.clone());
+
+        assert_eq!(handler.handle(&request("DESCRIBE", None)).status_code, 401);
+        let guest = request("DESCRIBE", None).add_header("Authorization", "Basic Z3Vlc3Q6Z3Vlc3Q=");
+        assert_eq!(handler.handle(&guest).status_code, 403);
+
+        let mut without_auth = self::handler();
+        without_auth.session_manager.access().set_policy(policy);
+        // Without authentication there is nobody to let through.
+        assert_eq!(
+            without_auth.handle(&request("DESCRIBE", None)).status_code,
+            403
+        );
+
+        handler.session_manager.access().set_policy(AccessPolicy {
+            rules: vec![AccessRule::new("stream").allow(Principal::Anyone)],
+            ..AccessPolicy::default()
+        });
+        let mut anonymous = MethodHandler::new(
+            handler.session_manager.clone(),
+            handler.client_addr,
+            handler.packetizer.clone(),
+        );
+        assert_eq!(
+            anonymous.handle(&request("DESCRIBE", None)).status_code,
+            200
+        );
+    }
+
+    #[test]
+    fn signed_urls_grant_their_mount() {
+        use crate::protocol::UrlSigner;
+        use std::time::Duration;
+
+        let mut handler = handler();
+        let signer = UrlSigner::new("secret");
+        handler
+            .session_manager
+            .access()
+            .set_signer(Some(signer.clone()));
+        assert_eq!(handler.handle(&request("DESCRIBE", None)).status_code, 403);
+
+        let url = signer.sign_url("rtsp://localhost/stream", Duration::from_secs(60), None);
+        let other = signer.sign_url("rtsp://localhost/other", Duration::from_secs(60), None);
+        let forged = RtspRequest::parse(&format!(
+            "DESCRIBE {} RTSP/1.0\r\nCSeq: 2\r\n",
+            other.replace("/other", "/stream")
+        ))
+        .unwrap();
+        assert_eq!(handler.handle(&forged).status_code, 403);
+
+        let describe =
+            RtspRequest::parse(&format!("DESCRIBE {url} RTSP/1.0\r\nCSeq: 3\r\n")).unwrap();
+        let response = handler.handle(&describe);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            header(&response, "Content-Base"),
+            Some("rtsp://localhost/stream")
+        );
+        // Later requests on the connection needn't repeat the token.
+        assert_eq!(handler.handle(&request("DESCRIBE", None)).status_code, 200);
+    }
+
+    #[test]
+    fn signed_urls_allow_playback_only_until_they_expire() {
+        use crate::protocol::{AccessPolicy, AccessRule, Principal, UrlSigner};
+        use std::time::Duration;
+
+        let mut handler = handler();
+        let signer = UrlSigner::new("secret");
+        handler
+            .session_manager
+            .access()
+            .set_signer(Some(signer.clone()));
+        let url = signer.sign_url("rtsp://localhost/stream", Duration::from_secs(60), None);
+        let signed = |method: &str| {
+            RtspRequest::parse(&format!("{method} {url} RTSP/1.0\r\nCSeq: 2\r\n")).unwrap()
+        };
+
+        assert_eq!(handler.handle(&signed("DESCRIBE")).status_code, 200);
+        assert_eq!(handler.handle(&signed("RECORD")).status_code, 403);
+
+        // A rule for the mount decides, and lets token holders in only if
+        // it lists them.
+        handler.session_manager.access().set_policy(AccessPolicy {
+            rules: vec![AccessRule::new("stream").allow(Principal::User("staff".into()))],
+            ..AccessPolicy::default()
+        });
+        assert_eq!(handler.handle(&signed("DESCRIBE")).status_code, 403);
+        handler.session_manager.access().set_policy(AccessPolicy {
+            rules: vec![AccessRule::new("stream").allow(Principal::TokenHolder)],
+            ..AccessPolicy::default()
+        });
+        assert_eq!(handler.handle(&signed("DESCRIBE")).status_code, 200);
+
+        handler
+            .session_manager
+            .access()
+            .set_policy(AccessPolicy::default());
+        handler.signed_paths.insert("stream".to_string(), 1);
+        assert_eq!(handler.handle(&request("DESCRIBE", None)).status_code, 403);
+    }
+
+    #[test]
+    fn play_without_session_is_invalid_in_init() {
+        let response = handler().handle(&request("PLAY", None));
+        assert_eq!(response.status_code, 455);
+        assert_eq!(
+            header(&response, "Allow"),
+            Some("OPTIONS, DESCRIBE, ANNOUNCE, SETUP, TEARDOWN, GET_PARAMETER, SET_PARAMETER")
+        );
+    }
+
+    #[test]
+    fn pause_before_play_is_invalid() {
+        let mut handler = handler();
+        let session = handler
+            .session_manager
+            .create_session("rtsp://localhost/stream");
+
+        let response = handler.handle(&request("PAUSE", Some(&session.id)));
+        assert_eq!(response.status_code, 455);
+        assert!(header(&response, "Allow").unwrap().contains("PLAY"));
+        assert_eq!(session.get_state(), SessionState::Ready);
+    }
+
+    #[test]
+    fn play_requires_transport() {
+        let mut handler = handler();
+        let session = handler
+            .session_manager
+            .create_session("rtsp://localhost/stream");
+
+        let response = handler.handle(&request("PLAY", Some(&session.id)));
+        assert_eq!(response.status_code, 455);
+        assert_eq!(session.get_state(), SessionState::Ready);
+    }
+
+    #[test]
+    fn play_reports_next_packet_and_position() {
+        let mut handler = handler();
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 3\r\n\
+                   Transport: RTP/AVP;unicast;client_port=50000-50001\r\n\r\n";
+        let response = handler.handle(&RtspRequest::parse(raw).unwrap());
+        let session = SessionHeader::parse(header(&response, "Session").unwrap()).unwrap();
+
+        // 1.5 s of media: three frames of one packet each.
+        for _ in 0..3 {
+            handler
+                .packetizer
+                .lock()
+                .packetize(&[0, 0, 0, 1, 0x65, 0x88], 45000);
+        }
+
+        let response = handler.handle(&request("PLAY", Some(&session.id)));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            header(&response, "RTP-Info"),
+            Some("url=rtsp://localhost/stream/track1;seq=3;rtptime=135000")
+        );
+        assert_eq!(header(&response, "Range"), Some("npt=1.500-"));
+    }
+
+    #[test]
+    fn other_connections_cannot_use_session() {
+        let mut owner = handler();
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 3\r\n\
+                   Transport: RTP/AVP;unicast;client_port=50000-50001\r\n\r\n";
+        let response = owner.handle(&RtspRequest::parse(raw).unwrap());
+        let id = SessionHeader::parse(header(&response, "Session").unwrap())
+            .unwrap()
+            .id;
+        let session = owner.session_manager.get_session(&id).unwrap();
+
+        let mut intruder = MethodHandler::new(
+            owner.session_manager.clone(),
+            "127.0.0.2:50000".parse().unwrap(),
+            owner.packetizer.clone(),
+        );
+        let response = intruder.handle(&request("TEARDOWN", Some(&session.id)));
+        assert_eq!(response.status_code, 454);
+        assert!(owner.session_manager.get_session(&session.id).is_some());
+
+        assert!(!session.allows_handoff());
+
+        // The creating client can opt its session into hand-off.
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 4\r\n\
+                   Require: rtsp.session-handoff\r\n\
+                   Transport: RTP/AVP;unicast;client_port=50002-50003\r\n\r\n";
+        let response = owner.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        let id = SessionHeader::parse(header(&response, "Session").unwrap())
+            .unwrap()
+            .id;
+        let session = owner.session_manager.get_session(&id).unwrap();
+        assert!(session.allows_handoff());
+
+        let response = intruder.handle(&request("GET_PARAMETER", Some(&session.id)));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(session.owner(), Some(intruder.connection_id));
+        assert!(!owner.sessions_to_clean_up().contains(&session.id));
+    }
+
+    #[test]
+    fn setup_picks_first_supported_transport() {
+        let mut handler = handler();
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 3\r\n\
+                   Transport: RTP/AVP/TCP;unicast;interleaved=0-1,RTP/AVP;unicast;client_port=50000-50001\r\n\r\n";
+        let response = handler.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        let transport = header(&response, "Transport").unwrap();
+        assert!(transport.starts_with("RTP/AVP;unicast;client_port=50000-50001;server_port="));
+    }
+
+    #[test]
+    fn interleaved_viewer_reports_update_its_stats() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let mut handler = handler();
+        handler.set_writer(Arc::new(ConnectionWriter::new(server).unwrap()));
+
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 3\r\n\
+                   Transport: RTP/AVP/TCP;unicast;interleaved=2-3,RTP/AVP;unicast;client_port=50000-50001\r\n\r\n";
+        let response = handler.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            header(&response, "Transport"),
+            Some("RTP/AVP/TCP;unicast;interleaved=2-3")
+        );
+        let id = SessionHeader::parse(header(&response, "Session").unwrap())
+            .unwrap()
+            .id;
+        let session = handler.session_manager.get_session(&id).unwrap();
+
+        let rtp = [0x80, 96, 0, 1, 0, 0, 0, 0, 0, 0, 0x12, 0x34, 0x65];
+        crate::transport::udp::send_rtp(&session, &rtp).unwrap();
+        let mut frame = [0u8; 4 + 13];
+        std::io::Read::read_exact(&mut client, &mut frame).unwrap();
+        assert_eq!(frame[..4], [b'$', 2, 0, 13]);
+        assert_eq!(frame[4..], rtp);
+
+        let report = serialize_compound(&[RtcpPacket::ReceiverReport(ReceiverReport {
+            ssrc: 0xBEEF,
+            reports: vec![ReportBlock {
+                ssrc: 0x1234,
+                fraction_lost: 64,
+                cumulative_lost: 10,
+                highest_sequence: 1,
+                jitter: 900,
+                last_sr: 0,
+                delay_since_last_sr: 0,
+            }],
+        })]);
+        handler.handle_interleaved(3, &report);
+
+        let viewer = Viewer::of(&session, 90000).unwrap();
+        assert_eq!(viewer.packets_sent, 1);
+        assert_eq!(viewer.fraction_lost, Some(0.25));
+        assert_eq!(viewer.cumulative_lost, Some(10));
+    }
+
+    #[test]
+    fn setup_rejects_unsupported_transports() {
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 3\r\n\
+                   Transport: RTP/AVP;multicast;port=5000-5001,RTP/SAVP;unicast;client_port=6000-6001\r\n\r\n";
+        let response = handler().handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 461);
+    }
+
+    #[test]
+    fn unknown_required_option_is_not_supported() {
+        let mut handler = handler();
+        handler
+            .session_manager
+            .features()
+            .register("com.example.replay");
+        let raw = "OPTIONS * RTSP/1.0\r\nCSeq: 1\r\nRequire: com.example.replay\r\n\
+                   Proxy-Require: play.basic, com.example.replay\r\n\r\n";
+        let response = handler.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 551);
+        assert_eq!(header(&response, "Unsupported"), Some("play.basic"));
+
+        let raw = "OPTIONS * RTSP/1.0\r\nCSeq: 2\r\nRequire: com.example.replay\r\n\r\n";
+        let response = handler.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 200);
+    }
+
+    fn parameter_request(method: &str, content_type: &str, body: &str) -> RtspRequest {
+        let raw = format!(
+            "{} rtsp://localhost/stream RTSP/1.0\r\nCSeq: 5\r\nContent-Type: {}\r\n\
+             Content-Length: {}\r\n\r\n",
+            method,
+            content_type,
+            body.len()
+        );
+        let mut request = RtspRequest::parse(&raw).unwrap();
+        request.body = body.as_bytes().to_vec();
+        request
+    }
+
+    #[test]
+    fn get_parameter_returns_application_values() {
+        let mut handler = handler();
+        handler
+            .session_manager
+            .parameters()
+            .set_getter(|name, _| (name == "bitrate").then(|| "2000000".to_string()));
+
+        let response = handler.handle(&parameter_request(
+            "GET_PARAMETER",
+            "text/parameters",
+            "bitrate\r\n",
+        ));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body.as_deref(), Some("bitrate: 2000000\r\n"));
+
+        let response = handler.handle(&parameter_request(
+            "GET_PARAMETER",
+            "text/parameters",
+            "zoom\r\n",
+        ));
+        assert_eq!(response.status_code, 451);
+        assert_eq!(response.body.as_deref(), Some("zoom\r\n"));
+    }
+
+    #[test]
+    fn set_parameter_delivers_pairs_to_application() {
+        let mut handler = handler();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        handler
+            .session_manager
+            .parameters()
+            .set_setter(move |request| {
+                sink.lock().extend(request.parameters.iter().cloned());
+                Ok(())
+            });
+
+        let response = handler.handle(&parameter_request(
+            "SET_PARAMETER",
+            "text/parameters",
+            "bitrate: 500000\r\n",
+        ));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            *received.lock(),
+            [("bitrate".to_string(), "500000".to_string())]
+        );
+
+        let response = handler.handle(&parameter_request(
+            "SET_PARAMETER",
+            "application/json",
+            "{}",
+        ));
+        assert_eq!(response.status_code, 415);
+    }
+
+    const ANNOUNCED_SDP: &str = "v=0\r\ns=No Name\r\nt=0 0\r\n\
+                                 m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:streamid=0\r\n\
+                                 m=audio 0 RTP/AVP 0\r\na=control:streamid=1\r\n";
+
+    fn announce(path: &str) -> RtspRequest {
+        let raw = format!(
+            "ANNOUNCE rtsp://localhost/{path} RTSP/1.0\r\nCSeq: 1\r\n\
+             Content-Type: application/sdp\r\nContent-Length: {}\r\n\r\n",
+            ANNOUNCED_SDP.len()
+        );
+        let mut request = RtspRequest::parse(&raw).unwrap();
+        request.body = ANNOUNCED_SDP.as_bytes().to_vec();
+        request
+    }
+
+    #[test]
+    fn announce_follows_publish_policy() {
+        let mut handler = handler();
+        assert_eq!(handler.handle(&announce("live/cam1")).status_code, 403);
+
+        handler
+            .session_manager
+            .mounts()
+            .set_publish_policy(mount::PublishPolicy::Any);
+        assert_eq!(handler.handle(&announce("live/cam1")).status_code, 200);
+        assert_eq!(handler.handle(&announce("live/cam1")).status_code, 403);
+
+        // Announced but never published: removed with the connection.
+        handler.release_announced_mounts();
+        assert!(handler.session_manager.mounts().get("live/cam1").is_none());
+    }
+
+    #[test]
+    fn publisher_records_over_interleaved_tcp() {
+        let mut publisher = handler();
+        let mounts = publisher.session_manager.mounts().clone();
+        mounts.set_publish_policy(mount::PublishPolicy::Any);
+        assert_eq!(publisher.handle(&announce("live/cam1")).status_code, 200);
+
+        let raw = "SETUP rtsp://localhost/live/cam1/streamid=0 RTSP/1.0\r\nCSeq: 2\r\n\
+                   Transport: RTP/AVP/TCP;unicast;interleaved=0-1;mode=record\r\n\r\n";
+        let response = publisher.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            header(&response, "Transport"),
+            Some("RTP/AVP/TCP;unicast;interleaved=0-1;mode=record")
+        );
+        let session = SessionHeader::parse(header(&response, "Session").unwrap()).unwrap();
+
+        let response = publisher.handle(&request("RECORD", Some(&session.id)));
+        assert_eq!(response.status_code, 200);
+        let mount = mounts.get("live/cam1").unwrap();
+        assert_eq!(mount.publisher(), Some(session.id.clone()));
+
+        // The RTP channel feeds the mount; the RTCP channel is ignored.
+        let mut header = crate::media::rtp::RtpHeader::new(96, 9);
+        header.advance_timestamp(4500);
+        publisher.handle_interleaved(0, &header.write(true));
+        publisher.handle_interleaved(1, &[0x80, 200, 0, 1]);
+
+        // A viewer can't set up the two-track mount by its aggregate URL.
+        let mut viewer = MethodHandler::new(
+            publisher.session_manager.clone(),
+            "127.0.0.1:50002".parse().unwrap(),
+            publisher.packetizer.clone(),
+        );
+        let raw = "SETUP rtsp://localhost/live/cam1 RTSP/1.0\r\nCSeq: 1\r\n\
+                   Transport: RTP/AVP;unicast;client_port=50002-50003\r\n\r\n";
+        assert_eq!(
+            viewer.handle(&RtspRequest::parse(raw).unwrap()).status_code,
+            459
+        );
+
+        let response = publisher.handle(&request("TEARDOWN", Some(&session.id)));
+        assert_eq!(response.status_code, 200);
+        assert!(mount.is_closed());
+        assert!(mounts.get("live/cam1").is_none());
+    }
+
+    #[test]
+    fn publisher_records_over_udp() {
+        use std::net::UdpSocket;
+        use std::time::Duration;
+
+        let mut publisher = handler();
+        let manager = publisher.session_manager.clone();
+        manager
+            .mounts()
+            .set_publish_policy(mount::PublishPolicy::Any);
+        assert_eq!(publisher.handle(&announce("live/cam1")).status_code, 200);
+
+        let raw = "SETUP rtsp://localhost/live/cam1/streamid=0 RTSP/1.0\r\nCSeq: 2\r\n\
+                   Transport: RTP/AVP;unicast;client_port=50000-50001;mode=record\r\n\r\n";
+        let response = publisher.handle(&RtspRequest::parse(raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        let spec = TransportSpec::parse(header(&response, "Transport").unwrap()).unwrap();
+        let (server_rtp_port, _) = spec.server_port.unwrap();
+        let session = SessionHeader::parse(header(&response, "Session").unwrap()).unwrap();
+        assert_eq!(manager.port_pool().in_use(), 1);
+
+        // Setting the track up again reuses its receiver.
+        let raw = format!(
+            "SETUP rtsp://localhost/live/cam1/streamid=0 RTSP/1.0\r\nCSeq: 3\r\nSession: {}\r\n\
+             Transport: RTP/AVP;unicast;client_port=50000-50001;mode=record\r\n\r\n",
+            session.id
+        );
+        let response = publisher.handle(&RtspRequest::parse(&raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        let again = TransportSpec::parse(header(&response, "Transport").unwrap()).unwrap();
+        assert_eq!(again.server_port, spec.server_port);
+        assert_eq!(manager.port_pool().in_use(), 1);
+        assert_eq!(
+            publisher
+                .handle(&request("RECORD", Some(&session.id)))
+                .status_code,
+            200
+        );
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let client_port = client.local_addr().unwrap().port();
+        let mut viewer = MethodHandler::new(
+            manager.clone(),
+            SocketAddr::new("127.0.0.1".parse().unwrap(), client_port),
+            publisher.packetizer.clone(),
+        );
+        let raw = format!(
+            "SETUP rtsp://localhost/live/cam1/streamid=0 RTSP/1.0\r\nCSeq: 1\r\n\
+             Transport: RTP/AVP;unicast;client_port={client_port}-{}\r\n\r\n",
+            client_port + 1
+        );
+        let response = viewer.handle(&RtspRequest::parse(&raw).unwrap());
+        assert_eq!(response.status_code, 200);
+        let viewing = SessionHeader::parse(header(&response, "Session").unwrap()).unwrap();
+        let raw = format!(
+            "PLAY rtsp://localhost/live/cam1 RTSP/1.0\r\nCSeq: 2\r\nSession: {}\r\n\r\n",
+            viewing.id
+        );
+        assert_eq!(
+            viewer
+                .handle(&RtspRequest::parse(&raw).unwrap())
+                .status_code,
+            200
+        );
+
+        // RTP sent to the server port reaches the viewer.
+        let encoder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let packet = crate::media::rtp::RtpHeader::new(96, 9).write(true);
+        let mut buf = [0u8; 1500];
+        let received = (0..20).find_map(|_| {
+            encoder
+                .send_to(&packet, ("127.0.0.1", server_rtp_port))
+                .unwrap();
+            client.recv(&mut buf).ok()
+        });
+        assert_eq!(received, Some(packet.len()));
+
+        // Ending the publisher releases its ports along with the viewer's.
+        let response = publisher.handle(&request("TEARDOWN", Some(&session.id)));
+        assert_eq!(response.status_code, 200);
+        assert!(manager.mounts().get("live/cam1").is_none());
+        assert_eq!(manager.port_pool().in_use(), 0);
+    }
+
+    #[test]
+    fn record_is_refused_on_a_playback_session() {
+        let mut handler = handler();
+        let raw = "SETUP rtsp://localhost/stream/track1 RTSP/1.0\r\nCSeq: 3\r\n\
+                   Transport: RTP/AVP;unicast;client_port=50000-50001\r\n\r\n";
+        let response = handler.handle(&RtspRequest::parse(raw).unwrap());
+        let session = SessionHeader::parse(header(&response, "Session").unwrap()).unwrap();
+
+        let response = handler.handle(&request("RECORD", Some(&session.id)));
+        assert_eq!(response.status_code, 455);
+        let allow = header(&response, "Allow").unwrap();
+        assert!(allow.contains("PLAY") && !allow.contains("RECORD"));
+        let session = handler.session_manager.get_session(&session.id).unwrap();
+        assert_eq!(session.get_state(), SessionState::Ready);
+    }
+
+    #[test]
+    fn unknown_session_is_not_found() {
+        let response = handler().handle(&request("PLAY", Some("DEADBEEF")));
+        assert_eq!(response.status_code, 454);
     }
 }
@@ -0,0 +1,364 @@
+use std::fmt;
+
+use crate::error::{ParseErrorKind, RtspError};
+
+fn invalid_header() -> RtspError {
+    RtspError::Parse {
+        kind: ParseErrorKind::InvalidHeader,
+    }
+}
+
+/// `Session` header: session ID with optional timeout (RFC 2326 §12.37).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionHeader {
+    pub id: String,
+    pub timeout: Option<u64>,
+}
+
+impl SessionHeader {
+    pub fn parse(value: &str) -> crate::error::Result<Self> {
+        let mut parts = value.split(';');
+        let id = parts.next().unwrap_or_default().trim();
+        if id.is_empty() {
+            return Err(invalid_header());
+        }
+
+        let mut timeout = None;
+        for param in parts {
+            if let Some(secs) = param.trim().strip_prefix("timeout=") {
+                timeout = Some(secs.trim().parse().map_err(|_| invalid_header())?);
+            }
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            timeout,
+        })
+    }
+}
+
+impl fmt::Display for SessionHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.timeout {
+            Some(timeout) => write!(f, "{};timeout={}", self.id, timeout),
+            None => f.write_str(&self.id),
+        }
+    }
+}
+
+/// A position in normal play time (RFC 2326 §3.6).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NptTime {
+    Now,
+    Seconds(f64),
+}
+
+impl NptTime {
+    fn parse(value: &str) -> crate::error::Result<Self> {
+        if value == "now" {
+            return Ok(Self::Now);
+        }
+        // npt-hhmmss: H:MM:SS[.fraction]
+        let seconds = match value.split(':').collect::<Vec<_>>()[..] {
+            [secs] => secs.parse::<f64>().ok(),
+            [h, m, s] => match (h.parse::<u64>(), m.parse::<u64>(), s.parse::<f64>()) {
+                (Ok(h), Ok(m), Ok(s)) => Some((h * 3600 + m * 60) as f64 + s),
+                _ => None,
+            },
+            _ => None,
+        };
+        seconds
+            .filter(|s| s.is_finite() && *s >= 0.0)
+            .map(Self::Seconds)
+            .ok_or_else(invalid_header)
+    }
+}
+
+impl fmt::Display for NptTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Now => f.write_str("now"),
+            Self::Seconds(secs) => write!(f, "{secs:.3}"),
+        }
+    }
+}
+
+/// `Range` header (RFC 2326 §12.29). Only NPT ranges are interpreted;
+/// SMPTE and clock ranges are kept verbatim.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Range {
+    Npt {
+        start: Option<NptTime>,
+        end: Option<NptTime>,
+    },
+    Other(String),
+}
+
+impl Range {
+    pub fn parse(value: &str) -> crate::error::Result<Self> {
+        // Ignore the optional ";time=" parameter.
+        let spec = value.split(';').next().unwrap_or_default().trim();
+        let Some(npt) = spec.strip_prefix("npt=") else {
+            return Ok(Self::Other(value.trim().to_string()));
+        };
+        let (start, end) = npt.split_once('-').ok_or_else(invalid_header)?;
+        let time = |t: &str| match t.trim() {
+            "" => Ok(None),
+            t => NptTime::parse(t).map(Some),
+        };
+        Ok(Self::Npt {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+
+    /// An open-ended NPT range starting at `start`, as sent for live streams.
+    pub fn npt_from(start: NptTime) -> Self {
+        Self::Npt {
+            start: Some(start),
+            end: None,
+        }
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Npt { start, end } => {
+                f.write_str("npt=")?;
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                f.write_str("-")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                Ok(())
+            }
+            Self::Other(value) => f.write_str(value),
+        }
+    }
+}
+
+/// One stream's entry in an `RTP-Info` header (RFC 2326 §12.33): the first
+/// packet the client will receive after PLAY.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpInfo {
+    pub url: String,
+    pub seq: Option<u16>,
+    pub rtptime: Option<u32>,
+}
+
+impl RtpInfo {
+    /// Parse every stream's entry in an `RTP-Info` header.
+    pub fn parse_list(value: &str) -> crate::error::Result<Vec<Self>> {
+        // URLs may contain commas, so a new entry starts only at "url=".
+        let mut entries: Vec<String> = Vec::new();
+        for part in value.split(',') {
+            match entries.last_mut() {
+                Some(last) if !part.trim_start().starts_with("url=") => {
+                    last.push(',');
+                    last.push_str(part);
+                }
+                _ => entries.push(part.to_string()),
+            }
+        }
+        entries.iter().map(|entry| Self::parse(entry)).collect()
+    }
+
+    fn parse(entry: &str) -> crate::error::Result<Self> {
+        let mut info = Self {
+            url: String::new(),
+            seq: None,
+            rtptime: None,
+        };
+        for param in entry.split(';') {
+            let (key, value) = param.trim().split_once('=').ok_or_else(invalid_header)?;
+            match key {
+                "url" => info.url = value.to_string(),
+                "seq" => info.seq = Some(value.parse().map_err(|_| invalid_header())?),
+                "rtptime" => info.rtptime = Some(value.parse().map_err(|_| invalid_header())?),
+                _ => {}
+            }
+        }
+        if info.url.is_empty() {
+            return Err(invalid_header());
+        }
+        Ok(info)
+    }
+
+    /// Serialize entries as an `RTP-Info` header value.
+    pub fn join(entries: &[Self]) -> String {
+        entries
+            .iter()
+            .map(Self::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl fmt::Display for RtpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "url={}", self.url)?;
+        if let Some(seq) = self.seq {
+            write!(f, ";seq={seq}")?;
+        }
+        if let Some(rtptime) = self.rtptime {
+            write!(f, ";rtptime={rtptime}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `Authorization` header: scheme and raw credentials (RFC 2326 §12.5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authorization {
+    pub scheme: String,
+    pub credentials: String,
+}
+
+impl Authorization {
+    pub fn parse(value: &str) -> crate::error::Result<Self> {
+        let (scheme, credentials) = value.trim().split_once(' ').ok_or_else(invalid_header)?;
+        Ok(Self {
+            scheme: scheme.to_string(),
+            credentials: credentials.trim().to_string(),
+        })
+    }
+
+    /// `key=value` / `key="value"` pairs of an auth-param list, as used by
+    /// the Digest scheme.
+    pub fn params(&self) -> Vec<(String, String)> {
+        parse_auth_params(&self.credentials)
+    }
+}
+
+/// Split a comma-separated auth-param list, honouring quoted strings.
+pub fn parse_auth_params(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = input.trim();
+
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let after = after.trim_start();
+
+        let (value, remainder) = if let Some(quoted) = after.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            (value, &quoted[end..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+
+        params.push((key, value));
+        rest = remainder.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    params
+}
+
+/// Option tags of a `Require` or `Proxy-Require` header (RFC 2326 §12.32).
+pub fn parse_option_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_header_with_timeout() {
+        let header = SessionHeader::parse("1A2B3C4D5E6F7081;timeout=30").unwrap();
+        assert_eq!(header.id, "1A2B3C4D5E6F7081");
+        assert_eq!(header.timeout, Some(30));
+        assert_eq!(header.to_string(), "1A2B3C4D5E6F7081;timeout=30");
+        assert!(SessionHeader::parse(";timeout=30").is_err());
+    }
+
+    #[test]
+    fn npt_ranges() {
+        assert_eq!(
+            Range::parse("npt=0.000-").unwrap(),
+            Range::npt_from(NptTime::Seconds(0.0))
+        );
+        assert_eq!(
+            Range::parse("npt=now-").unwrap(),
+            Range::npt_from(NptTime::Now)
+        );
+        assert_eq!(
+            Range::parse("npt=0:01:30.5-0:02:00").unwrap(),
+            Range::Npt {
+                start: Some(NptTime::Seconds(90.5)),
+                end: Some(NptTime::Seconds(120.0)),
+            }
+        );
+        assert_eq!(
+            Range::npt_from(NptTime::Seconds(1.5)).to_string(),
+            "npt=1.500-"
+        );
+        assert!(matches!(
+            Range::parse("clock=19961108T143720.25Z-").unwrap(),
+            Range::Other(_)
+        ));
+        assert!(Range::parse("npt=abc-").is_err());
+    }
+
+    #[test]
+    fn authorization_params() {
+        let auth = Authorization::parse(
+            r#"Digest username="alice", realm="rtsp", nonce="abc,def", uri="rtsp://h/s", response="00ff""#,
+        )
+        .unwrap();
+        assert_eq!(auth.scheme, "Digest");
+        let params = auth.params();
+        assert_eq!(params[0], ("username".into(), "alice".into()));
+        assert_eq!(params[2], ("nonce".into(), "abc,def".into()));
+        assert_eq!(params.len(), 5);
+    }
+
+    #[test]
+    fn rtp_info_round_trip() {
+        let value =
+            "url=rtsp://cam/stream/track1;seq=4711;rtptime=3000,url=rtsp://cam/a,b/track2;seq=7";
+        let entries = RtpInfo::parse_list(value).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].seq, Some(4711));
+        assert_eq!(entries[0].rtptime, Some(3000));
+        assert_eq!(entries[1].url, "rtsp://cam/a,b/track2");
+        assert_eq!(entries[1].rtptime, None);
+        assert_eq!(RtpInfo::join(&entries), value);
+        assert!(RtpInfo::parse_list("seq=1").is_err());
+    }
+
+    #[test]
+    fn option_tags() {
+        assert_eq!(
+            parse_option_tags("play.basic, com.example.x"),
+            ["play.basic", "com.example.x"]
+        );
+    }
+}
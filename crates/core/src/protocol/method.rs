@@ -0,0 +1,119 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An RTSP request method (RFC 2326 §6.1).
+///
+/// Method names are case-sensitive; anything not defined by the RFC is
+/// kept as an [`Method::Extension`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Options,
+    Describe,
+    Announce,
+    Setup,
+    Play,
+    Pause,
+    Record,
+    Teardown,
+    GetParameter,
+    SetParameter,
+    Redirect,
+    Extension(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Options => "OPTIONS",
+            Self::Describe => "DESCRIBE",
+            Self::Announce => "ANNOUNCE",
+            Self::Setup => "SETUP",
+            Self::Play => "PLAY",
+            Self::Pause => "PAUSE",
+            Self::Record => "RECORD",
+            Self::Teardown => "TEARDOWN",
+            Self::GetParameter => "GET_PARAMETER",
+            Self::SetParameter => "SET_PARAMETER",
+            Self::Redirect => "REDIRECT",
+            Self::Extension(name) => name,
+        }
+    }
+
+    /// Format `methods` as a `Public` or `Allow` header value.
+    pub fn join(methods: &[Method]) -> String {
+        methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl From<&str> for Method {
+    fn from(name: &str) -> Self {
+        match name {
+            "OPTIONS" => Self::Options,
+            "DESCRIBE" => Self::Describe,
+            "ANNOUNCE" => Self::Announce,
+            "SETUP" => Self::Setup,
+            "PLAY" => Self::Play,
+            "PAUSE" => Self::Pause,
+            "RECORD" => Self::Record,
+            "TEARDOWN" => Self::Teardown,
+            "GET_PARAMETER" => Self::GetParameter,
+            "SET_PARAMETER" => Self::SetParameter,
+            "REDIRECT" => Self::Redirect,
+            other => Self::Extension(other.to_string()),
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_standard_methods() {
+        for name in [
+            "OPTIONS",
+            "DESCRIBE",
+            "SETUP",
+            "PLAY",
+            "GET_PARAMETER",
+            "REDIRECT",
+        ] {
+            let method = Method::from(name);
+            assert!(!matches!(method, Method::Extension(_)));
+            assert_eq!(method.as_str(), name);
+        }
+    }
+
+    #[test]
+    fn keeps_extension_methods() {
+        assert_eq!(Method::from("FLUSH"), Method::Extension("FLUSH".into()));
+        // Method names are case-sensitive (RFC 2326 §6.1).
+        assert_eq!(Method::from("play"), Method::Extension("play".into()));
+    }
+
+    #[test]
+    fn joins_for_allow_header() {
+        assert_eq!(
+            Method::join(&[Method::Options, Method::Play]),
+            "OPTIONS, PLAY"
+        );
+    }
+}
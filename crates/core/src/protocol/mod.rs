@@ -1,8 +1,23 @@
+pub mod access;
+pub mod auth;
+pub mod features;
 pub mod handler;
+pub mod headers;
+pub mod method;
+pub mod parameters;
+pub mod reader;
 pub mod request;
 pub mod response;
 pub mod sdp;
+pub mod token;
 
+pub use access::{AccessPolicy, AccessRule, IpNet, Principal};
+pub use auth::{Auth, AuthSettings, Authenticator, DigestAlgorithm};
+pub use features::FeatureTags;
 pub use handler::MethodHandler;
+pub use method::Method;
+pub use parameters::{ParameterError, ParameterRequest, Parameters};
+pub use reader::{MessageLimits, MessageReader};
 pub use request::RtspRequest;
 pub use response::RtspResponse;
+pub use token::UrlSigner;
@@ -0,0 +1,213 @@
+use parking_lot::RwLock;
+
+/// Content type of GET_PARAMETER and SET_PARAMETER bodies.
+pub const TEXT_PARAMETERS: &str = "text/parameters";
+
+/// Why a GET_PARAMETER or SET_PARAMETER request was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterError {
+    /// The named parameters aren't known (451 Parameter Not Understood).
+    NotUnderstood(Vec<String>),
+    /// The named parameters can't be changed (458 Parameter Is Read-Only).
+    ReadOnly(Vec<String>),
+    /// A value couldn't be applied, e.g. out of range (400 Bad Request).
+    InvalidValue(Vec<String>),
+}
+
+impl ParameterError {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::NotUnderstood(_) => 451,
+            Self::ReadOnly(_) => 458,
+            Self::InvalidValue(_) => 400,
+        }
+    }
+
+    /// Parameters the error applies to, listed in the response body.
+    pub fn names(&self) -> &[String] {
+        match self {
+            Self::NotUnderstood(names) | Self::ReadOnly(names) | Self::InvalidValue(names) => names,
+        }
+    }
+}
+
+/// A GET_PARAMETER or SET_PARAMETER request passed to the application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterRequest {
+    /// Session named by the request, if any.
+    pub session_id: Option<String>,
+    /// Name/value pairs in request order. Values are empty for GET_PARAMETER.
+    pub parameters: Vec<(String, String)>,
+}
+
+type GetCallback = Box<dyn Fn(&str, Option<&str>) -> Option<String> + Send + Sync>;
+type SetCallback = Box<dyn Fn(&ParameterRequest) -> Result<(), ParameterError> + Send + Sync>;
+
+/// Application hooks answering GET_PARAMETER and applying SET_PARAMETER
+/// (RFC 2326 §10.8, §10.9).
+///
+/// Without a getter every named parameter is unknown; without a setter
+/// every SET_PARAMETER naming a parameter is refused. Requests with an
+/// empty body are keepalives and never reach the callbacks.
+#[derive(Default)]
+pub struct Parameters {
+    getter: RwLock<Option<GetCallback>>,
+    setter: RwLock<Option<SetCallback>>,
+}
+
+impl Parameters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer GET_PARAMETER with `getter(name, session_id)`, returning
+    /// `None` for parameters the application doesn't know.
+    pub fn set_getter(
+        &self,
+        getter: impl Fn(&str, Option<&str>) -> Option<String> + Send + Sync + 'static,
+    ) {
+        *self.getter.write() = Some(Box::new(getter));
+    }
+
+    /// Apply SET_PARAMETER with `setter`. All parameters of a request are
+    /// passed at once so the application can apply them all or none.
+    pub fn set_setter(
+        &self,
+        setter: impl Fn(&ParameterRequest) -> Result<(), ParameterError> + Send + Sync + 'static,
+    ) {
+        *self.setter.write() = Some(Box::new(setter));
+    }
+
+    /// Look up every parameter in `request`. Fails with all the unknown
+    /// names if any parameter is unknown.
+    pub fn get(&self, request: &ParameterRequest) -> Result<Vec<(String, String)>, ParameterError> {
+        let getter = self.getter.read();
+        let mut values = Vec::with_capacity(request.parameters.len());
+        let mut unknown = Vec::new();
+        for (name, _) in &request.parameters {
+            match getter
+                .as_ref()
+                .and_then(|getter| getter(name, request.session_id.as_deref()))
+            {
+                Some(value) => values.push((name.clone(), value)),
+                None => unknown.push(name.clone()),
+            }
+        }
+        if unknown.is_empty() {
+            Ok(values)
+        } else {
+            Err(ParameterError::NotUnderstood(unknown))
+        }
+    }
+
+    /// Hand the parameters in `request` to the application.
+    pub fn set(&self, request: &ParameterRequest) -> Result<(), ParameterError> {
+        match self.setter.read().as_ref() {
+            Some(setter) => setter(request),
+            None => Err(ParameterError::NotUnderstood(
+                request
+                    .parameters
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Debug for Parameters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Parameters")
+            .field("has_getter", &self.getter.read().is_some())
+            .field("has_setter", &self.setter.read().is_some())
+            .finish()
+    }
+}
+
+/// Parse a `text/parameters` body: one `name: value` per line, or a bare
+/// `name` in GET_PARAMETER requests. Blank lines are skipped.
+pub fn parse(body: &str) -> Vec<(String, String)> {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+            None => (line.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Serialize name/value pairs as a `text/parameters` body.
+pub fn format(parameters: &[(String, String)]) -> String {
+    parameters
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(names: &[&str]) -> ParameterRequest {
+        ParameterRequest {
+            session_id: None,
+            parameters: names
+                .iter()
+                .map(|n| (n.to_string(), String::new()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parses_names_and_pairs() {
+        assert_eq!(
+            parse("position\r\n\r\nbitrate: 2000000\r\noverlay: Gate 3: north\r\n"),
+            [
+                ("position".to_string(), String::new()),
+                ("bitrate".to_string(), "2000000".to_string()),
+                ("overlay".to_string(), "Gate 3: north".to_string()),
+            ]
+        );
+        let pairs = parse("bitrate: 2000000\r\n");
+        assert_eq!(format(&pairs), "bitrate: 2000000\r\n");
+    }
+
+    #[test]
+    fn unknown_parameters_are_not_understood() {
+        let parameters = Parameters::new();
+        assert_eq!(
+            parameters.get(&request(&["bitrate"])),
+            Err(ParameterError::NotUnderstood(vec!["bitrate".into()]))
+        );
+
+        parameters.set_getter(|name, _| (name == "bitrate").then(|| "2000000".to_string()));
+        assert_eq!(
+            parameters.get(&request(&["bitrate"])).unwrap(),
+            [("bitrate".to_string(), "2000000".to_string())]
+        );
+        let error = parameters
+            .get(&request(&["bitrate", "zoom", "tilt"]))
+            .unwrap_err();
+        assert_eq!(error.names(), ["zoom", "tilt"]);
+        assert_eq!(error.status_code(), 451);
+    }
+
+    #[test]
+    fn setter_receives_whole_request() {
+        let parameters = Parameters::new();
+        assert!(parameters.set(&request(&["bitrate"])).is_err());
+
+        parameters.set_setter(|request| {
+            match request.parameters.iter().find(|(n, _)| n == "position") {
+                Some((name, _)) => Err(ParameterError::ReadOnly(vec![name.clone()])),
+                None => Ok(()),
+            }
+        });
+        assert!(parameters.set(&request(&["bitrate", "overlay"])).is_ok());
+        let error = parameters
+            .set(&request(&["bitrate", "position"]))
+            .unwrap_err();
+        assert_eq!(error.status_code(), 458);
+    }
+}
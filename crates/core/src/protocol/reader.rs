@@ -0,0 +1,418 @@
+use crate::error::{ParseErrorKind, RtspError};
+use crate::protocol::request::RtspRequest;
+use crate::protocol::response::RtspResponse;
+
+/// Size limits for messages read from a client connection.
+#[derive(Debug, Clone)]
+pub struct MessageLimits {
+    /// Largest request line plus header block, in bytes.
+    pub max_header_bytes: usize,
+    /// Most header lines accepted in one request.
+    pub max_headers: usize,
+    /// Largest request body (`Content-Length`), in bytes.
+    pub max_body_bytes: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_header_bytes: 16 * 1024,
+            max_headers: 64,
+            max_body_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// A complete message read from the connection.
+#[derive(Debug)]
+pub enum Message {
+    Request(RtspRequest),
+    /// A response, read by clients or when a client answers a request the
+    /// server sent.
+    Response(RtspResponse),
+    /// A `$`-framed binary packet interleaved on the RTSP connection
+    /// (RFC 2326 §10.12).
+    Interleaved {
+        channel: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// A request the reader could not accept.
+#[derive(Debug)]
+pub struct MessageError {
+    pub error: RtspError,
+    /// CSeq of the rejected request, if it could be recovered.
+    pub cseq: Option<String>,
+}
+
+impl MessageError {
+    fn parse(kind: ParseErrorKind, cseq: Option<String>) -> Self {
+        Self {
+            error: RtspError::Parse { kind },
+            cseq,
+        }
+    }
+
+    /// Whether message framing was lost, so the connection must be closed
+    /// after answering.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self.error,
+            RtspError::Parse {
+                kind: ParseErrorKind::HeadersTooLarge | ParseErrorKind::InvalidContentLength
+            }
+        )
+    }
+}
+
+/// Byte-oriented incremental RTSP message reader.
+///
+/// Feed it whatever the socket returned and pull complete messages out with
+/// [`MessageReader::next_message`]. Handles pipelined requests, request
+/// bodies sized by `Content-Length`, interleaved `$` frames and header bytes
+/// that aren't valid UTF-8 (decoded lossily).
+#[derive(Debug)]
+pub struct MessageReader {
+    buf: Vec<u8>,
+    limits: MessageLimits,
+    /// Body bytes of a rejected request still to be dropped.
+    discard: usize,
+}
+
+impl MessageReader {
+    pub fn new(limits: MessageLimits) -> Self {
+        Self {
+            buf: Vec::new(),
+            limits,
+            discard: 0,
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        let skipped = self.discard.min(data.len());
+        self.discard -= skipped;
+        self.buf.extend_from_slice(&data[skipped..]);
+    }
+
+    /// Bytes received but not yet consumed as a message.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Take the next complete message, `Ok(None)` if more data is needed.
+    pub fn next_message(&mut self) -> Result<Option<Message>, MessageError> {
+        // Stray line breaks between messages are allowed.
+        let leading = self
+            .buf
+            .iter()
+            .take_while(|b| matches!(b, b'\r' | b'\n'))
+            .count();
+        self.buf.drain(..leading);
+
+        match self.buf.first() {
+            None => Ok(None),
+            Some(b'$') => Ok(self.next_interleaved()),
+            Some(_) => self.next_request(),
+        }
+    }
+
+    fn next_interleaved(&mut self) -> Option<Message> {
+        let header = self.buf.get(..4)?;
+        let channel = header[1];
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if self.buf.len() < 4 + len {
+            return None;
+        }
+        let data = self.buf[4..4 + len].to_vec();
+        self.buf.drain(..4 + len);
+        Some(Message::Interleaved { channel, data })
+    }
+
+    fn next_request(&mut self) -> Result<Option<Message>, MessageError> {
+        let Some(header_len) = find_header_end(&self.buf) else {
+            if self.buf.len() > self.limits.max_header_bytes {
+                let cseq = recover_cseq(&String::from_utf8_lossy(&self.buf));
+                self.buf.clear();
+                return Err(MessageError::parse(ParseErrorKind::HeadersTooLarge, cseq));
+            }
+            return Ok(None);
+        };
+
+        let head = String::from_utf8_lossy(&self.buf[..header_len]).into_owned();
+        let cseq = recover_cseq(&head);
+
+        if header_len > self.limits.max_header_bytes {
+            self.buf.clear();
+            return Err(MessageError::parse(ParseErrorKind::HeadersTooLarge, cseq));
+        }
+
+        let content_length = match content_length(&head) {
+            Ok(len) => len,
+            Err(kind) => {
+                self.buf.clear();
+                return Err(MessageError::parse(kind, cseq));
+            }
+        };
+
+        if content_length > self.limits.max_body_bytes {
+            self.buf.drain(..header_len);
+            let buffered = content_length.min(self.buf.len());
+            self.buf.drain(..buffered);
+            self.discard = content_length - buffered;
+            return Err(MessageError::parse(ParseErrorKind::BodyTooLarge, cseq));
+        }
+
+        if self.buf.len() < header_len + content_length {
+            return Ok(None);
+        }
+
+        let body = self.buf[header_len..header_len + content_length].to_vec();
+        self.buf.drain(..header_len + content_length);
+
+        if head.lines().skip(1).filter(|l| !l.is_empty()).count() > self.limits.max_headers {
+            return Err(MessageError::parse(ParseErrorKind::TooManyHeaders, cseq));
+        }
+
+        if head.starts_with("RTSP/") {
+            return match RtspResponse::parse(&head) {
+                Ok(response) if body.is_empty() => Ok(Some(Message::Response(response))),
+                Ok(response) => {
+                    let body = String::from_utf8_lossy(&body).into_owned();
+                    Ok(Some(Message::Response(response.with_body(body))))
+                }
+                Err(error) => Err(MessageError { error, cseq }),
+            };
+        }
+
+        match RtspRequest::parse(&head) {
+            Ok(mut request) => {
+                request.body = body;
+                Ok(Some(Message::Request(request)))
+            }
+            Err(error) => Err(MessageError { error, cseq }),
+        }
+    }
+}
+
+impl Default for MessageReader {
+    fn default() -> Self {
+        Self::new(MessageLimits::default())
+    }
+}
+
+/// Length of the request line and headers including the blank line that
+/// ends them (`\r\n\r\n`, or bare `\n\n` from lenient clients).
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < buf.len() {
+        if buf[i] == b'\n' {
+            match buf.get(i + 1) {
+                Some(b'\n') => return Some(i + 2),
+                Some(b'\r') if buf.get(i + 2) == Some(&b'\n') => return Some(i + 3),
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+fn content_length(head: &str) -> Result<usize, ParseErrorKind> {
+    match header_value(head, "Content-Length") {
+        Some(value) => value
+            .parse()
+            .map_err(|_| ParseErrorKind::InvalidContentLength),
+        None => Ok(0),
+    }
+}
+
+/// The CSeq of a request, read leniently so that error responses can echo
+/// it even when the rest of the request is unusable.
+pub fn recover_cseq(head: &str) -> Option<String> {
+    header_value(head, "CSeq")
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(message: Option<Message>) -> RtspRequest {
+        match message {
+            Some(Message::Request(request)) => request,
+            other => panic!("expected a request, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reads_request_in_pieces() {
+        let mut reader = MessageReader::default();
+        reader.feed(b"OPTIONS rtsp://localhost RTSP/1.0\r\nCS");
+        assert!(reader.next_message().unwrap().is_none());
+        reader.feed(b"eq: 1\r\n\r\n");
+        let req = request(reader.next_message().unwrap());
+        assert_eq!(req.cseq(), Some(1));
+        assert_eq!(reader.buffered(), 0);
+    }
+
+    #[test]
+    fn reads_body_and_pipelined_request() {
+        let mut reader = MessageReader::default();
+        reader.feed(
+            b"SET_PARAMETER rtsp://localhost RTSP/1.0\r\nCSeq: 2\r\nContent-Length: 10\r\n\r\n\
+              foo: bar\r\n\
+              OPTIONS rtsp://localhost RTSP/1.0\r\nCSeq: 3\r\n\r\n",
+        );
+        let first = request(reader.next_message().unwrap());
+        assert_eq!(first.body, b"foo: bar\r\n");
+        let second = request(reader.next_message().unwrap());
+        assert_eq!(second.method, crate::protocol::Method::Options);
+        assert_eq!(second.cseq(), Some(3));
+    }
+
+    #[test]
+    fn waits_for_complete_body() {
+        let mut reader = MessageReader::default();
+        reader
+            .feed(b"ANNOUNCE rtsp://localhost RTSP/1.0\r\nCSeq: 2\r\nContent-Length: 4\r\n\r\nv=");
+        assert!(reader.next_message().unwrap().is_none());
+        reader.feed(b"0\r\n");
+        assert_eq!(request(reader.next_message().unwrap()).body, b"v=0\r");
+    }
+
+    #[test]
+    fn reads_interleaved_frames_between_requests() {
+        let mut reader = MessageReader::default();
+        reader.feed(&[b'$', 1, 0, 3, 0xFF, 0xFE, 0x80]);
+        reader.feed(b"GET_PARAMETER rtsp://localhost RTSP/1.0\r\nCSeq: 4\r\n\r\n");
+        match reader.next_message().unwrap() {
+            Some(Message::Interleaved { channel, data }) => {
+                assert_eq!(channel, 1);
+                assert_eq!(data, [0xFF, 0xFE, 0x80]);
+            }
+            other => panic!("expected interleaved frame, got {other:?}"),
+        }
+        assert_eq!(request(reader.next_message().unwrap()).cseq(), Some(4));
+    }
+
+    #[test]
+    fn reads_responses_with_body() {
+        let mut reader = MessageReader::default();
+        reader.feed(
+            b"RTSP/1.0 200 OK\r\nCSeq: 2\r\nContent-Length: 5\r\n\r\nv=0\r\n$\x00\x00\x01\x80",
+        );
+        match reader.next_message().unwrap() {
+            Some(Message::Response(response)) => {
+                assert_eq!(response.get_cseq(), Some(2));
+                assert_eq!(response.body.as_deref(), Some("v=0\r\n"));
+            }
+            other => panic!("expected a response, got {other:?}"),
+        }
+        assert!(matches!(
+            reader.next_message().unwrap(),
+            Some(Message::Interleaved { channel: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn tolerates_non_utf8_headers() {
+        let mut reader = MessageReader::default();
+        reader
+            .feed(b"OPTIONS rtsp://localhost RTSP/1.0\r\nCSeq: 5\r\nUser-Agent: \xFF\xFE\r\n\r\n");
+        assert_eq!(request(reader.next_message().unwrap()).cseq(), Some(5));
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let mut reader = MessageReader::new(MessageLimits {
+            max_header_bytes: 64,
+            ..MessageLimits::default()
+        });
+        reader.feed(b"OPTIONS rtsp://localhost RTSP/1.0\r\nCSeq: 6\r\n");
+        reader.feed(&[b'a'; 64]);
+        let err = reader.next_message().unwrap_err();
+        assert!(matches!(
+            err.error,
+            RtspError::Parse {
+                kind: ParseErrorKind::HeadersTooLarge
+            }
+        ));
+        assert_eq!(err.cseq.as_deref(), Some("6"));
+        assert!(err.is_fatal());
+    }
+
+    #[test]
+    fn rejects_too_many_headers() {
+        let mut reader = MessageReader::new(MessageLimits {
+            max_headers: 2,
+            ..MessageLimits::default()
+        });
+        reader.feed(b"OPTIONS rtsp://localhost RTSP/1.0\r\nCSeq: 7\r\nA: 1\r\nB: 2\r\n\r\n");
+        let err = reader.next_message().unwrap_err();
+        assert!(matches!(
+            err.error,
+            RtspError::Parse {
+                kind: ParseErrorKind::TooManyHeaders
+            }
+        ));
+        assert!(!err.is_fatal());
+    }
+
+    #[test]
+    fn skips_oversized_body_and_continues() {
+        let mut reader = MessageReader::new(MessageLimits {
+            max_body_bytes: 4,
+            ..MessageLimits::default()
+        });
+        reader.feed(
+            b"ANNOUNCE rtsp://localhost RTSP/1.0\r\nCSeq: 8\r\nContent-Length: 10\r\n\r\n0123",
+        );
+        let err = reader.next_message().unwrap_err();
+        assert!(matches!(
+            err.error,
+            RtspError::Parse {
+                kind: ParseErrorKind::BodyTooLarge
+            }
+        ));
+        assert_eq!(err.cseq.as_deref(), Some("8"));
+        assert!(!err.is_fatal());
+
+        reader.feed(b"456789OPTIONS rtsp://localhost RTSP/1.0\r\nCSeq: 9\r\n\r\n");
+        assert_eq!(request(reader.next_message().unwrap()).cseq(), Some(9));
+    }
+
+    #[test]
+    fn recovers_cseq_of_malformed_request() {
+        let mut reader = MessageReader::default();
+        reader.feed(b"BROKEN\r\nCSeq: 11\r\n\r\n");
+        let err = reader.next_message().unwrap_err();
+        assert_eq!(err.error.status_code(), 400);
+        assert_eq!(err.cseq.as_deref(), Some("11"));
+        assert!(!err.is_fatal());
+    }
+
+    #[test]
+    fn rejects_invalid_content_length() {
+        let mut reader = MessageReader::default();
+        reader.feed(
+            b"ANNOUNCE rtsp://localhost RTSP/1.0\r\nCSeq: 10\r\nContent-Length: lots\r\n\r\n",
+        );
+        let err = reader.next_message().unwrap_err();
+        assert!(matches!(
+            err.error,
+            RtspError::Parse {
+                kind: ParseErrorKind::InvalidContentLength
+            }
+        ));
+        assert!(err.is_fatal());
+    }
+}
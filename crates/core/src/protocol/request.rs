@@ -1,33 +1,81 @@
 use crate::error::{ParseErrorKind, RtspError};
+use crate::protocol::headers::{self, Authorization, Range, SessionHeader};
+use crate::protocol::method::Method;
+use crate::session::transport::TransportHeader;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RtspRequest {
-    pub method: String,
+    pub method: Method,
     pub uri: String,
     pub version: String,
     pub headers: Vec<(String, String)>,
+    /// Message body, `Content-Length` bytes following the headers.
+    pub body: Vec<u8>,
 }
 
 impl RtspRequest {
+    /// A request to send, e.g. from [`crate::client::RtspClient`].
+    pub fn new(method: Method, uri: &str) -> Self {
+        RtspRequest {
+            method,
+            uri: uri.to_string(),
+            version: "RTSP/1.0".to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn add_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_cseq(self, cseq: u32) -> Self {
+        self.add_header("CSeq", &cseq.to_string())
+    }
+
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Wire form of the request, with `Content-Length` for a body.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut request = format!("{} {} {}\r\n", self.method, self.uri, self.version);
+        for (name, value) in &self.headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !self.body.is_empty() {
+            request.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        request.push_str("\r\n");
+
+        let mut bytes = request.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
     pub fn parse(raw: &str) -> crate::error::Result<Self> {
         let mut lines = raw.lines();
 
-        let request_line = lines
-            .next()
-            .ok_or(RtspError::Parse { kind: ParseErrorKind::EmptyRequest })?;
+        let request_line = lines.next().ok_or(RtspError::Parse {
+            kind: ParseErrorKind::EmptyRequest,
+        })?;
 
         let parts: Vec<&str> = request_line.split_whitespace().collect();
 
         if parts.len() != 3 {
-            return Err(RtspError::Parse { kind: ParseErrorKind::InvalidRequestLine });
+            return Err(RtspError::Parse {
+                kind: ParseErrorKind::InvalidRequestLine,
+            });
         }
 
-        let method = parts[0].to_string();
+        let method = Method::from(parts[0]);
         let uri = parts[1].to_string();
         let version = parts[2].to_string();
 
         if version != "RTSP/1.0" {
-            tracing::warn!(version, "client sent non-RTSP/1.0 version");
+            return Err(RtspError::UnsupportedVersion(version));
         }
 
         let mut headers = Vec::new();
@@ -47,12 +95,30 @@ impl RtspRequest {
             headers.push((name, value));
         }
 
-        Ok(RtspRequest {
+        let request = RtspRequest {
             method,
             uri,
             version,
             headers,
-        })
+            body: Vec::new(),
+        };
+
+        // Every request must carry a CSeq (RFC 2326 §12.17).
+        match request.get_header("CSeq") {
+            None => {
+                return Err(RtspError::Parse {
+                    kind: ParseErrorKind::MissingCSeq,
+                });
+            }
+            Some(_) if request.cseq().is_none() => {
+                return Err(RtspError::Parse {
+                    kind: ParseErrorKind::InvalidCSeq,
+                });
+            }
+            Some(_) => {}
+        }
+
+        Ok(request)
     }
 
     pub fn get_header(&self, name: &str) -> Option<&str> {
@@ -63,8 +129,58 @@ impl RtspRequest {
     }
 
     /// CSeq (Command Sequence) numbers and orders RTSP requests & responses (RFC 2326 §12.17).
-    pub fn cseq(&self) -> Option<&str> {
-        self.get_header("CSeq")
+    pub fn cseq(&self) -> Option<u32> {
+        self.get_header("CSeq")?.parse().ok()
+    }
+
+    pub fn session(&self) -> crate::error::Result<Option<SessionHeader>> {
+        self.typed_header("Session", SessionHeader::parse)
+    }
+
+    pub fn transport(&self) -> crate::error::Result<Option<TransportHeader>> {
+        self.typed_header("Transport", |value| {
+            TransportHeader::parse(value).ok_or(RtspError::Parse {
+                kind: ParseErrorKind::InvalidHeader,
+            })
+        })
+    }
+
+    pub fn range(&self) -> crate::error::Result<Option<Range>> {
+        self.typed_header("Range", Range::parse)
+    }
+
+    pub fn content_length(&self) -> crate::error::Result<Option<usize>> {
+        self.typed_header("Content-Length", |value| {
+            value.parse().map_err(|_| RtspError::Parse {
+                kind: ParseErrorKind::InvalidContentLength,
+            })
+        })
+    }
+
+    pub fn authorization(&self) -> crate::error::Result<Option<Authorization>> {
+        self.typed_header("Authorization", Authorization::parse)
+    }
+
+    /// Option tags the client requires the server to support.
+    pub fn require(&self) -> Vec<String> {
+        self.get_header("Require")
+            .map(headers::parse_option_tags)
+            .unwrap_or_default()
+    }
+
+    /// Option tags the client requires any proxy to support.
+    pub fn proxy_require(&self) -> Vec<String> {
+        self.get_header("Proxy-Require")
+            .map(headers::parse_option_tags)
+            .unwrap_or_default()
+    }
+
+    fn typed_header<T>(
+        &self,
+        name: &str,
+        parse: impl FnOnce(&str) -> crate::error::Result<T>,
+    ) -> crate::error::Result<Option<T>> {
+        self.get_header(name).map(parse).transpose()
     }
 }
 
@@ -72,14 +188,30 @@ impl RtspRequest {
 mod tests {
     use super::*;
 
+    #[test]
+    fn serialized_request_parses() {
+        let request = RtspRequest::new(Method::SetParameter, "rtsp://localhost/live")
+            .with_cseq(4)
+            .add_header("Session", "ABCD")
+            .with_body(b"bitrate: 2000\r\n".to_vec());
+        let bytes = request.serialize();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("SET_PARAMETER rtsp://localhost/live RTSP/1.0\r\nCSeq: 4\r\n"));
+        assert!(text.ends_with("Content-Length: 15\r\n\r\nbitrate: 2000\r\n"));
+
+        let parsed = RtspRequest::parse(&text).unwrap();
+        assert_eq!(parsed.cseq(), Some(4));
+        assert_eq!(parsed.session().unwrap().unwrap().id, "ABCD");
+    }
+
     #[test]
     fn parse_options_request() {
         let raw = "OPTIONS rtsp://localhost:8554/test RTSP/1.0\r\nCSeq: 1\r\n\r\n";
         let req = RtspRequest::parse(raw).unwrap();
-        assert_eq!(req.method, "OPTIONS");
+        assert_eq!(req.method, Method::Options);
         assert_eq!(req.uri, "rtsp://localhost:8554/test");
         assert_eq!(req.version, "RTSP/1.0");
-        assert_eq!(req.cseq(), Some("1"));
+        assert_eq!(req.cseq(), Some(1));
     }
 
     #[test]
@@ -88,8 +220,8 @@ mod tests {
                    CSeq: 3\r\n\
                    Transport: RTP/AVP;unicast;client_port=8000-8001\r\n\r\n";
         let req = RtspRequest::parse(raw).unwrap();
-        assert_eq!(req.method, "SETUP");
-        assert_eq!(req.cseq(), Some("3"));
+        assert_eq!(req.method, Method::Setup);
+        assert_eq!(req.cseq(), Some(3));
         assert_eq!(
             req.get_header("Transport"),
             Some("RTP/AVP;unicast;client_port=8000-8001")
@@ -106,6 +238,56 @@ mod tests {
         assert!(RtspRequest::parse("JUST_A_METHOD\r\n\r\n").is_err());
     }
 
+    #[test]
+    fn parse_rejects_other_versions() {
+        let raw = "OPTIONS rtsp://localhost RTSP/2.0\r\nCSeq: 1\r\n\r\n";
+        assert!(matches!(
+            RtspRequest::parse(raw),
+            Err(RtspError::UnsupportedVersion(v)) if v == "RTSP/2.0"
+        ));
+    }
+
+    #[test]
+    fn parse_requires_cseq() {
+        let raw = "OPTIONS rtsp://localhost RTSP/1.0\r\nUser-Agent: test\r\n\r\n";
+        assert!(matches!(
+            RtspRequest::parse(raw),
+            Err(RtspError::Parse {
+                kind: ParseErrorKind::MissingCSeq
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_non_numeric_cseq() {
+        let raw = "OPTIONS rtsp://localhost RTSP/1.0\r\nCSeq: abc\r\n\r\n";
+        assert!(matches!(
+            RtspRequest::parse(raw),
+            Err(RtspError::Parse {
+                kind: ParseErrorKind::InvalidCSeq
+            })
+        ));
+    }
+
+    #[test]
+    fn typed_headers() {
+        let raw = "PLAY rtsp://localhost/stream RTSP/1.0\r\n\
+                   CSeq: 4\r\n\
+                   Session: 00AB;timeout=60\r\n\
+                   Range: npt=now-\r\n\
+                   Require: play.basic\r\n\
+                   Content-Length: 0\r\n\r\n";
+        let req = RtspRequest::parse(raw).unwrap();
+        assert_eq!(req.method, Method::Play);
+        assert_eq!(req.session().unwrap().unwrap().id, "00AB");
+        assert_eq!(req.range().unwrap().unwrap().to_string(), "npt=now-");
+        assert_eq!(req.require(), ["play.basic"]);
+        assert!(req.proxy_require().is_empty());
+        assert_eq!(req.content_length().unwrap(), Some(0));
+        assert!(req.transport().unwrap().is_none());
+        assert!(req.authorization().unwrap().is_none());
+    }
+
     #[test]
     fn header_lookup_case_insensitive() {
         let raw = "OPTIONS rtsp://localhost RTSP/1.0\r\ncseq: 42\r\n\r\n";
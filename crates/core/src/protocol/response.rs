@@ -1,4 +1,12 @@
+use std::fmt::Display;
+
+use crate::error::{ParseErrorKind, RtspError};
+use crate::protocol::headers::{Range, RtpInfo, SessionHeader};
+use crate::protocol::method::Method;
+use crate::session::transport::TransportSpec;
+
 #[must_use]
+#[derive(Debug)]
 pub struct RtspResponse {
     pub status_code: u16,
     pub status_text: String,
@@ -7,7 +15,6 @@ pub struct RtspResponse {
 }
 
 impl RtspResponse {
-    #[must_use]
     pub fn new(status_code: u16, status_text: &str) -> Self {
         RtspResponse {
             status_code,
@@ -17,33 +24,145 @@ impl RtspResponse {
         }
     }
 
-    #[must_use]
+    /// A response with the standard reason phrase for `status_code`.
+    pub fn status(status_code: u16) -> Self {
+        Self::new(status_code, reason_phrase(status_code))
+    }
+
+    /// The error response for a request that failed with `error`.
+    pub fn from_error(error: &RtspError) -> Self {
+        Self::status(error.status_code())
+    }
+
     pub fn ok() -> Self {
         Self::new(200, "OK")
     }
 
-    #[must_use]
     pub fn not_found() -> Self {
         Self::new(404, "Not Found")
     }
 
-    #[must_use]
     pub fn bad_request() -> Self {
         Self::new(400, "Bad Request")
     }
 
-    #[must_use]
     pub fn add_header(mut self, name: &str, value: &str) -> Self {
         self.headers.push((name.to_string(), value.to_string()));
         self
     }
 
-    #[must_use]
+    pub fn cseq(self, cseq: u32) -> Self {
+        self.add_header("CSeq", &cseq.to_string())
+    }
+
+    pub fn session(self, session: &SessionHeader) -> Self {
+        self.add_header("Session", &session.to_string())
+    }
+
+    pub fn transport(self, transport: &impl Display) -> Self {
+        self.add_header("Transport", &transport.to_string())
+    }
+
+    pub fn range(self, range: &Range) -> Self {
+        self.add_header("Range", &range.to_string())
+    }
+
+    pub fn rtp_info(self, entries: &[RtpInfo]) -> Self {
+        self.add_header("RTP-Info", &RtpInfo::join(entries))
+    }
+
+    pub fn content_type(self, content_type: &str) -> Self {
+        self.add_header("Content-Type", content_type)
+    }
+
+    /// Methods the server supports (RFC 2326 §12.28).
+    pub fn public(self, methods: &[Method]) -> Self {
+        self.add_header("Public", &Method::join(methods))
+    }
+
+    /// Methods valid for the resource in its current state (RFC 2326 §12.4).
+    pub fn allow(self, methods: &[Method]) -> Self {
+        self.add_header("Allow", &Method::join(methods))
+    }
+
+    /// Option tags from `Require` or `Proxy-Require` the server doesn't
+    /// support (RFC 2326 §12.42).
+    pub fn unsupported(self, tags: &[String]) -> Self {
+        self.add_header("Unsupported", &tags.join(", "))
+    }
+
     pub fn with_body(mut self, body: String) -> Self {
         self.body = Some(body);
         self
     }
 
+    /// Parse a response's status line and headers, as received by a
+    /// client. The body is filled in by the reader.
+    pub fn parse(raw: &str) -> crate::error::Result<Self> {
+        let invalid = |kind| RtspError::Parse { kind };
+        let mut lines = raw.lines();
+        let status_line = lines.next().ok_or(invalid(ParseErrorKind::EmptyRequest))?;
+
+        let mut parts = status_line.splitn(3, ' ');
+        let version = parts.next().unwrap_or_default();
+        if version != "RTSP/1.0" {
+            return Err(RtspError::UnsupportedVersion(version.to_string()));
+        }
+        let status_code = parts
+            .next()
+            .and_then(|code| code.parse().ok())
+            .ok_or(invalid(ParseErrorKind::InvalidStatusLine))?;
+        let mut response = Self::new(status_code, parts.next().unwrap_or_default().trim());
+
+        for line in lines.take_while(|l| !l.is_empty()) {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(invalid(ParseErrorKind::InvalidHeader))?;
+            response = response.add_header(name.trim(), value.trim());
+        }
+        Ok(response)
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status_code)
+    }
+
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_cseq(&self) -> Option<u32> {
+        self.get_header("CSeq")?.parse().ok()
+    }
+
+    pub fn get_session(&self) -> crate::error::Result<Option<SessionHeader>> {
+        self.get_header("Session")
+            .map(SessionHeader::parse)
+            .transpose()
+    }
+
+    /// The transport the server chose in its SETUP response.
+    pub fn get_transport(&self) -> Option<TransportSpec> {
+        TransportSpec::parse(self.get_header("Transport")?)
+    }
+
+    pub fn get_rtp_info(&self) -> crate::error::Result<Vec<RtpInfo>> {
+        match self.get_header("RTP-Info") {
+            Some(value) => RtpInfo::parse_list(value),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Methods listed in `Public`.
+    pub fn get_public(&self) -> Vec<Method> {
+        self.get_header("Public")
+            .map(|value| value.split(',').map(|m| Method::from(m.trim())).collect())
+            .unwrap_or_default()
+    }
+
     pub fn serialize(&self) -> String {
         let mut response = format!("RTSP/1.0 {} {}\r\n", self.status_code, self.status_text);
 
@@ -62,6 +181,57 @@ impl RtspResponse {
     }
 }
 
+/// Reason phrase for an RTSP status code (RFC 2326 §7.1.1).
+pub fn reason_phrase(status_code: u16) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        200 => "OK",
+        201 => "Created",
+        250 => "Low on Storage Space",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Moved Temporarily",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Time-out",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Request Entity Too Large",
+        414 => "Request-URI Too Large",
+        415 => "Unsupported Media Type",
+        451 => "Parameter Not Understood",
+        452 => "Conference Not Found",
+        453 => "Not Enough Bandwidth",
+        454 => "Session Not Found",
+        455 => "Method Not Valid in This State",
+        456 => "Header Field Not Valid for Resource",
+        457 => "Invalid Range",
+        458 => "Parameter Is Read-Only",
+        459 => "Aggregate operation not allowed",
+        460 => "Only aggregate operation allowed",
+        461 => "Unsupported transport",
+        462 => "Destination unreachable",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Time-out",
+        505 => "RTSP Version not supported",
+        551 => "Option not supported",
+        _ => "Unknown",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,6 +258,28 @@ mod tests {
         assert!(s.ends_with("v=0\r\n"));
     }
 
+    #[test]
+    fn serialized_response_parses() {
+        let raw = RtspResponse::status(454)
+            .cseq(3)
+            .session(&SessionHeader {
+                id: "F00D".into(),
+                timeout: Some(60),
+            })
+            .public(&[Method::Options, Method::GetParameter])
+            .serialize();
+        let parsed = RtspResponse::parse(&raw).unwrap();
+        assert_eq!(parsed.status_code, 454);
+        assert_eq!(parsed.status_text, "Session Not Found");
+        assert!(!parsed.is_success());
+        assert_eq!(parsed.get_cseq(), Some(3));
+        assert_eq!(parsed.get_session().unwrap().unwrap().timeout, Some(60));
+        assert_eq!(parsed.get_public(), [Method::Options, Method::GetParameter]);
+
+        assert!(RtspResponse::parse("RTSP/1.0 OK\r\n\r\n").is_err());
+        assert!(RtspResponse::parse("HTTP/1.1 200 OK\r\n\r\n").is_err());
+    }
+
     #[test]
     fn not_found_response() {
         let resp = RtspResponse::not_found().add_header("CSeq", "5");
@@ -95,4 +287,28 @@ mod tests {
         let s = resp.serialize();
         assert!(s.starts_with("RTSP/1.0 404 Not Found\r\n"));
     }
+
+    #[test]
+    fn typed_header_builders() {
+        let resp = RtspResponse::ok()
+            .cseq(7)
+            .session(&SessionHeader {
+                id: "00AB".into(),
+                timeout: Some(60),
+            })
+            .allow(&[Method::Options, Method::Teardown]);
+        let s = resp.serialize();
+        assert!(s.contains("CSeq: 7\r\n"));
+        assert!(s.contains("Session: 00AB;timeout=60\r\n"));
+        assert!(s.contains("Allow: OPTIONS, TEARDOWN\r\n"));
+    }
+
+    #[test]
+    fn error_response_uses_mapped_status() {
+        let resp = RtspResponse::from_error(&RtspError::SessionNotFound("1".into()));
+        assert!(
+            resp.serialize()
+                .starts_with("RTSP/1.0 454 Session Not Found\r\n")
+        );
+    }
 }
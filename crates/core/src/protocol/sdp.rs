@@ -1,21 +1,30 @@
+use crate::error::{ParseErrorKind, Result, RtspError};
 use crate::media::Packetizer;
+use crate::rtcp::Retransmission;
 
 /// Generate an SDP session description for the given packetizer.
 ///
 /// Produces SDP per RFC 4566 with media-level attributes from the codec's
-/// [`Packetizer::sdp_attributes`] implementation.
-pub fn generate_sdp(packetizer: &dyn Packetizer) -> String {
+/// [`Packetizer::sdp_attributes`] implementation. When retransmission is
+/// enabled, NACK feedback (RFC 4585) and any RTX payload type (RFC 4588)
+/// are advertised.
+pub fn generate_sdp(packetizer: &dyn Packetizer, retransmission: Retransmission) -> String {
     let pt = packetizer.payload_type();
     let clock = packetizer.clock_rate();
     let codec = packetizer.codec_name();
 
+    let formats = match retransmission {
+        Retransmission::Rtx { payload_type } => format!("{pt} {payload_type}"),
+        _ => pt.to_string(),
+    };
+
     let mut sdp = format!(
         "v=0\r\n\
          o=- 0 0 IN IP4 127.0.0.1\r\n\
          s=RTSP Server\r\n\
          c=IN IP4 0.0.0.0\r\n\
          t=0 0\r\n\
-         m=video 0 RTP/AVP {pt}\r\n\
+         m=video 0 RTP/AVP {formats}\r\n\
          a=rtpmap:{pt} {codec}/{clock}\r\n"
     );
 
@@ -23,10 +32,221 @@ pub fn generate_sdp(packetizer: &dyn Packetizer) -> String {
         sdp.push_str(&format!("a={attr}\r\n"));
     }
 
+    sdp.push_str(&format!("a=rtcp-fb:{pt} nack pli\r\n"));
+    sdp.push_str(&format!("a=rtcp-fb:{pt} ccm fir\r\n"));
+
+    match retransmission {
+        Retransmission::Disabled => {}
+        Retransmission::Direct => {
+            sdp.push_str(&format!("a=rtcp-fb:{pt} nack\r\n"));
+        }
+        Retransmission::Rtx { payload_type } => {
+            sdp.push_str(&format!("a=rtcp-fb:{pt} nack\r\n"));
+            sdp.push_str(&format!("a=rtpmap:{payload_type} rtx/{clock}\r\n"));
+            sdp.push_str(&format!("a=fmtp:{payload_type} apt={pt}\r\n"));
+        }
+    }
+
     sdp.push_str("a=control:track1\r\n");
     sdp
 }
 
+/// Complete an SDP fragment supplied with pre-packetized media into a
+/// session description.
+///
+/// A fragment holding only `m=` sections and their attributes gets the
+/// session-level lines of [`generate_sdp`]; a full description is kept as
+/// is. Media sections without `a=control` are given `track1`, `track2`, ...
+pub fn complete_fragment(fragment: &str) -> String {
+    let lines: Vec<&str> = fragment
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    let mut sdp = String::new();
+    if !lines.first().is_some_and(|l| l.starts_with("v=")) {
+        sdp.push_str(
+            "v=0\r\n\
+             o=- 0 0 IN IP4 127.0.0.1\r\n\
+             s=RTSP Server\r\n\
+             c=IN IP4 0.0.0.0\r\n\
+             t=0 0\r\n",
+        );
+    }
+
+    let mut track = 0;
+    let mut needs_control = false;
+    for line in lines {
+        if line.starts_with("m=") {
+            if needs_control {
+                sdp.push_str(&format!("a=control:track{track}\r\n"));
+            }
+            track += 1;
+            needs_control = true;
+        } else if line.starts_with("a=control:") {
+            needs_control = false;
+        }
+        sdp.push_str(line);
+        sdp.push_str("\r\n");
+    }
+    if needs_control {
+        sdp.push_str(&format!("a=control:track{track}\r\n"));
+    }
+    sdp
+}
+
+/// An SDP session description (RFC 4566), parsed as far as the server
+/// needs to relay the streams it describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionDescription {
+    /// Session-level `a=` attributes as (name, value) pairs.
+    pub attributes: Vec<(String, String)>,
+    pub media: Vec<MediaDescription>,
+}
+
+/// One `m=` section of a session description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaDescription {
+    /// Media type, e.g. `video` or `audio`.
+    pub media: String,
+    pub port: u16,
+    /// Transport protocol, e.g. `RTP/AVP`.
+    pub protocol: String,
+    /// Payload formats; RTP payload type numbers for RTP media.
+    pub formats: Vec<String>,
+    /// `a=` attributes as (name, value) pairs; flags have an empty value.
+    pub attributes: Vec<(String, String)>,
+}
+
+/// An `a=rtpmap` attribute: encoding of one RTP payload type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpMap {
+    pub payload_type: u8,
+    pub encoding: String,
+    pub clock_rate: u32,
+    pub channels: Option<u8>,
+}
+
+impl SessionDescription {
+    /// Parse a session description. Lines other than `m=` and `a=` are
+    /// skipped; a description without media sections is rejected.
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = || RtspError::Parse {
+            kind: ParseErrorKind::InvalidSdp,
+        };
+        let mut description = Self {
+            attributes: Vec::new(),
+            media: Vec::new(),
+        };
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (kind, value) = line.split_once('=').ok_or_else(invalid)?;
+            match kind {
+                "m" => description
+                    .media
+                    .push(MediaDescription::parse(value).ok_or_else(invalid)?),
+                "a" => {
+                    let attribute = match value.split_once(':') {
+                        Some((name, value)) => (name.to_string(), value.to_string()),
+                        None => (value.to_string(), String::new()),
+                    };
+                    match description.media.last_mut() {
+                        Some(media) => media.attributes.push(attribute),
+                        None => description.attributes.push(attribute),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if description.media.is_empty() {
+            return Err(invalid());
+        }
+        Ok(description)
+    }
+}
+
+impl MediaDescription {
+    fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.split_whitespace();
+        let media = fields.next()?.to_string();
+        // `<port>/<count>` is allowed for layered streams.
+        let port = fields.next()?.split('/').next()?.parse().ok()?;
+        let protocol = fields.next()?.to_string();
+        Some(Self {
+            media,
+            port,
+            protocol,
+            formats: fields.map(str::to_string).collect(),
+            attributes: Vec::new(),
+        })
+    }
+
+    /// Value of the first attribute called `name`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `a=control` URL of this stream, relative or absolute.
+    pub fn control(&self) -> Option<&str> {
+        self.attribute("control")
+    }
+
+    /// The first payload type, which the sender prefers.
+    pub fn payload_type(&self) -> Option<u8> {
+        self.formats.first()?.parse().ok()
+    }
+
+    /// The `a=rtpmap` entry for `payload_type`.
+    pub fn rtpmap(&self, payload_type: u8) -> Option<RtpMap> {
+        self.attributes
+            .iter()
+            .filter(|(name, _)| name == "rtpmap")
+            .filter_map(|(_, value)| RtpMap::parse(value))
+            .find(|map| map.payload_type == payload_type)
+    }
+
+    /// Clock rate of `payload_type`, from its rtpmap or the static
+    /// assignments of RFC 3551 §6.
+    pub fn clock_rate(&self, payload_type: u8) -> Option<u32> {
+        match self.rtpmap(payload_type) {
+            Some(map) => Some(map.clock_rate),
+            None => static_clock_rate(payload_type),
+        }
+    }
+}
+
+impl RtpMap {
+    /// Parse the value of an `a=rtpmap` attribute, e.g. `96 H264/90000`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (payload_type, encoding) = value.trim().split_once(' ')?;
+        let mut parts = encoding.trim().split('/');
+        Some(Self {
+            payload_type: payload_type.parse().ok()?,
+            encoding: parts.next()?.to_string(),
+            clock_rate: parts.next()?.parse().ok()?,
+            channels: parts.next().and_then(|c| c.parse().ok()),
+        })
+    }
+}
+
+/// Clock rates of the static RTP payload types (RFC 3551 §6).
+fn static_clock_rate(payload_type: u8) -> Option<u32> {
+    match payload_type {
+        0 | 3..=5 | 7..=9 | 12 | 13 | 15 | 18 => Some(8000),
+        6 => Some(16000),
+        10 | 11 => Some(44100),
+        14 | 25 | 26 | 28 | 31..=34 => Some(90000),
+        16 => Some(11025),
+        17 => Some(22050),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,10 +255,82 @@ mod tests {
     #[test]
     fn generates_h264_sdp() {
         let p = H264Packetizer::new(96, 0x12345678);
-        let sdp = generate_sdp(&p);
+        let sdp = generate_sdp(&p, Retransmission::Disabled);
         assert!(sdp.contains("v=0\r\n"));
         assert!(sdp.contains("a=rtpmap:96 H264/90000\r\n"));
         assert!(sdp.contains("a=fmtp:96 packetization-mode=1\r\n"));
         assert!(sdp.contains("a=control:track1\r\n"));
+        assert!(!sdp.contains("a=rtcp-fb:96 nack\r\n"));
+    }
+
+    #[test]
+    fn advertises_keyframe_feedback() {
+        let p = H264Packetizer::new(96, 0x12345678);
+        let sdp = generate_sdp(&p, Retransmission::Disabled);
+        assert!(sdp.contains("a=rtcp-fb:96 nack pli\r\n"));
+        assert!(sdp.contains("a=rtcp-fb:96 ccm fir\r\n"));
+    }
+
+    #[test]
+    fn advertises_nack_feedback() {
+        let p = H264Packetizer::new(96, 0x12345678);
+        let sdp = generate_sdp(&p, Retransmission::Direct);
+        assert!(sdp.contains("m=video 0 RTP/AVP 96\r\n"));
+        assert!(sdp.contains("a=rtcp-fb:96 nack\r\n"));
+    }
+
+    #[test]
+    fn parses_announced_description() {
+        let text = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=No Name\r\nt=0 0\r\na=tool:libavformat\r\n\
+                    m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n\
+                    a=fmtp:96 packetization-mode=1\r\na=control:streamid=0\r\n\
+                    m=audio 0 RTP/AVP 0\r\na=control:streamid=1\r\n";
+        let sdp = SessionDescription::parse(text).unwrap();
+        assert_eq!(
+            sdp.attributes,
+            [("tool".to_string(), "libavformat".to_string())]
+        );
+        assert_eq!(sdp.media.len(), 2);
+
+        let video = &sdp.media[0];
+        assert_eq!(video.media, "video");
+        assert_eq!(video.control(), Some("streamid=0"));
+        assert_eq!(video.payload_type(), Some(96));
+        assert_eq!(video.rtpmap(96).unwrap().encoding, "H264");
+        assert_eq!(video.clock_rate(96), Some(90000));
+        assert_eq!(sdp.media[1].clock_rate(0), Some(8000));
+
+        assert!(SessionDescription::parse("v=0\r\ns=empty\r\n").is_err());
+    }
+
+    #[test]
+    fn generated_sdp_parses() {
+        let p = H264Packetizer::new(96, 0x12345678);
+        let sdp = SessionDescription::parse(&generate_sdp(&p, Retransmission::Disabled)).unwrap();
+        assert_eq!(sdp.media[0].control(), Some("track1"));
+    }
+
+    #[test]
+    fn completes_media_fragment() {
+        let sdp = complete_fragment(
+            "m=video 0 RTP/AVP 96\na=rtpmap:96 H264/90000\n\
+             m=audio 0 RTP/AVP 0\na=control:audio\n",
+        );
+        assert!(sdp.starts_with("v=0\r\n"));
+        let description = SessionDescription::parse(&sdp).unwrap();
+        assert_eq!(description.media[0].control(), Some("track1"));
+        assert_eq!(description.media[1].control(), Some("audio"));
+
+        let full = "v=0\r\ns=Camera\r\nm=video 0 RTP/AVP 96\r\na=control:video\r\n";
+        assert_eq!(complete_fragment(full), full);
+    }
+
+    #[test]
+    fn advertises_rtx_payload_type() {
+        let p = H264Packetizer::new(96, 0x12345678);
+        let sdp = generate_sdp(&p, Retransmission::Rtx { payload_type: 97 });
+        assert!(sdp.contains("m=video 0 RTP/AVP 96 97\r\n"));
+        assert!(sdp.contains("a=rtpmap:97 rtx/90000\r\n"));
+        assert!(sdp.contains("a=fmtp:97 apt=96\r\n"));
     }
 }
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::mount;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a signed URL was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    /// `exp` or `token` missing or unreadable.
+    Malformed,
+    Expired,
+    /// Signed for another client address.
+    WrongAddress,
+    /// Not signed with the server's secret, or for another path.
+    BadSignature,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed token"),
+            Self::Expired => write!(f, "token expired"),
+            Self::WrongAddress => write!(f, "token issued for another address"),
+            Self::BadSignature => write!(f, "invalid token signature"),
+        }
+    }
+}
+
+/// Signs and checks stream URLs such as
+/// `rtsp://host/cam1?exp=1767225600&token=...`, so a web portal can hand
+/// out short-lived links that play without credentials.
+///
+/// The token is an HMAC-SHA256 over the mount path, the expiry in Unix
+/// seconds and, for URLs bound to one client, its address in `ip`.
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Vec<u8>,
+}
+
+impl UrlSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+        }
+    }
+
+    /// `url` with a token valid for `valid_for` appended to its query,
+    /// usable only from `ip` if given.
+    pub fn sign_url(&self, url: &str, valid_for: Duration, ip: Option<IpAddr>) -> String {
+        let expires = unix_time(SystemTime::now() + valid_for);
+        let token = self.token(&mount::path_of(url), expires, ip);
+        let separator = if url.contains('?') { '&' } else { '?' };
+        match ip {
+            Some(ip) => format!("{url}{separator}exp={expires}&ip={ip}&token={token}"),
+            None => format!("{url}{separator}exp={expires}&token={token}"),
+        }
+    }
+
+    /// The token for `path` expiring at `expires`, in Unix seconds.
+    pub fn token(&self, path: &str, expires: u64, ip: Option<IpAddr>) -> String {
+        URL_SAFE_NO_PAD.encode(self.mac(path, expires, ip).finalize().into_bytes())
+    }
+
+    /// Check the token in `query` for the mount at `path`, requested from
+    /// `peer`, returning when it expires in Unix seconds.
+    pub fn verify(&self, path: &str, query: &str, peer: IpAddr) -> Result<u64, TokenError> {
+        let mut expires = None;
+        let mut ip = None;
+        let mut token = None;
+        for (name, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+            match name {
+                "exp" => expires = Some(value.parse::<u64>().map_err(|_| TokenError::Malformed)?),
+                "ip" => ip = Some(value.parse::<IpAddr>().map_err(|_| TokenError::Malformed)?),
+                "token" => {
+                    token = Some(
+                        URL_SAFE_NO_PAD
+                            .decode(value)
+                            .map_err(|_| TokenError::Malformed)?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        let (Some(expires), Some(token)) = (expires, token) else {
+            return Err(TokenError::Malformed);
+        };
+
+        self.mac(path, expires, ip)
+            .verify_slice(&token)
+            .map_err(|_| TokenError::BadSignature)?;
+        if unix_time(SystemTime::now()) > expires {
+            return Err(TokenError::Expired);
+        }
+        if ip.is_some_and(|ip| ip.to_canonical() != peer.to_canonical()) {
+            return Err(TokenError::WrongAddress);
+        }
+        Ok(expires)
+    }
+
+    fn mac(&self, path: &str, expires: u64, ip: Option<IpAddr>) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        let ip = ip
+            .map(|ip| ip.to_canonical().to_string())
+            .unwrap_or_default();
+        mac.update(format!("{}\n{expires}\n{ip}", path.trim_matches('/')).as_bytes());
+        mac
+    }
+}
+
+impl fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlSigner").finish_non_exhaustive()
+    }
+}
+
+/// Whether `query` carries a token at all.
+pub fn has_token(query: &str) -> bool {
+    query.split('&').any(|p| p.starts_with("token="))
+}
+
+pub(crate) fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_urls_verify_for_their_path_and_address() {
+        let signer = UrlSigner::new("portal secret");
+        let client: IpAddr = "192.168.1.20".parse().unwrap();
+        let url = signer.sign_url(
+            "rtsp://cams.example/store/till1",
+            Duration::from_secs(60),
+            Some(client),
+        );
+        assert!(url.starts_with("rtsp://cams.example/store/till1?exp="));
+
+        let query = mount::query_of(&url);
+        assert!(has_token(query));
+        let expires = signer.verify("store/till1", query, client).unwrap();
+        assert!(query.starts_with(&format!("exp={expires}&")));
+        assert_eq!(
+            signer.verify("store/till1", query, "::ffff:192.168.1.20".parse().unwrap()),
+            Ok(expires)
+        );
+        assert_eq!(
+            signer.verify("store/till1", query, "192.168.1.21".parse().unwrap()),
+            Err(TokenError::WrongAddress)
+        );
+        assert_eq!(
+            signer.verify("store/till2", query, client),
+            Err(TokenError::BadSignature)
+        );
+        assert_eq!(
+            UrlSigner::new("other").verify("store/till1", query, client),
+            Err(TokenError::BadSignature)
+        );
+
+        let tampered = query.replace("ip=192.168.1.20", "ip=10.0.0.1");
+        assert_eq!(
+            signer.verify("store/till1", &tampered, "10.0.0.1".parse().unwrap()),
+            Err(TokenError::BadSignature)
+        );
+        assert_eq!(
+            signer.verify("store/till1", "exp=1", client),
+            Err(TokenError::Malformed)
+        );
+    }
+
+    #[test]
+    fn expired_tokens_are_refused() {
+        let signer = UrlSigner::new(b"secret");
+        let client: IpAddr = "127.0.0.1".parse().unwrap();
+        let token = signer.token("cam1", 1_000_000, None);
+        let query = format!("exp=1000000&token={token}");
+        assert_eq!(
+            signer.verify("cam1", &query, client),
+            Err(TokenError::Expired)
+        );
+
+        let url = signer.sign_url("rtsp://host/cam1?foo=bar", Duration::from_secs(30), None);
+        assert!(url.contains("?foo=bar&exp="));
+        assert!(signer.verify("cam1", mount::query_of(&url), client).is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::RwLock;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const NTP_UNIX_OFFSET_SECS: u64 = 2_208_988_800;
+
+/// 64-bit NTP timestamp: 32.32 fixed-point seconds since 1900 (RFC 3550 §4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NtpTimestamp(pub u64);
+
+impl NtpTimestamp {
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_unix.as_secs() + NTP_UNIX_OFFSET_SECS;
+        let frac = ((since_unix.subsec_nanos() as u64) << 32) / 1_000_000_000;
+        Self((secs << 32) | frac)
+    }
+
+    /// The middle 32 bits, as used by the LSR/DLSR fields of report blocks.
+    pub fn middle_32(&self) -> u32 {
+        (self.0 >> 16) as u32
+    }
+}
+
+/// Linear mapping from a stream's RTP timestamps to wall-clock time.
+///
+/// Anchored at one (RTP timestamp, capture time) pair and extrapolated
+/// with the codec clock rate.
+#[derive(Debug, Clone, Copy)]
+pub struct MediaClock {
+    pub clock_rate: u32,
+    pub rtp_timestamp: u32,
+    pub wall_clock: SystemTime,
+}
+
+impl MediaClock {
+    /// RTP timestamp corresponding to the given wall-clock instant.
+    pub fn rtp_timestamp_at(&self, time: SystemTime) -> u32 {
+        let ticks = |d: Duration| (d.as_secs_f64() * self.clock_rate as f64).round() as u64 as u32;
+        match time.duration_since(self.wall_clock) {
+            Ok(ahead) => self.rtp_timestamp.wrapping_add(ticks(ahead)),
+            Err(e) => self.rtp_timestamp.wrapping_sub(ticks(e.duration())),
+        }
+    }
+}
+
+/// Per-SSRC [`MediaClock`]s shared by every session carrying the stream.
+///
+/// The first packet seen for an SSRC anchors its clock at the send time.
+/// Applications that know the real capture time of a frame should call
+/// [`StreamClocks::set_capture_time`], which re-anchors the mapping.
+#[derive(Debug, Default)]
+pub struct StreamClocks {
+    clocks: RwLock<HashMap<u32, MediaClock>>,
+}
+
+impl StreamClocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a packet is being sent now, anchoring the clock if this
+    /// SSRC has none yet.
+    pub fn observe(&self, ssrc: u32, rtp_timestamp: u32, clock_rate: u32) {
+        if self.clocks.read().contains_key(&ssrc) {
+            return;
+        }
+        self.clocks.write().entry(ssrc).or_insert(MediaClock {
+            clock_rate,
+            rtp_timestamp,
+            wall_clock: SystemTime::now(),
+        });
+    }
+
+    /// Anchor the SSRC's clock at the capture time of the frame with the given RTP timestamp.
+    pub fn set_capture_time(
+        &self,
+        ssrc: u32,
+        rtp_timestamp: u32,
+        clock_rate: u32,
+        capture: SystemTime,
+    ) {
+        self.clocks.write().insert(
+            ssrc,
+            MediaClock {
+                clock_rate,
+                rtp_timestamp,
+                wall_clock: capture,
+            },
+        );
+    }
+
+    pub fn get(&self, ssrc: u32) -> Option<MediaClock> {
+        self.clocks.read().get(&ssrc).copied()
+    }
+
+    pub fn remove(&self, ssrc: u32) {
+        self.clocks.write().remove(&ssrc);
+    }
+
+    pub fn clear(&self) {
+        self.clocks.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntp_epoch_offset() {
+        let ntp = NtpTimestamp::from_system_time(UNIX_EPOCH);
+        assert_eq!(ntp.0 >> 32, NTP_UNIX_OFFSET_SECS);
+        assert_eq!(ntp.0 & 0xFFFF_FFFF, 0);
+    }
+
+    #[test]
+    fn ntp_fraction() {
+        let ntp = NtpTimestamp::from_system_time(UNIX_EPOCH + Duration::from_millis(500));
+        assert_eq!(ntp.0 & 0xFFFF_FFFF, 0x8000_0000);
+        assert_eq!(
+            ntp.middle_32(),
+            ((NTP_UNIX_OFFSET_SECS as u32) << 16) | 0x8000
+        );
+    }
+
+    #[test]
+    fn media_clock_extrapolates_both_directions() {
+        let base = UNIX_EPOCH + Duration::from_secs(1000);
+        let clock = MediaClock {
+            clock_rate: 90000,
+            rtp_timestamp: 100,
+            wall_clock: base,
+        };
+        assert_eq!(clock.rtp_timestamp_at(base + Duration::from_secs(1)), 90100);
+        assert_eq!(
+            clock.rtp_timestamp_at(base - Duration::from_secs(1)),
+            100u32.wrapping_sub(90000)
+        );
+    }
+
+    #[test]
+    fn capture_time_overrides_observed_anchor() {
+        let clocks = StreamClocks::new();
+        clocks.observe(1, 0, 90000);
+        let capture = UNIX_EPOCH + Duration::from_secs(42);
+        clocks.set_capture_time(1, 3000, 90000, capture);
+        clocks.observe(1, 6000, 90000);
+
+        let clock = clocks.get(1).unwrap();
+        assert_eq!(clock.rtp_timestamp, 3000);
+        assert_eq!(clock.wall_clock, capture);
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Minimum report interval recommended by RFC 3550 §6.2.
+pub const RTCP_MIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Fraction of the session bandwidth allotted to RTCP (RFC 3550 §6.2).
+pub const RTCP_BANDWIDTH_FRACTION: f64 = 0.05;
+
+/// Inputs to the RTCP transmission interval computation (RFC 3550 §6.3.1).
+#[derive(Debug, Clone, Copy)]
+pub struct IntervalParams {
+    pub members: usize,
+    pub senders: usize,
+    /// RTCP bandwidth in octets per second.
+    pub rtcp_bandwidth: f64,
+    pub we_sent: bool,
+    /// Running average compound packet size in octets, including UDP/IP overhead.
+    pub avg_rtcp_size: f64,
+    pub initial: bool,
+    pub min_interval: Duration,
+}
+
+impl IntervalParams {
+    /// Deterministic interval `Td` before randomization.
+    pub fn deterministic(&self) -> Duration {
+        let mut min = self.min_interval.as_secs_f64();
+        if self.initial {
+            min /= 2.0;
+        }
+
+        let mut n = self.members.max(1) as f64;
+        let mut bandwidth = self.rtcp_bandwidth;
+        let senders = self.senders as f64;
+
+        // Senders get a quarter of the RTCP bandwidth when they are few.
+        if senders > 0.0 && senders <= n * 0.25 {
+            if self.we_sent {
+                bandwidth *= 0.25;
+                n = senders;
+            } else {
+                bandwidth *= 0.75;
+                n -= senders;
+            }
+        }
+
+        let t = if bandwidth > 0.0 {
+            self.avg_rtcp_size * n / bandwidth
+        } else {
+            0.0
+        };
+        Duration::from_secs_f64(t.max(min))
+    }
+
+    /// Randomized interval: `Td` scaled by U[0.5, 1.5] and divided by
+    /// e − 3/2 to compensate for timer reconsideration.
+    pub fn randomized(&self) -> Duration {
+        const COMPENSATION: f64 = std::f64::consts::E - 1.5;
+        let factor = rand::rng().random_range(0.5..1.5);
+        self.deterministic().mul_f64(factor / COMPENSATION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> IntervalParams {
+        IntervalParams {
+            members: 2,
+            senders: 1,
+            rtcp_bandwidth: 5000.0,
+            we_sent: true,
+            avg_rtcp_size: 100.0,
+            initial: false,
+            min_interval: RTCP_MIN_INTERVAL,
+        }
+    }
+
+    #[test]
+    fn clamps_to_minimum() {
+        assert_eq!(params().deterministic(), RTCP_MIN_INTERVAL);
+    }
+
+    #[test]
+    fn initial_halves_minimum() {
+        let p = IntervalParams {
+            initial: true,
+            ..params()
+        };
+        assert_eq!(p.deterministic(), RTCP_MIN_INTERVAL / 2);
+    }
+
+    #[test]
+    fn low_bandwidth_stretches_interval() {
+        let p = IntervalParams {
+            rtcp_bandwidth: 10.0,
+            ..params()
+        };
+        // 100 octets * 2 members / 10 octets/s
+        assert_eq!(p.deterministic(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn randomized_within_bounds() {
+        let p = params();
+        let td = p.deterministic().as_secs_f64();
+        for _ in 0..100 {
+            let t = p.randomized().as_secs_f64();
+            assert!(t >= td * 0.5 / 1.21828 - 1e-6);
+            assert!(t <= td * 1.5 / 1.21828 + 1e-6);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! RTCP (RFC 3550 §6): wire format, timing and per-session report state.
+//!
+//! The server acts as an RTP sender towards each viewer. Every RTSP session
+//! owns an [`RtcpSession`] that counts what was sent and periodically emits
+//! Sender Reports tying RTP timestamps to wall-clock time via [`StreamClocks`].
+
+pub mod clock;
+pub mod interval;
+pub mod packet;
+pub mod retransmit;
+pub mod session;
+
+pub use clock::{MediaClock, NtpTimestamp, StreamClocks};
+pub use retransmit::{PacketHistory, Retransmission};
+pub use session::RtcpSession;
@@ -0,0 +1,606 @@
+use super::clock::NtpTimestamp;
+
+pub const PT_SR: u8 = 200;
+pub const PT_RR: u8 = 201;
+pub const PT_SDES: u8 = 202;
+pub const PT_BYE: u8 = 203;
+pub const PT_RTPFB: u8 = 205;
+pub const PT_PSFB: u8 = 206;
+pub const PT_XR: u8 = 207;
+
+/// FMT of a Generic NACK within RTPFB (RFC 4585 §6.2.1).
+const FMT_NACK: u8 = 1;
+/// FMT of a Picture Loss Indication within PSFB (RFC 4585 §6.3.1).
+const FMT_PLI: u8 = 1;
+/// FMT of a Full Intra Request within PSFB (RFC 5104 §4.3.1).
+const FMT_FIR: u8 = 4;
+
+const SDES_CNAME: u8 = 1;
+const XR_RRTR: u8 = 4;
+const XR_DLRR: u8 = 5;
+
+/// Reception report block carried in SR and RR packets (RFC 3550 §6.4.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportBlock {
+    pub ssrc: u32,
+    pub fraction_lost: u8,
+    /// Signed 24-bit cumulative number of packets lost.
+    pub cumulative_lost: i32,
+    pub highest_sequence: u32,
+    pub jitter: u32,
+    pub last_sr: u32,
+    pub delay_since_last_sr: u32,
+}
+
+/// Sender Report (RFC 3550 §6.4.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenderReport {
+    pub ssrc: u32,
+    pub ntp_timestamp: NtpTimestamp,
+    pub rtp_timestamp: u32,
+    pub packet_count: u32,
+    pub octet_count: u32,
+    pub reports: Vec<ReportBlock>,
+}
+
+/// Receiver Report (RFC 3550 §6.4.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiverReport {
+    pub ssrc: u32,
+    pub reports: Vec<ReportBlock>,
+}
+
+/// One sub-block of a DLRR report block (RFC 3611 §4.5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DlrrItem {
+    pub ssrc: u32,
+    pub last_rr: u32,
+    pub delay_since_last_rr: u32,
+}
+
+/// Extended Report block (RFC 3611 §4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XrBlock {
+    /// Receiver Reference Time, lets a pure receiver obtain an RTT.
+    ReceiverReferenceTime(NtpTimestamp),
+    /// Answer to a Receiver Reference Time block.
+    Dlrr(Vec<DlrrItem>),
+    /// Any block type this crate does not interpret.
+    Other { block_type: u8 },
+}
+
+/// Extended Report (RFC 3611).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedReport {
+    pub ssrc: u32,
+    pub blocks: Vec<XrBlock>,
+}
+
+/// A single RTCP packet within a compound packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpPacket {
+    SenderReport(SenderReport),
+    ReceiverReport(ReceiverReport),
+    /// SDES with one CNAME item per chunk (RFC 3550 §6.5).
+    SourceDescription(Vec<(u32, String)>),
+    ExtendedReport(ExtendedReport),
+    /// BYE for the listed sources with an optional reason (RFC 3550 §6.6).
+    Goodbye {
+        sources: Vec<u32>,
+        reason: Option<String>,
+    },
+    /// Generic NACK (RFC 4585 §6.2.1), expanded to the lost sequence numbers.
+    Nack {
+        sender_ssrc: u32,
+        media_ssrc: u32,
+        lost: Vec<u16>,
+    },
+    /// Picture Loss Indication (RFC 4585 §6.3.1).
+    Pli {
+        sender_ssrc: u32,
+        media_ssrc: u32,
+    },
+    /// Full Intra Request (RFC 5104 §4.3.1): (target SSRC, command sequence number) pairs.
+    Fir {
+        sender_ssrc: u32,
+        requests: Vec<(u32, u8)>,
+    },
+    /// A well-formed packet of a type this crate does not interpret.
+    Unknown {
+        pt: u8,
+    },
+}
+
+impl RtcpPacket {
+    /// Append the wire form of this packet to `buf`.
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        match self {
+            Self::SenderReport(sr) => {
+                write_header(buf, sr.reports.len() as u8, PT_SR);
+                buf.extend_from_slice(&sr.ssrc.to_be_bytes());
+                buf.extend_from_slice(&sr.ntp_timestamp.0.to_be_bytes());
+                buf.extend_from_slice(&sr.rtp_timestamp.to_be_bytes());
+                buf.extend_from_slice(&sr.packet_count.to_be_bytes());
+                buf.extend_from_slice(&sr.octet_count.to_be_bytes());
+                for block in &sr.reports {
+                    write_report_block(buf, block);
+                }
+            }
+            Self::ReceiverReport(rr) => {
+                write_header(buf, rr.reports.len() as u8, PT_RR);
+                buf.extend_from_slice(&rr.ssrc.to_be_bytes());
+                for block in &rr.reports {
+                    write_report_block(buf, block);
+                }
+            }
+            Self::SourceDescription(chunks) => {
+                write_header(buf, chunks.len() as u8, PT_SDES);
+                for (ssrc, cname) in chunks {
+                    let cname = &cname.as_bytes()[..cname.len().min(255)];
+                    buf.extend_from_slice(&ssrc.to_be_bytes());
+                    buf.push(SDES_CNAME);
+                    buf.push(cname.len() as u8);
+                    buf.extend_from_slice(cname);
+                    // Item list ends with a null octet, then pad to a 32-bit boundary.
+                    buf.push(0);
+                    while (buf.len() - start) & 3 != 0 {
+                        buf.push(0);
+                    }
+                }
+            }
+            Self::ExtendedReport(xr) => {
+                write_header(buf, 0, PT_XR);
+                buf.extend_from_slice(&xr.ssrc.to_be_bytes());
+                for block in &xr.blocks {
+                    write_xr_block(buf, block);
+                }
+            }
+            Self::Goodbye { sources, reason } => {
+                write_header(buf, sources.len() as u8, PT_BYE);
+                for ssrc in sources {
+                    buf.extend_from_slice(&ssrc.to_be_bytes());
+                }
+                if let Some(reason) = reason {
+                    let reason = &reason.as_bytes()[..reason.len().min(255)];
+                    buf.push(reason.len() as u8);
+                    buf.extend_from_slice(reason);
+                    while (buf.len() - start) & 3 != 0 {
+                        buf.push(0);
+                    }
+                }
+            }
+            Self::Nack {
+                sender_ssrc,
+                media_ssrc,
+                lost,
+            } => {
+                write_header(buf, FMT_NACK, PT_RTPFB);
+                buf.extend_from_slice(&sender_ssrc.to_be_bytes());
+                buf.extend_from_slice(&media_ssrc.to_be_bytes());
+                for (pid, blp) in nack_fci(lost) {
+                    buf.extend_from_slice(&pid.to_be_bytes());
+                    buf.extend_from_slice(&blp.to_be_bytes());
+                }
+            }
+            Self::Pli {
+                sender_ssrc,
+                media_ssrc,
+            } => {
+                write_header(buf, FMT_PLI, PT_PSFB);
+                buf.extend_from_slice(&sender_ssrc.to_be_bytes());
+                buf.extend_from_slice(&media_ssrc.to_be_bytes());
+            }
+            Self::Fir {
+                sender_ssrc,
+                requests,
+            } => {
+                write_header(buf, FMT_FIR, PT_PSFB);
+                buf.extend_from_slice(&sender_ssrc.to_be_bytes());
+                buf.extend_from_slice(&0u32.to_be_bytes());
+                for (ssrc, seq) in requests {
+                    buf.extend_from_slice(&ssrc.to_be_bytes());
+                    buf.extend_from_slice(&[*seq, 0, 0, 0]);
+                }
+            }
+            Self::Unknown { pt } => write_header(buf, 0, *pt),
+        }
+        finish_length(buf, start);
+    }
+}
+
+/// Parse a compound RTCP packet.
+///
+/// Returns `None` if any packet header is malformed or the lengths do not
+/// add up to the datagram size (RFC 3550 Appendix A.2).
+pub fn parse_compound(data: &[u8]) -> Option<Vec<RtcpPacket>> {
+    let mut packets = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        if rest.len() < 4 || rest[0] >> 6 != 2 {
+            return None;
+        }
+        let count = (rest[0] & 0x1f) as usize;
+        let pt = rest[1];
+        let len = (u16::from_be_bytes([rest[2], rest[3]]) as usize + 1) * 4;
+        let body = rest.get(4..len)?;
+        rest = &rest[len..];
+
+        let packet = match pt {
+            PT_SR => {
+                let ssrc = read_u32(body, 0)?;
+                RtcpPacket::SenderReport(SenderReport {
+                    ssrc,
+                    ntp_timestamp: NtpTimestamp(read_u64(body, 4)?),
+                    rtp_timestamp: read_u32(body, 12)?,
+                    packet_count: read_u32(body, 16)?,
+                    octet_count: read_u32(body, 20)?,
+                    reports: read_report_blocks(body.get(24..)?, count)?,
+                })
+            }
+            PT_RR => RtcpPacket::ReceiverReport(ReceiverReport {
+                ssrc: read_u32(body, 0)?,
+                reports: read_report_blocks(body.get(4..)?, count)?,
+            }),
+            PT_SDES => RtcpPacket::SourceDescription(read_sdes(body, count)?),
+            PT_BYE => {
+                let sources = (0..count)
+                    .map(|i| read_u32(body, i * 4))
+                    .collect::<Option<Vec<_>>>()?;
+                let reason = body.get(count * 4).and_then(|&len| {
+                    let text = body.get(count * 4 + 1..count * 4 + 1 + len as usize)?;
+                    Some(String::from_utf8_lossy(text).into_owned())
+                });
+                RtcpPacket::Goodbye { sources, reason }
+            }
+            PT_XR => RtcpPacket::ExtendedReport(ExtendedReport {
+                ssrc: read_u32(body, 0)?,
+                blocks: read_xr_blocks(body.get(4..)?)?,
+            }),
+            PT_RTPFB if count == FMT_NACK as usize => {
+                let mut lost = Vec::new();
+                for fci in body.get(8..)?.chunks_exact(4) {
+                    let pid = u16::from_be_bytes([fci[0], fci[1]]);
+                    let blp = u16::from_be_bytes([fci[2], fci[3]]);
+                    lost.push(pid);
+                    lost.extend(
+                        (0..16)
+                            .filter(|i| blp & (1 << i) != 0)
+                            .map(|i| pid.wrapping_add(i + 1)),
+                    );
+                }
+                RtcpPacket::Nack {
+                    sender_ssrc: read_u32(body, 0)?,
+                    media_ssrc: read_u32(body, 4)?,
+                    lost,
+                }
+            }
+            PT_PSFB if count == FMT_PLI as usize => RtcpPacket::Pli {
+                sender_ssrc: read_u32(body, 0)?,
+                media_ssrc: read_u32(body, 4)?,
+            },
+            PT_PSFB if count == FMT_FIR as usize => RtcpPacket::Fir {
+                sender_ssrc: read_u32(body, 0)?,
+                requests: body
+                    .get(8..)?
+                    .chunks_exact(8)
+                    .map(|fci| (u32::from_be_bytes([fci[0], fci[1], fci[2], fci[3]]), fci[4]))
+                    .collect(),
+            },
+            _ => RtcpPacket::Unknown { pt },
+        };
+        packets.push(packet);
+    }
+
+    Some(packets)
+}
+
+/// Serialize packets back to back into one compound RTCP packet (RFC 3550 §6.1).
+pub fn serialize_compound(packets: &[RtcpPacket]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for packet in packets {
+        packet.write(&mut buf);
+    }
+    buf
+}
+
+fn write_header(buf: &mut Vec<u8>, count: u8, pt: u8) {
+    buf.push((2 << 6) | (count & 0x1f));
+    buf.push(pt);
+    buf.extend_from_slice(&[0, 0]); // length, patched by finish_length
+}
+
+/// Patch the length field: packet size in 32-bit words minus one.
+fn finish_length(buf: &mut [u8], start: usize) {
+    let words = ((buf.len() - start) / 4 - 1) as u16;
+    buf[start + 2..start + 4].copy_from_slice(&words.to_be_bytes());
+}
+
+fn write_report_block(buf: &mut Vec<u8>, block: &ReportBlock) {
+    buf.extend_from_slice(&block.ssrc.to_be_bytes());
+    let lost = (block.cumulative_lost.clamp(-0x80_0000, 0x7F_FFFF) as u32) & 0x00FF_FFFF;
+    buf.extend_from_slice(&(((block.fraction_lost as u32) << 24) | lost).to_be_bytes());
+    buf.extend_from_slice(&block.highest_sequence.to_be_bytes());
+    buf.extend_from_slice(&block.jitter.to_be_bytes());
+    buf.extend_from_slice(&block.last_sr.to_be_bytes());
+    buf.extend_from_slice(&block.delay_since_last_sr.to_be_bytes());
+}
+
+/// Group sequence numbers into (PID, BLP) pairs: each pair covers PID and
+/// the 16 sequence numbers after it.
+fn nack_fci(lost: &[u16]) -> Vec<(u16, u16)> {
+    let mut fci: Vec<(u16, u16)> = Vec::new();
+    for &seq in lost {
+        if let Some((pid, blp)) = fci.last_mut() {
+            let offset = seq.wrapping_sub(*pid);
+            if (1..=16).contains(&offset) {
+                *blp |= 1 << (offset - 1);
+                continue;
+            }
+        }
+        fci.push((seq, 0));
+    }
+    fci
+}
+
+fn write_xr_block(buf: &mut Vec<u8>, block: &XrBlock) {
+    match block {
+        XrBlock::ReceiverReferenceTime(ntp) => {
+            buf.extend_from_slice(&[XR_RRTR, 0, 0, 2]);
+            buf.extend_from_slice(&ntp.0.to_be_bytes());
+        }
+        XrBlock::Dlrr(items) => {
+            buf.extend_from_slice(&[XR_DLRR, 0]);
+            buf.extend_from_slice(&((items.len() * 3) as u16).to_be_bytes());
+            for item in items {
+                buf.extend_from_slice(&item.ssrc.to_be_bytes());
+                buf.extend_from_slice(&item.last_rr.to_be_bytes());
+                buf.extend_from_slice(&item.delay_since_last_rr.to_be_bytes());
+            }
+        }
+        XrBlock::Other { block_type } => buf.extend_from_slice(&[*block_type, 0, 0, 0]),
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn read_report_blocks(data: &[u8], count: usize) -> Option<Vec<ReportBlock>> {
+    (0..count)
+        .map(|i| {
+            let block = data.get(i * 24..(i + 1) * 24)?;
+            let lost = read_u32(block, 4)?;
+            // Sign-extend the 24-bit cumulative loss.
+            let cumulative_lost = ((lost << 8) as i32) >> 8;
+            Some(ReportBlock {
+                ssrc: read_u32(block, 0)?,
+                fraction_lost: (lost >> 24) as u8,
+                cumulative_lost,
+                highest_sequence: read_u32(block, 8)?,
+                jitter: read_u32(block, 12)?,
+                last_sr: read_u32(block, 16)?,
+                delay_since_last_sr: read_u32(block, 20)?,
+            })
+        })
+        .collect()
+}
+
+/// Read SDES chunks, keeping only the CNAME of each source.
+fn read_sdes(data: &[u8], count: usize) -> Option<Vec<(u32, String)>> {
+    let mut chunks = Vec::with_capacity(count);
+    let mut at = 0;
+
+    for _ in 0..count {
+        let ssrc = read_u32(data, at)?;
+        at += 4;
+        let mut cname = String::new();
+        loop {
+            let item = *data.get(at)?;
+            if item == 0 {
+                // Skip the terminator and padding up to the next 32-bit boundary.
+                at = (at + 4) & !3;
+                break;
+            }
+            let len = *data.get(at + 1)? as usize;
+            let text = data.get(at + 2..at + 2 + len)?;
+            if item == SDES_CNAME {
+                cname = String::from_utf8_lossy(text).into_owned();
+            }
+            at += 2 + len;
+        }
+        chunks.push((ssrc, cname));
+    }
+
+    Some(chunks)
+}
+
+fn read_xr_blocks(mut data: &[u8]) -> Option<Vec<XrBlock>> {
+    let mut blocks = Vec::new();
+
+    while data.len() >= 4 {
+        let block_type = data[0];
+        let len = (u16::from_be_bytes([data[2], data[3]]) as usize + 1) * 4;
+        let body = data.get(4..len)?;
+        data = &data[len..];
+
+        blocks.push(match block_type {
+            XR_RRTR => XrBlock::ReceiverReferenceTime(NtpTimestamp(read_u64(body, 0)?)),
+            XR_DLRR => XrBlock::Dlrr(
+                body.chunks_exact(12)
+                    .map(|item| {
+                        Some(DlrrItem {
+                            ssrc: read_u32(item, 0)?,
+                            last_rr: read_u32(item, 4)?,
+                            delay_since_last_rr: read_u32(item, 8)?,
+                        })
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            _ => XrBlock::Other { block_type },
+        });
+    }
+
+    Some(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sr() -> SenderReport {
+        SenderReport {
+            ssrc: 0xAABBCCDD,
+            ntp_timestamp: NtpTimestamp(0x0102030405060708),
+            rtp_timestamp: 90000,
+            packet_count: 10,
+            octet_count: 12000,
+            reports: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sender_report_layout() {
+        let mut buf = Vec::new();
+        RtcpPacket::SenderReport(sr()).write(&mut buf);
+
+        assert_eq!(buf.len(), 28);
+        assert_eq!(buf[0], 0x80);
+        assert_eq!(buf[1], PT_SR);
+        assert_eq!(u16::from_be_bytes([buf[2], buf[3]]), 6);
+        assert_eq!(&buf[4..8], &0xAABBCCDDu32.to_be_bytes());
+        assert_eq!(&buf[8..16], &0x0102030405060708u64.to_be_bytes());
+        assert_eq!(&buf[16..20], &90000u32.to_be_bytes());
+        assert_eq!(&buf[20..24], &10u32.to_be_bytes());
+        assert_eq!(&buf[24..28], &12000u32.to_be_bytes());
+    }
+
+    #[test]
+    fn sender_report_with_block() {
+        let mut report = sr();
+        report.reports.push(ReportBlock {
+            ssrc: 1,
+            fraction_lost: 0x40,
+            cumulative_lost: -1,
+            highest_sequence: 7,
+            jitter: 3,
+            last_sr: 0,
+            delay_since_last_sr: 0,
+        });
+        let mut buf = Vec::new();
+        RtcpPacket::SenderReport(report).write(&mut buf);
+
+        assert_eq!(buf.len(), 52);
+        assert_eq!(buf[0] & 0x1f, 1);
+        assert_eq!(&buf[32..36], &[0x40, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn sdes_is_padded_to_word_boundary() {
+        let mut buf = Vec::new();
+        RtcpPacket::SourceDescription(vec![(7, "cam".to_string())]).write(&mut buf);
+
+        assert_eq!(buf.len() % 4, 0);
+        assert_eq!(buf[1], PT_SDES);
+        assert_eq!(buf[8], SDES_CNAME);
+        assert_eq!(buf[9], 3);
+        assert_eq!(&buf[10..13], b"cam");
+        assert_eq!(buf[13], 0);
+        let words = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        assert_eq!((words + 1) * 4, buf.len());
+    }
+
+    #[test]
+    fn compound_concatenates() {
+        let compound = serialize_compound(&[
+            RtcpPacket::SenderReport(sr()),
+            RtcpPacket::SourceDescription(vec![(0xAABBCCDD, "x".to_string())]),
+        ]);
+        assert_eq!(compound[1], PT_SR);
+        assert_eq!(compound[28 + 1], PT_SDES);
+    }
+
+    #[test]
+    fn parse_roundtrip() {
+        let mut report = sr();
+        report.reports.push(ReportBlock {
+            ssrc: 9,
+            fraction_lost: 12,
+            cumulative_lost: -3,
+            highest_sequence: 0x0001_0005,
+            jitter: 40,
+            last_sr: 0x1234_5678,
+            delay_since_last_sr: 65536,
+        });
+        let packets = vec![
+            RtcpPacket::SenderReport(report),
+            RtcpPacket::ReceiverReport(ReceiverReport {
+                ssrc: 5,
+                reports: Vec::new(),
+            }),
+            RtcpPacket::SourceDescription(vec![(5, "viewer@host".to_string())]),
+            RtcpPacket::ExtendedReport(ExtendedReport {
+                ssrc: 5,
+                blocks: vec![
+                    XrBlock::ReceiverReferenceTime(NtpTimestamp(42)),
+                    XrBlock::Dlrr(vec![DlrrItem {
+                        ssrc: 1,
+                        last_rr: 2,
+                        delay_since_last_rr: 3,
+                    }]),
+                ],
+            }),
+            RtcpPacket::Goodbye {
+                sources: vec![0xAABBCCDD],
+                reason: Some("stream ended".to_string()),
+            },
+            RtcpPacket::Goodbye {
+                sources: vec![1, 2],
+                reason: None,
+            },
+            RtcpPacket::Nack {
+                sender_ssrc: 5,
+                media_ssrc: 0xAABBCCDD,
+                lost: vec![65535, 0, 3, 15, 40],
+            },
+            RtcpPacket::Pli {
+                sender_ssrc: 5,
+                media_ssrc: 0xAABBCCDD,
+            },
+            RtcpPacket::Fir {
+                sender_ssrc: 5,
+                requests: vec![(0xAABBCCDD, 3)],
+            },
+        ];
+
+        let parsed = parse_compound(&serialize_compound(&packets)).unwrap();
+        assert_eq!(parsed, packets);
+    }
+
+    #[test]
+    fn parse_rejects_truncated() {
+        let mut buf = serialize_compound(&[RtcpPacket::SenderReport(sr())]);
+        buf.pop();
+        assert!(parse_compound(&buf).is_none());
+        assert!(parse_compound(&[0x40, PT_RR, 0, 1, 0, 0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn parse_keeps_unknown_types() {
+        let parsed = parse_compound(&[0x80, 210, 0, 0]).unwrap();
+        assert_eq!(parsed, vec![RtcpPacket::Unknown { pt: 210 }]);
+    }
+
+    #[test]
+    fn nack_packs_bitmask() {
+        assert_eq!(
+            nack_fci(&[100, 101, 116, 117]),
+            vec![(100, 0x8001), (117, 0)]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use parking_lot::RwLock;
+
+use crate::media::rtp::RtpPacket;
+
+/// Default number of packets kept per SSRC for retransmission.
+pub const DEFAULT_HISTORY_SIZE: usize = 1024;
+
+/// How lost packets reported by Generic NACK (RFC 4585 §6.2.1) are resent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retransmission {
+    /// Ignore NACKs and keep no packet history.
+    Disabled,
+    /// Resend the original packet unchanged on the media SSRC.
+    Direct,
+    /// Resend in an RFC 4588 RTX stream with its own payload type and SSRC.
+    Rtx { payload_type: u8 },
+}
+
+/// Bounded history of sent RTP packets, keyed by SSRC and sequence number.
+///
+/// Each SSRC gets a ring of `capacity` slots indexed by sequence number,
+/// so lookups are O(1) and old packets are overwritten as new ones arrive.
+#[derive(Debug)]
+pub struct PacketHistory {
+    capacity: usize,
+    streams: RwLock<HashMap<u32, StreamHistory>>,
+}
+
+#[derive(Debug)]
+struct StreamHistory {
+    rtx_ssrc: u32,
+    slots: Vec<Option<(u16, Vec<u8>)>>,
+}
+
+impl PacketHistory {
+    /// Create a history holding `capacity` packets per SSRC, rounded up to
+    /// a power of two so slots stay aligned across sequence wrap-around.
+    /// A capacity of zero disables recording.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = if capacity == 0 {
+            0
+        } else {
+            capacity.next_power_of_two().min(1 << 16)
+        };
+        Self {
+            capacity,
+            streams: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Remember a packet that is about to be sent.
+    pub fn record(&self, packet: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Some(rtp) = RtpPacket::parse(packet) else {
+            return;
+        };
+
+        let mut streams = self.streams.write();
+        let stream = streams.entry(rtp.ssrc).or_insert_with(|| StreamHistory {
+            rtx_ssrc: rand::random(),
+            slots: vec![None; self.capacity],
+        });
+        let slot = rtp.sequence as usize % self.capacity;
+        stream.slots[slot] = Some((rtp.sequence, packet.to_vec()));
+    }
+
+    /// Look up a previously sent packet.
+    pub fn get(&self, ssrc: u32, sequence: u16) -> Option<Vec<u8>> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let streams = self.streams.read();
+        match &streams.get(&ssrc)?.slots[sequence as usize % self.capacity] {
+            Some((seq, packet)) if *seq == sequence => Some(packet.clone()),
+            _ => None,
+        }
+    }
+
+    /// SSRC of the RTX stream paired with `ssrc`.
+    pub fn rtx_ssrc(&self, ssrc: u32) -> Option<u32> {
+        self.streams.read().get(&ssrc).map(|s| s.rtx_ssrc)
+    }
+
+    /// Forget the packets sent with `ssrc`.
+    pub fn remove(&self, ssrc: u32) {
+        self.streams.write().remove(&ssrc);
+    }
+
+    pub fn clear(&self) {
+        self.streams.write().clear();
+    }
+}
+
+/// Wrap an original packet in an RTX packet (RFC 4588 §4).
+///
+/// The payload is the original sequence number followed by the original
+/// payload; timestamp and marker are kept.
+pub fn build_rtx_packet(
+    original: &[u8],
+    payload_type: u8,
+    rtx_ssrc: u32,
+    rtx_sequence: u16,
+) -> Option<Vec<u8>> {
+    let rtp = RtpPacket::parse(original)?;
+
+    let mut packet = Vec::with_capacity(12 + 2 + rtp.payload.len());
+    packet.push(2 << 6);
+    packet.push(((rtp.marker as u8) << 7) | (payload_type & 0x7f));
+    packet.extend_from_slice(&rtx_sequence.to_be_bytes());
+    packet.extend_from_slice(&rtp.timestamp.to_be_bytes());
+    packet.extend_from_slice(&rtx_ssrc.to_be_bytes());
+    packet.extend_from_slice(&rtp.sequence.to_be_bytes());
+    packet.extend_from_slice(rtp.payload);
+    Some(packet)
+}
+
+/// Per-viewer retransmission counters.
+#[derive(Debug, Clone, Default)]
+pub struct RetransmissionStats {
+    pub nacks_received: u64,
+    pub packets_requested: u64,
+    pub packets_retransmitted: u64,
+    /// Requested packets no longer in the history.
+    pub packets_missed: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::rtp::RtpHeader;
+
+    fn packet(header: &mut RtpHeader, byte: u8) -> Vec<u8> {
+        let mut p = header.write(true).to_vec();
+        p.push(byte);
+        p
+    }
+
+    #[test]
+    fn stores_and_evicts() {
+        let history = PacketHistory::new(3);
+        assert_eq!(history.capacity(), 4);
+
+        let mut header = RtpHeader::new(96, 1);
+        let packets: Vec<Vec<u8>> = (0..6).map(|i| packet(&mut header, i)).collect();
+        for p in &packets {
+            history.record(p);
+        }
+
+        assert_eq!(history.get(1, 5), Some(packets[5].clone()));
+        assert_eq!(history.get(1, 2), Some(packets[2].clone()));
+        assert_eq!(history.get(1, 1), None);
+        assert_eq!(history.get(2, 5), None);
+    }
+
+    #[test]
+    fn zero_capacity_disables() {
+        let history = PacketHistory::new(0);
+        let mut header = RtpHeader::new(96, 1);
+        history.record(&packet(&mut header, 0));
+        assert_eq!(history.get(1, 0), None);
+    }
+
+    #[test]
+    fn rtx_packet_layout() {
+        let mut header = RtpHeader::new(96, 1);
+        header.advance_timestamp(3000);
+        let original = packet(&mut header, 0xAB);
+
+        let rtx = build_rtx_packet(&original, 97, 0x5555, 10).unwrap();
+        let parsed = RtpPacket::parse(&rtx).unwrap();
+        assert_eq!(parsed.pt, 97);
+        assert_eq!(parsed.ssrc, 0x5555);
+        assert_eq!(parsed.sequence, 10);
+        assert_eq!(parsed.timestamp, 3000);
+        assert!(parsed.marker);
+        assert_eq!(parsed.payload, &[0, 0, 0xAB]);
+    }
+}
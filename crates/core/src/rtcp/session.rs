@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::clock::{NtpTimestamp, StreamClocks};
+use super::interval::{IntervalParams, RTCP_BANDWIDTH_FRACTION};
+use super::packet::{
+    DlrrItem, ExtendedReport, ReportBlock, RtcpPacket, SenderReport, XrBlock, parse_compound,
+    serialize_compound,
+};
+use super::retransmit::RetransmissionStats;
+use crate::media::rtp::RtpPacket;
+
+/// IPv4 + UDP header overhead counted in the average RTCP size (RFC 3550 §6.2).
+const UDP_IP_OVERHEAD: usize = 28;
+
+/// Per-SSRC counters for what has been sent to one viewer.
+#[derive(Debug, Clone)]
+pub struct SenderStats {
+    pub ssrc: u32,
+    pub packet_count: u32,
+    /// Payload octets only, excluding RTP headers and padding.
+    pub octet_count: u32,
+    pub last_rtp_timestamp: u32,
+    pub last_sent: Instant,
+}
+
+/// Reception quality reported by a viewer in RR/SR report blocks.
+#[derive(Debug, Clone)]
+pub struct ReceiverStats {
+    /// SSRC of the reporting viewer.
+    pub ssrc: u32,
+    /// Fraction of packets lost since the previous report, in 1/256 units.
+    pub fraction_lost: u8,
+    pub cumulative_lost: i32,
+    pub highest_sequence: u32,
+    /// Interarrival jitter in RTP timestamp units.
+    pub jitter: u32,
+    /// Round-trip time from the last LSR/DLSR pair (RFC 3550 §6.4.1).
+    pub rtt: Option<Duration>,
+    pub reports_received: u64,
+    pub last_report: Instant,
+}
+
+impl ReceiverStats {
+    /// Fraction lost as a ratio in 0.0..=1.0.
+    pub fn loss_ratio(&self) -> f64 {
+        self.fraction_lost as f64 / 256.0
+    }
+
+    pub fn jitter_duration(&self, clock_rate: u32) -> Duration {
+        Duration::from_secs_f64(self.jitter as f64 / clock_rate.max(1) as f64)
+    }
+}
+
+/// RTCP state for one RTSP session.
+///
+/// Unicast RTSP means each session is its own two-party RTP session:
+/// the server as sender and one viewer as receiver.
+#[derive(Debug)]
+pub struct RtcpSession {
+    senders: HashMap<u32, SenderStats>,
+    avg_rtcp_size: f64,
+    initial: bool,
+    next_report: Option<Instant>,
+    last_report: Option<Instant>,
+    octets_since_report: u64,
+    receiver: Option<ReceiverStats>,
+    /// Last Receiver Reference Time (XR): (reporter SSRC, middle 32 bits, arrival).
+    last_rrtr: Option<(u32, u32, Instant)>,
+    degraded: bool,
+    retransmission: RetransmissionStats,
+    /// Next RTX sequence number per RTX SSRC.
+    rtx_sequences: HashMap<u32, u16>,
+    /// Last FIR command sequence number seen per target SSRC.
+    fir_sequences: HashMap<u32, u8>,
+}
+
+impl RtcpSession {
+    pub fn new() -> Self {
+        Self {
+            senders: HashMap::new(),
+            // Guess for the first compound packet: SR + SDES.
+            avg_rtcp_size: 80.0,
+            initial: true,
+            next_report: None,
+            last_report: None,
+            octets_since_report: 0,
+            receiver: None,
+            last_rrtr: None,
+            degraded: false,
+            retransmission: RetransmissionStats::default(),
+            rtx_sequences: HashMap::new(),
+            fir_sequences: HashMap::new(),
+        }
+    }
+
+    /// Account for an RTP packet that was just sent to this session.
+    pub fn on_rtp_sent(&mut self, packet: &[u8]) {
+        let Some(rtp) = RtpPacket::parse(packet) else {
+            return;
+        };
+        let now = Instant::now();
+        let stats = self.senders.entry(rtp.ssrc).or_insert(SenderStats {
+            ssrc: rtp.ssrc,
+            packet_count: 0,
+            octet_count: 0,
+            last_rtp_timestamp: rtp.timestamp,
+            last_sent: now,
+        });
+        stats.packet_count = stats.packet_count.wrapping_add(1);
+        stats.octet_count = stats.octet_count.wrapping_add(rtp.payload.len() as u32);
+        stats.last_rtp_timestamp = rtp.timestamp;
+        stats.last_sent = now;
+        self.octets_since_report += packet.len() as u64;
+    }
+
+    pub fn senders(&self) -> impl Iterator<Item = &SenderStats> {
+        self.senders.values()
+    }
+
+    /// Latest reception quality reported by the viewer, if any.
+    pub fn receiver_stats(&self) -> Option<&ReceiverStats> {
+        self.receiver.as_ref()
+    }
+
+    /// Ingest a compound RTCP packet received from the viewer.
+    ///
+    /// Reports update the reception statistics. The parsed packets are
+    /// returned so the caller can act on feedback such as NACKs; `None`
+    /// means the packet could not be parsed.
+    pub fn on_rtcp_received(&mut self, data: &[u8], now: Instant) -> Option<Vec<RtcpPacket>> {
+        let packets = parse_compound(data)?;
+        let arrival = NtpTimestamp::now().middle_32();
+
+        for packet in &packets {
+            match packet {
+                RtcpPacket::ReceiverReport(rr) => {
+                    self.on_report_blocks(rr.ssrc, &rr.reports, arrival, now);
+                }
+                RtcpPacket::SenderReport(sr) => {
+                    self.on_report_blocks(sr.ssrc, &sr.reports, arrival, now);
+                }
+                RtcpPacket::ExtendedReport(xr) => {
+                    for block in &xr.blocks {
+                        if let XrBlock::ReceiverReferenceTime(ntp) = block {
+                            self.last_rrtr = Some((xr.ssrc, ntp.middle_32(), now));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(packets)
+    }
+
+    pub fn retransmission_stats(&self) -> &RetransmissionStats {
+        &self.retransmission
+    }
+
+    /// Account for one NACK and how many of its packets could be resent.
+    pub fn record_nack(&mut self, requested: usize, retransmitted: usize) {
+        let stats = &mut self.retransmission;
+        stats.nacks_received += 1;
+        stats.packets_requested += requested as u64;
+        stats.packets_retransmitted += retransmitted as u64;
+        stats.packets_missed += (requested - retransmitted) as u64;
+    }
+
+    /// Next sequence number for the RTX stream `rtx_ssrc` towards this viewer.
+    pub fn next_rtx_sequence(&mut self, rtx_ssrc: u32) -> u16 {
+        let seq = self
+            .rtx_sequences
+            .entry(rtx_ssrc)
+            .or_insert_with(rand::random);
+        let current = *seq;
+        *seq = seq.wrapping_add(1);
+        current
+    }
+
+    /// Record whether the viewer is currently considered degraded.
+    /// Returns `true` if this changed the previous state.
+    pub fn set_degraded(&mut self, degraded: bool) -> bool {
+        std::mem::replace(&mut self.degraded, degraded) != degraded
+    }
+
+    /// Record a FIR command for `ssrc`. Returns `false` for a repeat of the
+    /// previous command, which must not trigger another keyframe (RFC 5104 §4.3.1.2).
+    pub fn accept_fir(&mut self, ssrc: u32, seq: u8) -> bool {
+        self.fir_sequences.insert(ssrc, seq) != Some(seq)
+    }
+
+    fn on_report_blocks(
+        &mut self,
+        reporter: u32,
+        blocks: &[ReportBlock],
+        arrival: u32,
+        now: Instant,
+    ) {
+        for block in blocks.iter().filter(|b| self.senders.contains_key(&b.ssrc)) {
+            // RTT = A - LSR - DLSR, all in 1/65536 s (RFC 3550 §6.4.1).
+            let rtt = (block.last_sr != 0)
+                .then(|| {
+                    arrival
+                        .wrapping_sub(block.last_sr)
+                        .wrapping_sub(block.delay_since_last_sr)
+                })
+                .filter(|rtt| *rtt < 0x8000_0000)
+                .map(|rtt| Duration::from_secs_f64(rtt as f64 / 65536.0));
+
+            let previous = self.receiver.take();
+            self.receiver = Some(ReceiverStats {
+                ssrc: reporter,
+                fraction_lost: block.fraction_lost,
+                cumulative_lost: block.cumulative_lost,
+                highest_sequence: block.highest_sequence,
+                jitter: block.jitter,
+                rtt: rtt.or(previous.as_ref().and_then(|p| p.rtt)),
+                reports_received: previous.map_or(0, |p| p.reports_received) + 1,
+                last_report: now,
+            });
+        }
+    }
+
+    /// Return a compound SR + SDES packet if a report is due at `now`.
+    ///
+    /// The first call only schedules the initial report. Sessions that have
+    /// not sent any media yet produce nothing but keep their schedule.
+    pub fn poll_report(
+        &mut self,
+        now: Instant,
+        clocks: &StreamClocks,
+        cname: &str,
+        min_interval: Duration,
+    ) -> Option<Vec<u8>> {
+        let Some(due) = self.next_report else {
+            self.next_report = Some(now + self.interval(now, min_interval));
+            return None;
+        };
+        if now < due {
+            return None;
+        }
+
+        let report = self.build_report(clocks, cname);
+        if let Some(bytes) = &report {
+            let size = (bytes.len() + UDP_IP_OVERHEAD) as f64;
+            self.avg_rtcp_size = size / 16.0 + self.avg_rtcp_size * 15.0 / 16.0;
+            self.initial = false;
+        }
+
+        self.next_report = Some(now + self.interval(now, min_interval));
+        self.last_report = Some(now);
+        self.octets_since_report = 0;
+        report
+    }
+
+    /// Compound SR + SDES + BYE announcing that every SSRC sent to this
+    /// viewer is leaving (RFC 3550 §6.6). `None` if nothing was ever sent.
+    pub fn build_bye(
+        &self,
+        clocks: &StreamClocks,
+        cname: &str,
+        reason: Option<&str>,
+    ) -> Option<Vec<u8>> {
+        let mut packets = self.report_packets(clocks, cname)?;
+        let mut sources: Vec<u32> = self.senders.keys().copied().collect();
+        sources.sort_unstable();
+        packets.push(RtcpPacket::Goodbye {
+            sources,
+            reason: reason.map(str::to_string),
+        });
+        Some(serialize_compound(&packets))
+    }
+
+    fn build_report(&self, clocks: &StreamClocks, cname: &str) -> Option<Vec<u8>> {
+        self.report_packets(clocks, cname)
+            .map(|packets| serialize_compound(&packets))
+    }
+
+    fn report_packets(&self, clocks: &StreamClocks, cname: &str) -> Option<Vec<RtcpPacket>> {
+        if self.senders.is_empty() {
+            return None;
+        }
+
+        let wall = SystemTime::now();
+        let ntp_timestamp = NtpTimestamp::from_system_time(wall);
+
+        let mut ssrcs: Vec<u32> = self.senders.keys().copied().collect();
+        ssrcs.sort_unstable();
+
+        let mut packets: Vec<RtcpPacket> = ssrcs
+            .iter()
+            .map(|ssrc| {
+                let stats = &self.senders[ssrc];
+                let rtp_timestamp = clocks
+                    .get(*ssrc)
+                    .map(|clock| clock.rtp_timestamp_at(wall))
+                    .unwrap_or(stats.last_rtp_timestamp);
+                RtcpPacket::SenderReport(SenderReport {
+                    ssrc: *ssrc,
+                    ntp_timestamp,
+                    rtp_timestamp,
+                    packet_count: stats.packet_count,
+                    octet_count: stats.octet_count,
+                    reports: Vec::new(),
+                })
+            })
+            .collect();
+
+        packets.push(RtcpPacket::SourceDescription(
+            ssrcs
+                .iter()
+                .map(|ssrc| (*ssrc, cname.to_string()))
+                .collect(),
+        ));
+
+        // Answer the viewer's Receiver Reference Time so it can compute RTT too.
+        if let Some((reporter, last_rr, received)) = self.last_rrtr {
+            let delay = received.elapsed().as_secs_f64() * 65536.0;
+            packets.push(RtcpPacket::ExtendedReport(ExtendedReport {
+                ssrc: ssrcs[0],
+                blocks: vec![XrBlock::Dlrr(vec![DlrrItem {
+                    ssrc: reporter,
+                    last_rr,
+                    delay_since_last_rr: delay as u32,
+                }])],
+            }));
+        }
+
+        Some(packets)
+    }
+
+    fn interval(&self, now: Instant, min_interval: Duration) -> Duration {
+        let elapsed = self
+            .last_report
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+        let session_bandwidth = if elapsed > 0.0 {
+            self.octets_since_report as f64 / elapsed
+        } else {
+            0.0
+        };
+        let we_sent = self
+            .senders
+            .values()
+            .any(|s| now.duration_since(s.last_sent) < min_interval * 2);
+
+        IntervalParams {
+            members: 2,
+            senders: if we_sent { 1 } else { 0 },
+            rtcp_bandwidth: session_bandwidth * RTCP_BANDWIDTH_FRACTION,
+            we_sent,
+            avg_rtcp_size: self.avg_rtcp_size,
+            initial: self.initial,
+            min_interval,
+        }
+        .randomized()
+    }
+}
+
+impl Default for RtcpSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::rtp::RtpHeader;
+    use crate::rtcp::packet::{PT_BYE, PT_SDES, PT_SR, ReceiverReport};
+
+    fn packet(header: &mut RtpHeader, payload_len: usize) -> Vec<u8> {
+        let mut p = header.write(false).to_vec();
+        p.extend(std::iter::repeat_n(0u8, payload_len));
+        p
+    }
+
+    #[test]
+    fn counts_packets_and_payload_octets() {
+        let mut rtcp = RtcpSession::new();
+        let mut header = RtpHeader::new(96, 0x1234);
+        rtcp.on_rtp_sent(&packet(&mut header, 100));
+        rtcp.on_rtp_sent(&packet(&mut header, 50));
+
+        let stats = rtcp.senders().next().unwrap();
+        assert_eq!(stats.ssrc, 0x1234);
+        assert_eq!(stats.packet_count, 2);
+        assert_eq!(stats.octet_count, 150);
+    }
+
+    #[test]
+    fn report_waits_for_schedule() {
+        let mut rtcp = RtcpSession::new();
+        let mut header = RtpHeader::new(96, 0x1234);
+        rtcp.on_rtp_sent(&packet(&mut header, 10));
+        let clocks = StreamClocks::new();
+        let now = Instant::now();
+
+        assert!(
+            rtcp.poll_report(now, &clocks, "cam", Duration::from_secs(5))
+                .is_none()
+        );
+        assert!(
+            rtcp.poll_report(now, &clocks, "cam", Duration::from_secs(5))
+                .is_none()
+        );
+
+        let later = now + Duration::from_secs(10);
+        let report = rtcp
+            .poll_report(later, &clocks, "cam", Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(report[1], PT_SR);
+        assert_eq!(&report[4..8], &0x1234u32.to_be_bytes());
+        assert_eq!(report[28 + 1], PT_SDES);
+    }
+
+    #[test]
+    fn report_uses_clock_mapping() {
+        let mut rtcp = RtcpSession::new();
+        let mut header = RtpHeader::new(96, 7);
+        rtcp.on_rtp_sent(&packet(&mut header, 10));
+
+        let clocks = StreamClocks::new();
+        let one_sec_ago = SystemTime::now() - Duration::from_secs(1);
+        clocks.set_capture_time(7, 1000, 90000, one_sec_ago);
+
+        let report = rtcp.build_report(&clocks, "cam").unwrap();
+        let rtp_ts = u32::from_be_bytes([report[16], report[17], report[18], report[19]]);
+        let expected = 1000 + 90000;
+        assert!(rtp_ts.abs_diff(expected) < 9000, "rtp_ts={rtp_ts}");
+    }
+
+    #[test]
+    fn no_report_without_media() {
+        let mut rtcp = RtcpSession::new();
+        let clocks = StreamClocks::new();
+        let now = Instant::now();
+        rtcp.poll_report(now, &clocks, "cam", Duration::from_secs(5));
+        assert!(
+            rtcp.poll_report(
+                now + Duration::from_secs(60),
+                &clocks,
+                "cam",
+                Duration::from_secs(5)
+            )
+            .is_none()
+        );
+    }
+
+    fn receiver_report(ssrc: u32, last_sr: u32, delay_since_last_sr: u32) -> Vec<u8> {
+        serialize_compound(&[RtcpPacket::ReceiverReport(ReceiverReport {
+            ssrc: 0xBEEF,
+            reports: vec![ReportBlock {
+                ssrc,
+                fraction_lost: 64,
+                cumulative_lost: 10,
+                highest_sequence: 500,
+                jitter: 900,
+                last_sr,
+                delay_since_last_sr,
+            }],
+        })])
+    }
+
+    #[test]
+    fn ingests_receiver_report() {
+        let mut rtcp = RtcpSession::new();
+        let mut header = RtpHeader::new(96, 0x1234);
+        rtcp.on_rtp_sent(&packet(&mut header, 10));
+
+        assert!(
+            rtcp.on_rtcp_received(&receiver_report(0x1234, 0, 0), Instant::now())
+                .is_some()
+        );
+
+        let stats = rtcp.receiver_stats().unwrap();
+        assert_eq!(stats.ssrc, 0xBEEF);
+        assert_eq!(stats.loss_ratio(), 0.25);
+        assert_eq!(stats.cumulative_lost, 10);
+        assert_eq!(stats.jitter_duration(90000), Duration::from_millis(10));
+        assert!(stats.rtt.is_none());
+    }
+
+    #[test]
+    fn computes_rtt_from_lsr_and_dlsr() {
+        let mut rtcp = RtcpSession::new();
+        let mut header = RtpHeader::new(96, 0x1234);
+        rtcp.on_rtp_sent(&packet(&mut header, 10));
+
+        // SR sent 1.5 s ago, held by the viewer for 1 s => RTT ≈ 0.5 s.
+        let now = NtpTimestamp::now().middle_32();
+        let lsr = now.wrapping_sub(65536 * 3 / 2);
+        rtcp.on_rtcp_received(&receiver_report(0x1234, lsr, 65536), Instant::now());
+
+        let rtt = rtcp.receiver_stats().unwrap().rtt.unwrap();
+        assert!(rtt >= Duration::from_millis(500) && rtt < Duration::from_millis(600));
+    }
+
+    #[test]
+    fn ignores_reports_about_other_sources() {
+        let mut rtcp = RtcpSession::new();
+        rtcp.on_rtcp_received(&receiver_report(0x9999, 0, 0), Instant::now());
+        assert!(rtcp.receiver_stats().is_none());
+        assert!(rtcp.on_rtcp_received(&[1, 2, 3], Instant::now()).is_none());
+    }
+
+    #[test]
+    fn bye_ends_compound_packet() {
+        let mut rtcp = RtcpSession::new();
+        let clocks = StreamClocks::new();
+        assert!(rtcp.build_bye(&clocks, "cam", None).is_none());
+
+        let mut header = RtpHeader::new(96, 0x1234);
+        rtcp.on_rtp_sent(&packet(&mut header, 10));
+        let bye = rtcp.build_bye(&clocks, "cam", Some("done")).unwrap();
+
+        let packets = parse_compound(&bye).unwrap();
+        assert_eq!(bye[1], PT_SR);
+        assert_eq!(
+            packets.last(),
+            Some(&RtcpPacket::Goodbye {
+                sources: vec![0x1234],
+                reason: Some("done".to_string()),
+            })
+        );
+        assert!(bye.windows(2).any(|w| w == [0x81, PT_BYE]));
+    }
+}
@@ -1,17 +1,84 @@
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener};
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::{Duration, SystemTime};
 
 use parking_lot::Mutex;
 
 use crate::error::{Result, RtspError};
 use crate::media::Packetizer;
 use crate::media::h264::H264Packetizer;
-use crate::session::SessionManager;
+use crate::media::jitter::DEFAULT_JITTER_LATENCY;
+use crate::media::keyframe::{DEFAULT_KEYFRAME_REQUEST_INTERVAL, KeyframeRequest};
+use crate::media::rtp::RtpPacket;
+use crate::mount::{PublishPolicy, RtpSource};
+use crate::protocol::{
+    AccessPolicy, AuthSettings, Authenticator, MessageLimits, ParameterError, ParameterRequest,
+    UrlSigner,
+};
+use crate::rtcp::Retransmission;
+use crate::rtcp::interval::RTCP_MIN_INTERVAL;
+use crate::rtcp::retransmit::DEFAULT_HISTORY_SIZE;
+use crate::session::ports::DEFAULT_SERVER_PORT_RANGE;
+use crate::session::{
+    DEFAULT_SESSION_TIMEOUT_SECS, Session, SessionManager, SessionSettings, reaper,
+};
 use crate::transport::UdpTransport;
+use crate::transport::ingest::UdpSource;
+use crate::transport::relay::{Relay, RelayConfig};
+use crate::transport::rtcp::{self, RtcpSettings};
 use crate::transport::tcp;
 
+/// Tunable server settings.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// UDP ports handed out as `server_port` in SETUP. RTP gets the even
+    /// port of each pair, RTCP the odd one after it.
+    pub server_port_range: RangeInclusive<u16>,
+    /// Minimum interval between RTCP Sender Reports (RFC 3550 §6.2).
+    pub rtcp_interval: Duration,
+    /// SDES CNAME sent with every Sender Report.
+    pub cname: String,
+    /// How packets reported lost via RTCP Generic NACK are resent.
+    pub retransmission: Retransmission,
+    /// Sent packets kept per SSRC for answering NACKs.
+    pub packet_history_size: usize,
+    /// Minimum time between keyframe requests passed to the application.
+    /// Requests arriving sooner are coalesced and delivered once it passes.
+    pub keyframe_request_interval: Duration,
+    /// Sessions are removed after this long without an RTSP request,
+    /// keepalive or RTCP report from the client. Advertised in whole seconds.
+    pub session_timeout: Duration,
+    /// Size limits on requests read from clients. Oversized requests are
+    /// answered with 400 or 413.
+    pub message_limits: MessageLimits,
+    /// Which paths clients may publish to with ANNOUNCE and RECORD.
+    /// Published streams are relayed to viewers of the same path.
+    pub publish_policy: PublishPolicy,
+    /// How long RTP arriving at a UDP source waits for packets missing
+    /// before it, before the gap is given up as loss.
+    pub ingest_latency: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            server_port_range: DEFAULT_SERVER_PORT_RANGE,
+            rtcp_interval: RTCP_MIN_INTERVAL,
+            cname: rtcp::random_cname(),
+            retransmission: Retransmission::Direct,
+            packet_history_size: DEFAULT_HISTORY_SIZE,
+            keyframe_request_interval: DEFAULT_KEYFRAME_REQUEST_INTERVAL,
+            session_timeout: Duration::from_secs(DEFAULT_SESSION_TIMEOUT_SECS),
+            message_limits: MessageLimits::default(),
+            publish_policy: PublishPolicy::default(),
+            ingest_latency: DEFAULT_JITTER_LATENCY,
+        }
+    }
+}
+
 /// High-level RTSP server orchestrator.
 ///
 /// Owns the session manager, transport layer, and a default packetizer.
@@ -23,27 +90,59 @@ pub struct Server {
     bind_addr: String,
     udp: Option<UdpTransport>,
     packetizer: Arc<Mutex<Box<dyn Packetizer>>>,
+    clock_rate: u32,
+    message_limits: MessageLimits,
+    ingest_latency: Duration,
 }
 
 impl Server {
     pub fn new(bind_addr: &str) -> Self {
-        Self {
-            session_manager: SessionManager::new(),
-            running: Arc::new(AtomicBool::new(false)),
-            bind_addr: bind_addr.to_string(),
-            udp: None,
-            packetizer: Arc::new(Mutex::new(Box::new(H264Packetizer::with_random_ssrc(96)))),
-        }
+        Self::with_config(bind_addr, ServerConfig::default())
+    }
+
+    /// Create a server with custom [`ServerConfig`] settings.
+    pub fn with_config(bind_addr: &str, config: ServerConfig) -> Self {
+        Self::build(
+            bind_addr,
+            Box::new(H264Packetizer::with_random_ssrc(96)),
+            config,
+        )
     }
 
     /// Create a server with a custom packetizer (for H.265, etc. in the future).
     pub fn with_packetizer(bind_addr: &str, packetizer: Box<dyn Packetizer>) -> Self {
+        Self::build(bind_addr, packetizer, ServerConfig::default())
+    }
+
+    fn build(bind_addr: &str, packetizer: Box<dyn Packetizer>, config: ServerConfig) -> Self {
+        let clock_rate = packetizer.clock_rate();
+        let rtcp = RtcpSettings {
+            cname: config.cname,
+            min_interval: config.rtcp_interval,
+            clock_rate,
+            retransmission: config.retransmission,
+            history_size: config.packet_history_size,
+            keyframe_request_interval: config.keyframe_request_interval,
+        };
+        let session_manager = SessionManager::with_config(
+            config.server_port_range,
+            rtcp,
+            SessionSettings {
+                timeout: config.session_timeout,
+            },
+        );
+        session_manager
+            .mounts()
+            .set_publish_policy(config.publish_policy);
         Self {
-            session_manager: SessionManager::new(),
+            session_manager,
             running: Arc::new(AtomicBool::new(false)),
             bind_addr: bind_addr.to_string(),
             udp: None,
             packetizer: Arc::new(Mutex::new(packetizer)),
+            clock_rate,
+            message_limits: config.message_limits,
+            ingest_latency: config.ingest_latency,
         }
     }
 
@@ -62,47 +161,235 @@ impl Server {
         let running = self.running.clone();
         let session_manager = self.session_manager.clone();
         let packetizer = self.packetizer.clone();
+        let limits = self.message_limits.clone();
 
         tracing::info!(addr = %self.bind_addr, "RTSP server listening");
 
         thread::spawn(move || {
-            tcp::accept_loop(listener, session_manager, packetizer, running);
+            tcp::accept_loop(listener, session_manager, packetizer, limits, running);
+        });
+
+        let running = self.running.clone();
+        let session_manager = self.session_manager.clone();
+
+        thread::spawn(move || {
+            rtcp::rtcp_loop(session_manager, running);
+        });
+
+        let running = self.running.clone();
+        let session_manager = self.session_manager.clone();
+
+        thread::spawn(move || {
+            reaper::reaper_loop(session_manager, running);
         });
 
         Ok(())
     }
 
+    /// Stop the server, sending every viewer an RTCP BYE first.
     pub fn stop(&mut self) {
-        self.running.store(false, Ordering::SeqCst);
+        if self.running.swap(false, Ordering::SeqCst) {
+            self.session_manager
+                .remove_all_sessions(Some("server shutting down"));
+        }
         tracing::info!("server stopping");
     }
 
+    /// End the stream gracefully: every viewer gets an RTCP BYE with the
+    /// optional `reason` and its session is removed, so players report the
+    /// stream as ended instead of waiting for a timeout. Viewers and
+    /// publishers of mounts are left alone.
+    ///
+    /// The server keeps running; viewers can reconnect once the application
+    /// sends media again.
+    pub fn end_stream(&self, reason: Option<&str>) -> usize {
+        let removed = self.session_manager.remove_stream_sessions(reason);
+        // Re-anchor the NTP↔RTP mapping when media resumes.
+        let ssrc = self.packetizer.lock().rtp_header().ssrc;
+        self.session_manager.clocks().remove(ssrc);
+        self.session_manager.history().remove(ssrc);
+        tracing::info!(removed, reason, "stream ended");
+        removed
+    }
+
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
 
     pub fn send_rtp_packet(&self, session_id: &str, payload: &[u8]) -> Result<usize> {
         let udp = self.udp.as_ref().ok_or(RtspError::NotStarted)?;
+        self.observe_sent(payload);
         udp.send_to_session(&self.session_manager, session_id, payload)
     }
 
     pub fn broadcast_rtp_packet(&self, payload: &[u8]) -> Result<usize> {
         let udp = self.udp.as_ref().ok_or(RtspError::NotStarted)?;
+        self.observe_sent(payload);
         udp.broadcast(&self.session_manager, payload)
     }
 
+    /// Tell the server when the frame carried by `rtp_packet` was captured.
+    ///
+    /// Any packet of the frame will do; its SSRC and RTP timestamp anchor the
+    /// NTP↔RTP mapping reported in RTCP Sender Reports. Without this, the
+    /// mapping is anchored at the time the stream's first packet was sent.
+    pub fn set_frame_capture_time(
+        &self,
+        rtp_packet: &[u8],
+        capture_time: SystemTime,
+    ) -> Result<()> {
+        let rtp = RtpPacket::parse(rtp_packet).ok_or(RtspError::InvalidRtpPacket)?;
+        self.session_manager.clocks().set_capture_time(
+            rtp.ssrc,
+            rtp.timestamp,
+            self.clock_rate,
+            capture_time,
+        );
+        Ok(())
+    }
+
+    /// Call `callback` whenever a viewer needs a keyframe: on RTCP PLI or
+    /// FIR, and when a viewer starts playing.
+    ///
+    /// The callback runs on a server thread and should only signal the
+    /// encoder. Once set, requests are no longer queued for
+    /// [`Server::poll_keyframe_request`].
+    pub fn on_keyframe_request(&self, callback: impl Fn(&KeyframeRequest) + Send + Sync + 'static) {
+        self.session_manager.keyframes().set_callback(callback);
+    }
+
+    /// Take the pending keyframe request, if any, for applications that
+    /// check once per frame instead of registering a callback.
+    pub fn poll_keyframe_request(&self) -> Option<KeyframeRequest> {
+        self.session_manager.keyframes().poll()
+    }
+
+    /// Answer GET_PARAMETER requests naming application parameters, such as
+    /// position or bitrate. `getter` receives the parameter name and the
+    /// requesting session, and returns `None` for unknown parameters, which
+    /// are answered with 451 Parameter Not Understood.
+    ///
+    /// Runs on a connection thread, so it should only read current values.
+    pub fn on_get_parameter(
+        &self,
+        getter: impl Fn(&str, Option<&str>) -> Option<String> + Send + Sync + 'static,
+    ) {
+        self.session_manager.parameters().set_getter(getter);
+    }
+
+    /// Apply SET_PARAMETER requests, e.g. to change an overlay or the
+    /// encoder bitrate. `setter` receives all pairs of a request at once and
+    /// should apply all or none of them; its error picks the response status.
+    pub fn on_set_parameter(
+        &self,
+        setter: impl Fn(&ParameterRequest) -> std::result::Result<(), ParameterError>
+        + Send
+        + Sync
+        + 'static,
+    ) {
+        self.session_manager.parameters().set_setter(setter);
+    }
+
+    /// Accept `tag` in `Require` and `Proxy-Require` headers. Requests
+    /// requiring a tag that isn't registered get 551 Option Not Supported.
+    pub fn register_feature_tag(&self, tag: &str) {
+        self.session_manager.features().register(tag);
+    }
+
+    /// Serve RTP packets the application already formed at `path`, next
+    /// to the default stream.
+    ///
+    /// `sdp` describes the packets: a full session description, or only
+    /// its `m=` sections, e.g. from [`RtpCodec::media_description`]. Push
+    /// packets with [`RtpSource::push`]; dropping the source ends the
+    /// mount.
+    ///
+    /// [`RtpCodec::media_description`]: crate::mount::RtpCodec::media_description
+    pub fn add_rtp_source(&self, path: &str, sdp: &str) -> Result<RtpSource> {
+        RtpSource::new(path, sdp, self.session_manager.clone())
+    }
+
+    /// Serve RTP arriving on the local UDP address `bind` at `path`,
+    /// turning the server into an RTP-to-RTSP gateway for encoders that
+    /// push plain RTP.
+    ///
+    /// `sdp` describes the incoming stream as for [`Self::add_rtp_source`],
+    /// e.g. the SDP file `ffmpeg -f rtp` prints, or the `m=` section of an
+    /// [`RtpCodec`]. Dropping the source ends the mount.
+    ///
+    /// [`RtpCodec`]: crate::mount::RtpCodec
+    pub fn add_udp_source(&self, path: &str, bind: SocketAddr, sdp: &str) -> Result<UdpSource> {
+        let source = self.add_rtp_source(path, sdp)?;
+        UdpSource::spawn(
+            bind,
+            source,
+            self.ingest_latency,
+            self.session_manager.clone(),
+        )
+    }
+
+    /// Require clients to authenticate against `authenticator` for every
+    /// request but OPTIONS, with Digest and, if `settings` allow it, Basic.
+    pub fn set_authenticator(
+        &self,
+        authenticator: impl Authenticator + 'static,
+        settings: AuthSettings,
+    ) {
+        self.session_manager.auth().enable(authenticator, settings);
+    }
+
+    /// Serve clients without asking for credentials again.
+    pub fn clear_authenticator(&self) {
+        self.session_manager.auth().disable();
+    }
+
+    /// Decide who may connect, and who may use which methods on which
+    /// mounts. Replaces the previous policy; connections already accepted
+    /// keep running, but their further requests are checked against it.
+    pub fn set_access_policy(&self, policy: AccessPolicy) {
+        self.session_manager.access().set_policy(policy);
+    }
+
+    /// Accept URLs signed with `secret` by [`UrlSigner::sign_url`] in place
+    /// of credentials. Clients presenting neither get 403, unless an access
+    /// rule lets anyone through.
+    pub fn set_url_secret(&self, secret: impl AsRef<[u8]>) {
+        self.session_manager
+            .access()
+            .set_signer(Some(UrlSigner::new(secret)));
+    }
+
+    /// Stop accepting signed URLs.
+    pub fn clear_url_secret(&self) {
+        self.session_manager.access().set_signer(None);
+    }
+
+    /// Restream the RTSP stream at `url` from `path`, so any number of
+    /// viewers share one upstream session, e.g. of a camera that allows
+    /// only a few.
+    ///
+    /// The relay connects in the background, pulling on demand or always
+    /// as `config` says, and reconnects with backoff when upstream fails.
+    /// Dropping the relay ends the mount.
+    pub fn add_relay(&self, path: &str, url: &str, config: RelayConfig) -> Result<Relay> {
+        Relay::spawn(path, url, config, self.session_manager.clone())
+    }
+
+    /// Track an outgoing packet for RTCP timing and NACK retransmission.
+    fn observe_sent(&self, payload: &[u8]) {
+        self.session_manager.history().record(payload);
+        if let Some(rtp) = RtpPacket::parse(payload) {
+            self.session_manager
+                .clocks()
+                .observe(rtp.ssrc, rtp.timestamp, self.clock_rate);
+        }
+    }
+
     pub fn get_viewers(&self) -> Vec<Viewer> {
         self.session_manager
             .get_playing_sessions()
             .iter()
-            .filter_map(|session| {
-                session.get_transport().map(|transport| Viewer {
-                    session_id: session.id.clone(),
-                    uri: session.uri.clone(),
-                    client_addr: transport.client_addr.to_string(),
-                    client_rtp_port: transport.client_rtp_port,
-                })
-            })
+            .filter_map(|session| Viewer::of(session, self.clock_rate))
             .collect()
     }
 
@@ -120,10 +407,54 @@ impl Server {
 }
 
 /// Information about a connected viewer (client in PLAY state).
+///
+/// Reception fields are `None` until the viewer sends its first RTCP
+/// receiver report.
 #[derive(Debug, Clone)]
 pub struct Viewer {
     pub session_id: String,
     pub uri: String,
     pub client_addr: String,
     pub client_rtp_port: u16,
+    pub packets_sent: u64,
+    /// Payload octets sent, excluding RTP headers.
+    pub octets_sent: u64,
+    /// Fraction of packets lost since the viewer's previous report (0.0–1.0).
+    pub fraction_lost: Option<f64>,
+    pub cumulative_lost: Option<i32>,
+    pub jitter: Option<Duration>,
+    pub rtt: Option<Duration>,
+    pub nacks_received: u64,
+    pub packets_retransmitted: u64,
+    /// NACKed packets that had already left the packet history.
+    pub retransmissions_missed: u64,
+}
+
+impl Viewer {
+    /// Snapshot a session's statistics; `None` before it has a transport.
+    pub(crate) fn of(session: &Session, clock_rate: u32) -> Option<Self> {
+        let transport = session.get_transport()?;
+        let rtcp = session.rtcp.lock();
+        let (packets_sent, octets_sent) = rtcp.senders().fold((0u64, 0u64), |acc, s| {
+            (acc.0 + s.packet_count as u64, acc.1 + s.octet_count as u64)
+        });
+        let reception = rtcp.receiver_stats();
+        let retransmission = rtcp.retransmission_stats();
+
+        Some(Viewer {
+            session_id: session.id.clone(),
+            uri: session.uri.clone(),
+            client_addr: transport.client_addr.to_string(),
+            client_rtp_port: transport.client_rtp_port,
+            packets_sent,
+            octets_sent,
+            fraction_lost: reception.map(|r| r.loss_ratio()),
+            cumulative_lost: reception.map(|r| r.cumulative_lost),
+            jitter: reception.map(|r| r.jitter_duration(clock_rate)),
+            rtt: reception.and_then(|r| r.rtt),
+            nacks_received: retransmission.nacks_received,
+            packets_retransmitted: retransmission.packets_retransmitted,
+            retransmissions_missed: retransmission.packets_missed,
+        })
+    }
 }
@@ -1,45 +1,82 @@
+pub mod ports;
+pub mod reaper;
+pub mod state;
 pub mod transport;
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::ops::RangeInclusive;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::error::Result;
-pub use transport::Transport;
-
-static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
-
-const SERVER_PORT_MIN: u64 = 5000;
-const SERVER_PORT_MAX: u64 = 65534;
+use crate::media::keyframe::KeyframeRequests;
+use crate::mount::MountRegistry;
+use crate::protocol::access::AccessControl;
+use crate::protocol::headers::SessionHeader;
+use crate::protocol::{Auth, FeatureTags, Parameters};
+use crate::rtcp::{PacketHistory, Retransmission, RtcpSession, StreamClocks};
+use crate::transport::rtcp::{self, RtcpSettings};
+use crate::transport::{ConnectionWriter, SessionSockets};
+pub use ports::PortPool;
+pub use state::SessionState;
+pub use transport::{Transport, TransportMode};
 
 pub const DEFAULT_SESSION_TIMEOUT_SECS: u64 = 60;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum SessionState {
-    Ready,
-    Playing,
-    Paused,
-}
+/// BYE reason sent to viewers whose session expired.
+const TIMEOUT_REASON: &str = "session timed out";
+
+/// BYE reason sent to viewers of a mount whose publisher left.
+const MOUNT_ENDED_REASON: &str = "stream ended";
 
 #[derive(Debug)]
 pub struct Session {
     pub id: String,
     pub uri: String,
     pub transport: RwLock<Option<Transport>>,
+    /// Transports a publisher set up over UDP, by mount track.
+    track_transports: Mutex<HashMap<usize, Transport>>,
     pub state: RwLock<SessionState>,
+    /// Whether the session plays media to its client or records it from
+    /// a publisher, fixed by the SETUP that created it.
+    mode: RwLock<TransportMode>,
     pub timeout_secs: u64,
+    last_activity: Mutex<Instant>,
+    /// Connection that created the session, or took it over by hand-off.
+    owner: Mutex<Option<u64>>,
+    handoff: AtomicBool,
+    sockets: RwLock<Option<Arc<SessionSockets>>>,
+    /// The RTSP connection media is interleaved on, for TCP viewers.
+    writer: RwLock<Option<Arc<ConnectionWriter>>>,
+    /// Path of the mount the session plays or publishes, if any.
+    mount: RwLock<Option<String>>,
+    pub rtcp: Mutex<RtcpSession>,
 }
 
 impl Session {
     pub fn new(uri: &str) -> Self {
-        let id = SESSION_COUNTER.fetch_add(1, Ordering::SeqCst);
+        Self::with_timeout(uri, DEFAULT_SESSION_TIMEOUT_SECS)
+    }
+
+    /// Create a session that expires after `timeout_secs` without activity.
+    pub fn with_timeout(uri: &str, timeout_secs: u64) -> Self {
         Session {
-            id: format!("{:016X}", id),
+            id: new_session_id(),
             uri: uri.to_string(),
             transport: RwLock::new(None),
+            track_transports: Mutex::new(HashMap::new()),
             state: RwLock::new(SessionState::Ready),
-            timeout_secs: DEFAULT_SESSION_TIMEOUT_SECS,
+            mode: RwLock::new(TransportMode::Play),
+            timeout_secs,
+            last_activity: Mutex::new(Instant::now()),
+            owner: Mutex::new(None),
+            handoff: AtomicBool::new(false),
+            sockets: RwLock::new(None),
+            writer: RwLock::new(None),
+            mount: RwLock::new(None),
+            rtcp: Mutex::new(RtcpSession::new()),
         }
     }
 
@@ -52,41 +89,244 @@ impl Session {
         self.transport.read().clone()
     }
 
+    /// Record the transport a publisher set up for `track` of its mount.
+    pub fn set_track_transport(&self, track: usize, transport: Transport) {
+        tracing::debug!(session_id = %self.id, track, server_rtp_port = transport.server_rtp_port, "track transport configured");
+        self.track_transports.lock().insert(track, transport);
+    }
+
+    pub fn track_transport(&self, track: usize) -> Option<Transport> {
+        self.track_transports.lock().get(&track).cloned()
+    }
+
+    pub fn set_sockets(&self, sockets: SessionSockets) {
+        *self.sockets.write() = Some(Arc::new(sockets));
+    }
+
+    /// UDP sockets bound to this session's server ports, if SETUP bound them.
+    pub fn sockets(&self) -> Option<Arc<SessionSockets>> {
+        self.sockets.read().clone()
+    }
+
+    pub fn set_writer(&self, writer: Arc<ConnectionWriter>) {
+        *self.writer.write() = Some(writer);
+    }
+
+    /// The connection this session's media is interleaved on, if it set up
+    /// over TCP.
+    pub fn writer(&self) -> Option<Arc<ConnectionWriter>> {
+        self.writer.read().clone()
+    }
+
+    /// Path of the mount this session plays or publishes; `None` for
+    /// viewers of the application's own stream.
+    pub fn mount(&self) -> Option<String> {
+        self.mount.read().clone()
+    }
+
+    pub fn set_mount(&self, path: &str) {
+        *self.mount.write() = Some(path.to_string());
+    }
+
     pub fn set_state(&self, state: SessionState) {
         tracing::debug!(session_id = %self.id, old_state = ?*self.state.read(), new_state = ?state, "state transition");
         *self.state.write() = state;
     }
 
     pub fn get_state(&self) -> SessionState {
-        self.state.read().clone()
+        *self.state.read()
+    }
+
+    pub fn set_mode(&self, mode: TransportMode) {
+        *self.mode.write() = mode;
+    }
+
+    pub fn mode(&self) -> TransportMode {
+        *self.mode.read()
     }
 
     pub fn is_playing(&self) -> bool {
         *self.state.read() == SessionState::Playing
     }
 
+    /// Record client activity (an RTSP request, keepalive or RTCP report),
+    /// postponing expiry.
+    pub fn touch(&self) {
+        *self.last_activity.lock() = Instant::now();
+    }
+
+    pub fn last_activity(&self) -> Instant {
+        *self.last_activity.lock()
+    }
+
+    /// Whether the client has been silent for longer than the timeout at `now`.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_activity()) > Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Connection the session belongs to; `None` if not bound to one.
+    pub fn owner(&self) -> Option<u64> {
+        *self.owner.lock()
+    }
+
+    pub fn set_owner(&self, connection_id: u64) {
+        *self.owner.lock() = Some(connection_id);
+    }
+
+    /// Whether another connection may take the session over, as HTTP
+    /// tunnelling and client reconnects need. Off unless the client that
+    /// created the session opted in with [`SESSION_HANDOFF`].
+    ///
+    /// [`SESSION_HANDOFF`]: crate::protocol::features::SESSION_HANDOFF
+    pub fn allows_handoff(&self) -> bool {
+        self.handoff.load(Ordering::Relaxed)
+    }
+
+    pub fn set_handoff(&self, allow: bool) {
+        self.handoff.store(allow, Ordering::Relaxed);
+    }
+
     /// Returns the Session header value including timeout, e.g. "0000000000000001;timeout=60"
     pub fn session_header_value(&self) -> String {
-        format!("{};timeout={}", self.id, self.timeout_secs)
+        self.session_header().to_string()
+    }
+
+    pub fn session_header(&self) -> SessionHeader {
+        SessionHeader {
+            id: self.id.clone(),
+            timeout: Some(self.timeout_secs),
+        }
+    }
+}
+
+/// An unguessable session ID: 64 bits from the OS-seeded CSPRNG behind
+/// `rand::rng()`, so other clients can't hijack a session (RFC 2326 §12.37).
+fn new_session_id() -> String {
+    format!("{:016X}", rand::random::<u64>())
+}
+
+/// Settings applied to every session the manager creates.
+#[derive(Debug, Clone)]
+pub struct SessionSettings {
+    /// Sessions expire after this long without client activity.
+    /// Advertised in whole seconds.
+    pub timeout: Duration,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_SESSION_TIMEOUT_SECS),
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct SessionManager {
     sessions: Arc<RwLock<HashMap<String, Arc<Session>>>>,
-    next_server_port: Arc<AtomicU64>,
+    ports: Arc<PortPool>,
+    clocks: Arc<StreamClocks>,
+    history: Arc<PacketHistory>,
+    keyframes: Arc<KeyframeRequests>,
+    features: Arc<FeatureTags>,
+    parameters: Arc<Parameters>,
+    mounts: Arc<MountRegistry>,
+    auth: Arc<Auth>,
+    access: Arc<AccessControl>,
+    rtcp: Arc<RtcpSettings>,
+    settings: Arc<SessionSettings>,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
+        Self::with_config(
+            ports::DEFAULT_SERVER_PORT_RANGE,
+            RtcpSettings::default(),
+            SessionSettings::default(),
+        )
+    }
+
+    /// Create a session manager that leases server ports from `range`.
+    pub fn with_port_range(range: RangeInclusive<u16>) -> Self {
+        Self::with_config(range, RtcpSettings::default(), SessionSettings::default())
+    }
+
+    pub fn with_config(
+        range: RangeInclusive<u16>,
+        rtcp: RtcpSettings,
+        settings: SessionSettings,
+    ) -> Self {
+        let history_size = match rtcp.retransmission {
+            Retransmission::Disabled => 0,
+            _ => rtcp.history_size,
+        };
         SessionManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
-            next_server_port: Arc::new(AtomicU64::new(SERVER_PORT_MIN)),
+            ports: Arc::new(PortPool::new(range)),
+            clocks: Arc::new(StreamClocks::new()),
+            history: Arc::new(PacketHistory::new(history_size)),
+            keyframes: Arc::new(KeyframeRequests::new(rtcp.keyframe_request_interval)),
+            features: Arc::new(FeatureTags::new()),
+            parameters: Arc::new(Parameters::new()),
+            mounts: Arc::new(MountRegistry::new()),
+            auth: Arc::new(Auth::new()),
+            access: Arc::new(AccessControl::new()),
+            rtcp: Arc::new(rtcp),
+            settings: Arc::new(settings),
         }
     }
 
+    /// NTP↔RTP mappings of the streams sent to sessions.
+    pub fn clocks(&self) -> &Arc<StreamClocks> {
+        &self.clocks
+    }
+
+    /// Recently sent packets, for answering NACKs.
+    pub fn history(&self) -> &Arc<PacketHistory> {
+        &self.history
+    }
+
+    /// Keyframe requests from viewers, waiting for the application's encoder.
+    pub fn keyframes(&self) -> &Arc<KeyframeRequests> {
+        &self.keyframes
+    }
+
+    /// Option tags accepted in `Require` and `Proxy-Require`.
+    pub fn features(&self) -> &Arc<FeatureTags> {
+        &self.features
+    }
+
+    /// Application hooks for GET_PARAMETER and SET_PARAMETER.
+    pub fn parameters(&self) -> &Arc<Parameters> {
+        &self.parameters
+    }
+
+    /// Streams served from sources other than the application.
+    pub fn mounts(&self) -> &Arc<MountRegistry> {
+        &self.mounts
+    }
+
+    /// Credentials clients must present, shared by every connection.
+    pub fn auth(&self) -> &Arc<Auth> {
+        &self.auth
+    }
+
+    /// Who may connect, and who may use which mounts.
+    pub fn access(&self) -> &Arc<AccessControl> {
+        &self.access
+    }
+
+    pub fn rtcp_settings(&self) -> &RtcpSettings {
+        &self.rtcp
+    }
+
+    pub fn session_settings(&self) -> &SessionSettings {
+        &self.settings
+    }
+
     pub fn create_session(&self, uri: &str) -> Arc<Session> {
-        let session = Arc::new(Session::new(uri));
+        let timeout_secs = self.settings.timeout.as_secs().max(1);
+        let session = Arc::new(Session::with_timeout(uri, timeout_secs));
         let id = session.id.clone();
         self.sessions.write().insert(id.clone(), session.clone());
 
@@ -100,9 +340,20 @@ impl SessionManager {
         self.sessions.read().get(id).cloned()
     }
 
+    /// Remove a session, telling the viewer with an RTCP BYE.
     pub fn remove_session(&self, id: &str) -> Option<Arc<Session>> {
+        self.remove_session_with_reason(id, None)
+    }
+
+    /// Like [`Self::remove_session`], with a reason carried in the BYE.
+    pub fn remove_session_with_reason(
+        &self,
+        id: &str,
+        reason: Option<&str>,
+    ) -> Option<Arc<Session>> {
         let removed = self.sessions.write().remove(id);
-        if removed.is_some() {
+        if let Some(session) = &removed {
+            self.finish(session, reason);
             let total = self.sessions.read().len();
             tracing::debug!(session_id = %id, total_sessions = total, "session removed");
         }
@@ -111,33 +362,94 @@ impl SessionManager {
 
     /// Remove all sessions whose IDs are in the given list.
     pub fn remove_sessions(&self, ids: &[String]) -> usize {
-        let mut sessions = self.sessions.write();
-        let mut removed = 0;
-        for id in ids {
-            if sessions.remove(id).is_some() {
-                removed += 1;
-            }
+        let removed: Vec<Arc<Session>> = {
+            let mut sessions = self.sessions.write();
+            ids.iter().filter_map(|id| sessions.remove(id)).collect()
+        };
+        for session in &removed {
+            self.finish(session, None);
         }
-        if removed > 0 {
-            tracing::debug!(removed, remaining = sessions.len(), "batch session cleanup");
+        if !removed.is_empty() {
+            let remaining = self.sessions.read().len();
+            tracing::debug!(removed = removed.len(), remaining, "batch session cleanup");
         }
-        removed
+        removed.len()
     }
 
-    /// Allocate a pair of (RTP, RTCP) server ports.
-    /// Ports wrap around when the range is exhausted.
-    pub fn allocate_server_ports(&self) -> Result<(u16, u16)> {
-        let rtp = self.next_server_port.fetch_add(2, Ordering::SeqCst);
+    /// Remove every session, sending each viewer an RTCP BYE with `reason`.
+    pub fn remove_all_sessions(&self, reason: Option<&str>) -> usize {
+        let removed: Vec<Arc<Session>> = self.sessions.write().drain().map(|(_, s)| s).collect();
+        for session in &removed {
+            self.finish(session, reason);
+        }
+        if !removed.is_empty() {
+            tracing::debug!(removed = removed.len(), reason, "removed all sessions");
+        }
+        removed.len()
+    }
 
-        if rtp > SERVER_PORT_MAX {
-            tracing::warn!(rtp, "port range exhausted, wrapping to {SERVER_PORT_MIN}");
-            self.next_server_port.store(SERVER_PORT_MIN, Ordering::SeqCst);
-            let rtp = self.next_server_port.fetch_add(2, Ordering::SeqCst);
-            return Ok((rtp as u16, rtp as u16 + 1));
+    /// Remove the sessions of the application's own stream, leaving those
+    /// attached to a mount, sending each viewer an RTCP BYE with `reason`.
+    pub fn remove_stream_sessions(&self, reason: Option<&str>) -> usize {
+        let removed: Vec<Arc<Session>> = {
+            let mut sessions = self.sessions.write();
+            let ids: Vec<String> = sessions
+                .values()
+                .filter(|s| s.mount().is_none())
+                .map(|s| s.id.clone())
+                .collect();
+            ids.iter().filter_map(|id| sessions.remove(id)).collect()
+        };
+        for session in &removed {
+            self.finish(session, reason);
+        }
+        if !removed.is_empty() {
+            tracing::debug!(removed = removed.len(), reason, "removed stream sessions");
         }
+        removed.len()
+    }
+
+    /// Remove sessions whose client has been silent past their timeout,
+    /// sending each an RTCP BYE. Returns how many were removed.
+    pub fn remove_expired_sessions(&self, now: Instant) -> usize {
+        let expired: Vec<Arc<Session>> = {
+            let mut sessions = self.sessions.write();
+            let ids: Vec<String> = sessions
+                .values()
+                .filter(|s| s.is_expired(now))
+                .map(|s| s.id.clone())
+                .collect();
+            ids.iter().filter_map(|id| sessions.remove(id)).collect()
+        };
+        for session in &expired {
+            tracing::info!(
+                session_id = %session.id,
+                timeout_secs = session.timeout_secs,
+                "session timed out"
+            );
+            self.finish(session, Some(TIMEOUT_REASON));
+        }
+        expired.len()
+    }
+
+    /// Allocate a pair of (RTP, RTCP) server ports from the port pool.
+    /// The pair is returned to the pool when the session using it is removed.
+    pub fn allocate_server_ports(&self) -> Result<(u16, u16)> {
+        self.ports.allocate()
+    }
+
+    /// Return a pair obtained from [`Self::allocate_server_ports`] that
+    /// never made it onto a session.
+    pub fn release_server_ports(&self, rtp_port: u16) {
+        self.ports.release(rtp_port);
+    }
 
-        tracing::trace!(rtp_port = rtp, rtcp_port = rtp + 1, "allocated server ports");
-        Ok((rtp as u16, rtp as u16 + 1))
+    pub fn port_pool(&self) -> &PortPool {
+        &self.ports
+    }
+
+    pub fn get_sessions(&self) -> Vec<Arc<Session>> {
+        self.sessions.read().values().cloned().collect()
     }
 
     pub fn get_playing_sessions(&self) -> Vec<Arc<Session>> {
@@ -148,6 +460,51 @@ impl SessionManager {
             .cloned()
             .collect()
     }
+
+    /// Say goodbye to the viewer and return the session's ports to the pool.
+    fn finish(&self, session: &Session, reason: Option<&str>) {
+        rtcp::send_bye(session, &self.clocks, &self.rtcp, reason);
+        if let Some(transport) = session.get_transport()
+            && transport.interleaved.is_none()
+        {
+            self.ports.release(transport.server_rtp_port);
+        }
+        for transport in session.track_transports.lock().values() {
+            self.ports.release(transport.server_rtp_port);
+        }
+        if let Some(path) = session.mount() {
+            self.detach_from_mount(session, &path);
+        }
+    }
+
+    /// Stop relaying a mount to a departing viewer. When its publisher
+    /// leaves, the mount is removed and its viewers' sessions ended.
+    fn detach_from_mount(&self, session: &Session, path: &str) {
+        let Some(mount) = self.mounts.get(path) else {
+            return;
+        };
+        if mount.publisher().as_deref() != Some(session.id.as_str()) {
+            mount.remove_output(&session.id);
+            return;
+        }
+
+        tracing::info!(path, "publisher left, ending mount");
+        self.remove_mount(path);
+    }
+
+    /// Remove the mount at `path` and end its viewers' sessions with an
+    /// RTCP BYE. Returns `false` if there was no such mount.
+    pub fn remove_mount(&self, path: &str) -> bool {
+        let Some(mount) = self.mounts.remove(path) else {
+            return false;
+        };
+        let viewers = mount.viewer_ids();
+        tracing::debug!(path, viewers = viewers.len(), "ending mount viewers");
+        for id in viewers {
+            self.remove_session_with_reason(&id, Some(MOUNT_ENDED_REASON));
+        }
+        true
+    }
 }
 
 impl Default for SessionManager {
@@ -155,3 +512,67 @@ impl Default for SessionManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(timeout: Duration) -> SessionManager {
+        SessionManager::with_config(
+            ports::DEFAULT_SERVER_PORT_RANGE,
+            RtcpSettings::default(),
+            SessionSettings { timeout },
+        )
+    }
+
+    #[test]
+    fn sessions_advertise_configured_timeout() {
+        let sm = manager(Duration::from_secs(30));
+        let session = sm.create_session("rtsp://localhost/stream");
+        assert!(session.session_header_value().ends_with(";timeout=30"));
+    }
+
+    #[test]
+    fn removes_only_expired_sessions() {
+        let sm = manager(Duration::from_secs(10));
+        let idle = sm.create_session("rtsp://localhost/stream");
+        let active = sm.create_session("rtsp://localhost/stream");
+
+        let later = Instant::now() + Duration::from_secs(11);
+        assert!(idle.is_expired(later));
+        *active.last_activity.lock() = later;
+
+        assert_eq!(sm.remove_expired_sessions(later), 1);
+        assert!(sm.get_session(&idle.id).is_none());
+        assert!(sm.get_session(&active.id).is_some());
+    }
+
+    #[test]
+    fn ending_the_stream_leaves_mounted_sessions() {
+        let sm = manager(Duration::from_secs(10));
+        let viewer = sm.create_session("rtsp://localhost/stream");
+        let publisher = sm.create_session("rtsp://localhost/live/cam1");
+        publisher.set_mount("live/cam1");
+
+        assert_eq!(sm.remove_stream_sessions(Some("end of stream")), 1);
+        assert!(sm.get_session(&viewer.id).is_none());
+        assert!(sm.get_session(&publisher.id).is_some());
+    }
+
+    #[test]
+    fn session_ids_are_unique_and_64_bit() {
+        let ids: std::collections::HashSet<String> = (0..1000).map(|_| new_session_id()).collect();
+        assert_eq!(ids.len(), 1000);
+        assert!(ids.iter().all(|id| id.len() == 16));
+    }
+
+    #[test]
+    fn touch_postpones_expiry() {
+        let session = Session::with_timeout("rtsp://localhost/stream", 1);
+        let now = Instant::now();
+        assert!(!session.is_expired(now));
+        session.touch();
+        assert!(!session.is_expired(now + Duration::from_millis(500)));
+        assert!(session.is_expired(Instant::now() + Duration::from_secs(2)));
+    }
+}
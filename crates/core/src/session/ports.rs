@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::net::UdpSocket;
+use std::ops::RangeInclusive;
+
+use parking_lot::Mutex;
+
+use crate::error::{Result, RtspError};
+
+/// Default range for server-side RTP/RTCP ports.
+pub const DEFAULT_SERVER_PORT_RANGE: RangeInclusive<u16> = 5000..=65535;
+
+/// Pool of server-side (RTP, RTCP) UDP port pairs.
+///
+/// RTP always gets the even port and RTCP the following odd port
+/// (RFC 3550 §11). Pairs are handed out round-robin from the configured
+/// range, skipping pairs that are already leased or cannot be bound on
+/// the host, and are returned to the pool with [`PortPool::release`].
+#[derive(Debug)]
+pub struct PortPool {
+    range: RangeInclusive<u16>,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    /// RTP ports currently leased out.
+    in_use: HashSet<u16>,
+    /// Next RTP port to try.
+    cursor: u16,
+}
+
+impl PortPool {
+    pub fn new(range: RangeInclusive<u16>) -> Self {
+        let first = first_even(*range.start());
+        Self {
+            range,
+            state: Mutex::new(PoolState {
+                in_use: HashSet::new(),
+                cursor: first,
+            }),
+        }
+    }
+
+    pub fn range(&self) -> &RangeInclusive<u16> {
+        &self.range
+    }
+
+    /// Number of pairs the range can hold.
+    pub fn capacity(&self) -> usize {
+        let first = first_even(*self.range.start()) as u32;
+        let end = *self.range.end() as u32;
+        if first + 1 > end {
+            0
+        } else {
+            (end - first).div_ceil(2) as usize
+        }
+    }
+
+    /// Number of pairs currently leased out.
+    pub fn in_use(&self) -> usize {
+        self.state.lock().in_use.len()
+    }
+
+    /// Lease a free (RTP, RTCP) pair.
+    ///
+    /// Each candidate is probed by binding both ports, so pairs held by
+    /// other processes on the host are skipped.
+    pub fn allocate(&self) -> Result<(u16, u16)> {
+        let capacity = self.capacity();
+        let first = first_even(*self.range.start());
+        let mut state = self.state.lock();
+
+        for _ in 0..capacity {
+            let rtp = state.cursor;
+            state.cursor = match rtp.checked_add(2) {
+                Some(next) if next < *self.range.end() => next,
+                _ => first,
+            };
+
+            if state.in_use.contains(&rtp) {
+                continue;
+            }
+
+            if !is_pair_available(rtp) {
+                tracing::trace!(rtp_port = rtp, "server port pair busy on host, skipping");
+                continue;
+            }
+
+            state.in_use.insert(rtp);
+            tracing::trace!(
+                rtp_port = rtp,
+                rtcp_port = rtp + 1,
+                "allocated server ports"
+            );
+            return Ok((rtp, rtp + 1));
+        }
+
+        tracing::warn!(
+            range = ?self.range,
+            leased = state.in_use.len(),
+            "server port range exhausted"
+        );
+        Err(RtspError::PortRangeExhausted)
+    }
+
+    /// Return a pair to the pool, identified by its RTP port.
+    pub fn release(&self, rtp_port: u16) {
+        if self.state.lock().in_use.remove(&rtp_port) {
+            tracing::trace!(rtp_port, "released server ports");
+        }
+    }
+}
+
+impl Default for PortPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_SERVER_PORT_RANGE)
+    }
+}
+
+fn first_even(port: u16) -> u16 {
+    port.saturating_add(port & 1)
+}
+
+fn is_pair_available(rtp: u16) -> bool {
+    let Ok(_rtp_socket) = UdpSocket::bind(("0.0.0.0", rtp)) else {
+        return false;
+    };
+    UdpSocket::bind(("0.0.0.0", rtp + 1)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Find a small range whose ports are currently free on the host.
+    /// Each test passes its own `from` so parallel tests don't collide.
+    fn free_range(from: u16, pairs: u16) -> RangeInclusive<u16> {
+        let mut start = from;
+        loop {
+            let end = start + pairs * 2 - 1;
+            if (start..end).step_by(2).all(is_pair_available) {
+                return start..=end;
+            }
+            start += pairs * 2;
+        }
+    }
+
+    #[test]
+    fn rtp_port_is_even() {
+        let range = free_range(40000, 2);
+        let pool = PortPool::new(range.start() + 1..=*range.end());
+        let (rtp, rtcp) = pool.allocate().unwrap();
+        assert_eq!(rtp % 2, 0);
+        assert_eq!(rtcp, rtp + 1);
+    }
+
+    #[test]
+    fn exhausts_and_reuses_released_pairs() {
+        let pool = PortPool::new(free_range(41000, 2));
+        assert_eq!(pool.capacity(), 2);
+
+        let (a, _) = pool.allocate().unwrap();
+        let (b, _) = pool.allocate().unwrap();
+        assert_ne!(a, b);
+        assert!(matches!(
+            pool.allocate(),
+            Err(RtspError::PortRangeExhausted)
+        ));
+
+        pool.release(a);
+        assert_eq!(pool.in_use(), 1);
+        assert_eq!(pool.allocate().unwrap().0, a);
+    }
+
+    #[test]
+    fn skips_ports_bound_by_others() {
+        let range = free_range(42000, 2);
+        let _held = UdpSocket::bind(("0.0.0.0", *range.start())).unwrap();
+        let pool = PortPool::new(range.clone());
+        let (rtp, _) = pool.allocate().unwrap();
+        assert_eq!(rtp, range.start() + 2);
+    }
+
+    #[test]
+    fn empty_range_has_no_capacity() {
+        let pool = PortPool::new(6001..=6001);
+        assert_eq!(pool.capacity(), 0);
+        assert!(pool.allocate().is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::SessionManager;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often sessions are checked for expiry.
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Background loop removing sessions whose client went silent.
+///
+/// A client that vanished without TEARDOWN, or whose TCP connection stays
+/// half-open, would otherwise keep receiving RTP forever. Exits once
+/// `running` is cleared.
+pub fn reaper_loop(session_manager: SessionManager, running: Arc<AtomicBool>) {
+    let mut next_reap = Instant::now() + REAP_INTERVAL;
+
+    while running.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= next_reap {
+            let removed = session_manager.remove_expired_sessions(now);
+            if removed > 0 {
+                tracing::debug!(removed, "reaped expired sessions");
+            }
+            next_reap = now + REAP_INTERVAL;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    tracing::debug!("session reaper exited");
+}
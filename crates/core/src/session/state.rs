@@ -0,0 +1,204 @@
+use crate::protocol::Method;
+use crate::session::TransportMode;
+
+/// Server-side session state (RFC 2326 Appendix A.2).
+///
+/// A session is created by SETUP, so live sessions start in [`Ready`];
+/// [`Init`] is the state of a request that names no session.
+///
+/// [`Ready`]: SessionState::Ready
+/// [`Init`]: SessionState::Init
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    Init,
+    Ready,
+    Playing,
+    Recording,
+}
+
+impl SessionState {
+    /// State after `method` succeeds on a session in `mode`, or `None` if
+    /// the method is not valid in this state and must be answered with 455.
+    ///
+    /// Methods that don't affect the state (OPTIONS, DESCRIBE, ANNOUNCE,
+    /// GET_PARAMETER, ...) are valid everywhere. SETUP is refused while
+    /// playing or recording since transport parameters can't change
+    /// mid-stream (RFC 2326 §10.4). PLAY and RECORD each belong to their
+    /// own mode's state machine (RFC 2326 Appendix A.2).
+    pub fn next(self, method: &Method, mode: TransportMode) -> Option<SessionState> {
+        use SessionState::*;
+
+        match (self, method) {
+            (Init | Ready, Method::Setup) => Some(Ready),
+            (_, Method::Setup) => None,
+            (_, Method::Teardown) => Some(Init),
+            (Ready | Playing, Method::Play) if mode == TransportMode::Play => Some(Playing),
+            (_, Method::Play) => None,
+            (Ready | Recording, Method::Record) if mode == TransportMode::Record => Some(Recording),
+            (_, Method::Record) => None,
+            (Playing | Recording, Method::Pause) => Some(Ready),
+            (_, Method::Pause) => None,
+            _ => Some(self),
+        }
+    }
+
+    /// The subset of `methods` valid in this state, for an `Allow` header.
+    pub fn allowed_methods(self, methods: &[Method], mode: TransportMode) -> Vec<Method> {
+        methods
+            .iter()
+            .filter(|m| self.next(m, mode).is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionState::{self, *};
+    use crate::protocol::Method;
+    use crate::session::TransportMode;
+
+    const METHODS: &[Method] = &[
+        Method::Options,
+        Method::Describe,
+        Method::Announce,
+        Method::Setup,
+        Method::Play,
+        Method::Pause,
+        Method::Record,
+        Method::Teardown,
+        Method::GetParameter,
+        Method::SetParameter,
+    ];
+
+    /// Expected next state for each method in `METHODS` order, per state
+    /// and mode.
+    const TABLE: &[(SessionState, TransportMode, [Option<SessionState>; 10])] = &[
+        (
+            Init,
+            TransportMode::Play,
+            [
+                Some(Init),
+                Some(Init),
+                Some(Init),
+                Some(Ready),
+                None,
+                None,
+                None,
+                Some(Init),
+                Some(Init),
+                Some(Init),
+            ],
+        ),
+        (
+            Ready,
+            TransportMode::Play,
+            [
+                Some(Ready),
+                Some(Ready),
+                Some(Ready),
+                Some(Ready),
+                Some(Playing),
+                None,
+                None,
+                Some(Init),
+                Some(Ready),
+                Some(Ready),
+            ],
+        ),
+        (
+            Playing,
+            TransportMode::Play,
+            [
+                Some(Playing),
+                Some(Playing),
+                Some(Playing),
+                None,
+                Some(Playing),
+                Some(Ready),
+                None,
+                Some(Init),
+                Some(Playing),
+                Some(Playing),
+            ],
+        ),
+        (
+            Init,
+            TransportMode::Record,
+            [
+                Some(Init),
+                Some(Init),
+                Some(Init),
+                Some(Ready),
+                None,
+                None,
+                None,
+                Some(Init),
+                Some(Init),
+                Some(Init),
+            ],
+        ),
+        (
+            Ready,
+            TransportMode::Record,
+            [
+                Some(Ready),
+                Some(Ready),
+                Some(Ready),
+                Some(Ready),
+                None,
+                None,
+                Some(Recording),
+                Some(Init),
+                Some(Ready),
+                Some(Ready),
+            ],
+        ),
+        (
+            Recording,
+            TransportMode::Record,
+            [
+                Some(Recording),
+                Some(Recording),
+                Some(Recording),
+                None,
+                None,
+                Some(Ready),
+                Some(Recording),
+                Some(Init),
+                Some(Recording),
+                Some(Recording),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn every_method_in_every_state() {
+        for (state, mode, expected) in TABLE {
+            for (method, next) in METHODS.iter().zip(expected) {
+                assert_eq!(
+                    state.next(method, *mode),
+                    *next,
+                    "{method} in {state:?} ({mode:?})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn allow_lists_valid_methods() {
+        let supported = [
+            Method::Options,
+            Method::Setup,
+            Method::Play,
+            Method::Pause,
+            Method::Teardown,
+        ];
+        let allowed = |state: SessionState| {
+            Method::join(&state.allowed_methods(&supported, TransportMode::Play))
+        };
+        assert_eq!(allowed(Init), "OPTIONS, SETUP, TEARDOWN");
+        assert_eq!(allowed(Ready), "OPTIONS, SETUP, PLAY, TEARDOWN");
+        assert_eq!(allowed(Playing), "OPTIONS, PLAY, PAUSE, TEARDOWN");
+    }
+}
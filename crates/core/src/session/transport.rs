@@ -1,44 +1,293 @@
+use std::fmt;
 use std::net::SocketAddr;
 
+/// RTP profile requested in the Transport header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// RTP/AVP (RFC 3551).
+    #[default]
+    Avp,
+    /// RTP/AVPF, with RTCP feedback such as NACK (RFC 4585).
+    Avpf,
+    /// RTP/SAVP, secure RTP (RFC 3711).
+    Savp,
+    /// RTP/SAVPF, secure RTP with feedback (RFC 5124).
+    Savpf,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Avp => "RTP/AVP",
+            Self::Avpf => "RTP/AVPF",
+            Self::Savp => "RTP/SAVP",
+            Self::Savpf => "RTP/SAVPF",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "AVP" => Some(Self::Avp),
+            "AVPF" => Some(Self::Avpf),
+            "SAVP" => Some(Self::Savp),
+            "SAVPF" => Some(Self::Savpf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LowerTransport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Unicast,
+    Multicast,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportMode {
+    Play,
+    Record,
+}
+
 /// RTP/RTCP transport parameters negotiated during SETUP (RFC 2326 §12.39).
 #[derive(Debug, Clone)]
 pub struct Transport {
+    pub profile: Profile,
     pub client_rtp_port: u16,
     pub client_rtcp_port: u16,
     pub server_rtp_port: u16,
     pub server_rtcp_port: u16,
     pub client_addr: SocketAddr,
+    /// RTP and RTCP channels when the viewer receives media interleaved on
+    /// its RTSP connection; the ports are then unused.
+    pub interleaved: Option<(u8, u8)>,
 }
 
-/// Parsed client-side transport info from the RTSP Transport header.
-#[derive(Debug, Clone)]
+impl Transport {
+    /// Where RTCP for this session is sent: the client's address with its RTCP port.
+    pub fn client_rtcp_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.client_addr.ip(), self.client_rtcp_port)
+    }
+
+    /// The Transport header describing this transport, as sent in the SETUP response.
+    pub fn spec(&self) -> TransportSpec {
+        if let Some(channels) = self.interleaved {
+            return TransportSpec {
+                profile: self.profile,
+                lower_transport: LowerTransport::Tcp,
+                delivery: Some(Delivery::Unicast),
+                interleaved: Some(channels),
+                ..TransportSpec::default()
+            };
+        }
+        TransportSpec {
+            profile: self.profile,
+            delivery: Some(Delivery::Unicast),
+            client_port: Some((self.client_rtp_port, self.client_rtcp_port)),
+            server_port: Some((self.server_rtp_port, self.server_rtcp_port)),
+            ..TransportSpec::default()
+        }
+    }
+}
+
+/// One transport specification of a Transport header (RFC 2326 §12.39).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransportSpec {
+    pub profile: Profile,
+    pub lower_transport: LowerTransport,
+    /// `unicast` or `multicast`; `None` if the client left it unspecified.
+    pub delivery: Option<Delivery>,
+    pub destination: Option<String>,
+    pub source: Option<String>,
+    pub interleaved: Option<(u8, u8)>,
+    pub append: bool,
+    pub ttl: Option<u8>,
+    pub layers: Option<u32>,
+    pub port: Option<(u16, u16)>,
+    pub client_port: Option<(u16, u16)>,
+    pub server_port: Option<(u16, u16)>,
+    pub ssrc: Option<u32>,
+    pub mode: Option<TransportMode>,
+}
+
+impl TransportSpec {
+    /// Parse one transport specification, e.g.
+    /// `RTP/AVP;unicast;client_port=8000-8001`. Returns `None` for
+    /// malformed specs and transport protocols other than RTP.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut params = spec.split(';').map(str::trim);
+
+        let mut triplet = params.next()?.split('/');
+        if triplet.next()? != "RTP" {
+            return None;
+        }
+        let profile = Profile::from_name(triplet.next()?)?;
+        let lower_transport = match triplet.next() {
+            None | Some("UDP") => LowerTransport::Udp,
+            Some("TCP") => LowerTransport::Tcp,
+            Some(_) => return None,
+        };
+        if triplet.next().is_some() {
+            return None;
+        }
+
+        let mut parsed = TransportSpec {
+            profile,
+            lower_transport,
+            ..TransportSpec::default()
+        };
+
+        for param in params.filter(|p| !p.is_empty()) {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (param, None),
+            };
+            match (name, value) {
+                ("unicast", None) => parsed.delivery = Some(Delivery::Unicast),
+                ("multicast", None) => parsed.delivery = Some(Delivery::Multicast),
+                ("append", None) => parsed.append = true,
+                ("destination", value) => parsed.destination = value.map(str::to_string),
+                ("source", Some(value)) => parsed.source = Some(value.to_string()),
+                ("interleaved", Some(value)) => parsed.interleaved = Some(parse_pair(value)?),
+                ("ttl", Some(value)) => parsed.ttl = Some(value.parse().ok()?),
+                ("layers", Some(value)) => parsed.layers = Some(value.parse().ok()?),
+                ("port", Some(value)) => parsed.port = Some(parse_pair(value)?),
+                ("client_port", Some(value)) => parsed.client_port = Some(parse_pair(value)?),
+                ("server_port", Some(value)) => parsed.server_port = Some(parse_pair(value)?),
+                ("ssrc", Some(value)) => parsed.ssrc = Some(u32::from_str_radix(value, 16).ok()?),
+                ("mode", Some(value)) => {
+                    parsed.mode = match value.trim_matches('"').to_ascii_uppercase().as_str() {
+                        "PLAY" => Some(TransportMode::Play),
+                        "RECORD" => Some(TransportMode::Record),
+                        _ => return None,
+                    }
+                }
+                // Unknown parameters are ignored (RFC 2326 §12.39).
+                _ => {}
+            }
+        }
+
+        Some(parsed)
+    }
+}
+
+/// Parse `a-b`, or a single `a` meaning `a` and `a + 1`.
+fn parse_pair<T>(value: &str) -> Option<(T, T)>
+where
+    T: std::str::FromStr + Copy + TryFrom<u32>,
+    u32: From<T>,
+{
+    match value.split_once('-') {
+        Some((a, b)) => Some((a.trim().parse().ok()?, b.trim().parse().ok()?)),
+        None => {
+            let a: T = value.parse().ok()?;
+            let b = T::try_from(u32::from(a) + 1).ok()?;
+            Some((a, b))
+        }
+    }
+}
+
+impl fmt::Display for TransportSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.profile.as_str())?;
+        if self.lower_transport == LowerTransport::Tcp {
+            f.write_str("/TCP")?;
+        }
+        match self.delivery {
+            Some(Delivery::Unicast) => f.write_str(";unicast")?,
+            Some(Delivery::Multicast) => f.write_str(";multicast")?,
+            None => {}
+        }
+        if let Some(destination) = &self.destination {
+            write!(f, ";destination={destination}")?;
+        }
+        if let Some(source) = &self.source {
+            write!(f, ";source={source}")?;
+        }
+        if let Some((a, b)) = self.interleaved {
+            write!(f, ";interleaved={a}-{b}")?;
+        }
+        if self.append {
+            f.write_str(";append")?;
+        }
+        if let Some(ttl) = self.ttl {
+            write!(f, ";ttl={ttl}")?;
+        }
+        if let Some(layers) = self.layers {
+            write!(f, ";layers={layers}")?;
+        }
+        if let Some((a, b)) = self.port {
+            write!(f, ";port={a}-{b}")?;
+        }
+        if let Some((a, b)) = self.client_port {
+            write!(f, ";client_port={a}-{b}")?;
+        }
+        if let Some((a, b)) = self.server_port {
+            write!(f, ";server_port={a}-{b}")?;
+        }
+        if let Some(ssrc) = self.ssrc {
+            write!(f, ";ssrc={ssrc:08X}")?;
+        }
+        match self.mode {
+            Some(TransportMode::Play) => f.write_str(";mode=play")?,
+            Some(TransportMode::Record) => f.write_str(";mode=record")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// A Transport header: the client's transport alternatives in order of
+/// preference (RFC 2326 §12.39).
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransportHeader {
-    pub client_rtp_port: u16,
-    pub client_rtcp_port: u16,
+    /// Alternatives this crate could parse; specs for other transport
+    /// protocols are dropped.
+    pub alternatives: Vec<TransportSpec>,
 }
 
 impl TransportHeader {
-    /// Parse the RTSP Transport header value (RFC 2326 §12.39).
-    /// Extracts client_port RTP-RTCP pair from e.g. "RTP/AVP;unicast;client_port=8000-8001".
+    /// Parse the RTSP Transport header value (RFC 2326 §12.39), e.g.
+    /// `RTP/AVP/TCP;interleaved=0-1,RTP/AVP;unicast;client_port=8000-8001`.
+    /// Returns `None` if the header is empty.
     pub fn parse(header: &str) -> Option<Self> {
-        for part in header.split(';') {
-            let part = part.trim();
-            if part.starts_with("client_port=") {
-                let ports = &part["client_port=".len()..];
-                let port_parts: Vec<&str> = ports.split('-').collect();
-
-                if port_parts.len() == 2 {
-                    let rtp_port: u16 = port_parts[0].parse().ok()?;
-                    let rtcp_port: u16 = port_parts[1].parse().ok()?;
-
-                    return Some(TransportHeader {
-                        client_rtp_port: rtp_port,
-                        client_rtcp_port: rtcp_port,
-                    });
+        if header.trim().is_empty() {
+            return None;
+        }
+        let alternatives = header
+            .split(',')
+            .filter_map(|spec| {
+                let parsed = TransportSpec::parse(spec);
+                if parsed.is_none() {
+                    tracing::debug!(spec, "skipping unparseable transport");
                 }
+                parsed
+            })
+            .collect();
+        Some(Self { alternatives })
+    }
+
+    /// The first alternative accepted by `supported`.
+    pub fn select(&self, supported: impl Fn(&TransportSpec) -> bool) -> Option<&TransportSpec> {
+        self.alternatives.iter().find(|spec| supported(spec))
+    }
+}
+
+impl fmt::Display for TransportHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, spec) in self.alternatives.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
             }
+            write!(f, "{spec}")?;
         }
-        None
+        Ok(())
     }
 }
 
@@ -46,15 +295,102 @@ impl TransportHeader {
 mod tests {
     use super::*;
 
+    fn single(header: &str) -> TransportSpec {
+        TransportHeader::parse(header)
+            .unwrap()
+            .alternatives
+            .remove(0)
+    }
+
     #[test]
     fn parse_valid_transport() {
-        let th = TransportHeader::parse("RTP/AVP;unicast;client_port=5000-5001").unwrap();
-        assert_eq!(th.client_rtp_port, 5000);
-        assert_eq!(th.client_rtcp_port, 5001);
+        let spec = single("RTP/AVP;unicast;client_port=5000-5001");
+        assert_eq!(spec.client_port, Some((5000, 5001)));
+        assert_eq!(spec.profile, Profile::Avp);
+        assert_eq!(spec.lower_transport, LowerTransport::Udp);
+        assert_eq!(spec.delivery, Some(Delivery::Unicast));
+    }
+
+    #[test]
+    fn parse_avpf_profile() {
+        let spec = single("RTP/AVPF;unicast;client_port=5000-5001");
+        assert_eq!(spec.profile, Profile::Avpf);
     }
 
     #[test]
     fn parse_no_client_port() {
-        assert!(TransportHeader::parse("RTP/AVP;unicast").is_none());
+        assert_eq!(single("RTP/AVP;unicast").client_port, None);
+    }
+
+    #[test]
+    fn parse_all_parameters() {
+        let spec = single(
+            "RTP/AVP/TCP;multicast;destination=224.2.0.1;source=10.0.0.1;interleaved=2-3;\
+             append;ttl=16;layers=1;port=3456;server_port=6000-6001;ssrc=0A1B2C3D;mode=\"PLAY\"",
+        );
+        assert_eq!(spec.lower_transport, LowerTransport::Tcp);
+        assert_eq!(spec.delivery, Some(Delivery::Multicast));
+        assert_eq!(spec.destination.as_deref(), Some("224.2.0.1"));
+        assert_eq!(spec.source.as_deref(), Some("10.0.0.1"));
+        assert_eq!(spec.interleaved, Some((2, 3)));
+        assert!(spec.append);
+        assert_eq!(spec.ttl, Some(16));
+        assert_eq!(spec.layers, Some(1));
+        assert_eq!(spec.port, Some((3456, 3457)));
+        assert_eq!(spec.server_port, Some((6000, 6001)));
+        assert_eq!(spec.ssrc, Some(0x0A1B2C3D));
+        assert_eq!(spec.mode, Some(TransportMode::Play));
+    }
+
+    #[test]
+    fn serializes_round_trip() {
+        let text = "RTP/AVP/TCP;unicast;interleaved=0-1;ssrc=0A1B2C3D;mode=record";
+        let spec = single(text);
+        assert_eq!(spec.to_string(), text);
+        assert_eq!(single(&spec.to_string()), spec);
+    }
+
+    #[test]
+    fn alternatives_in_preference_order() {
+        let header = TransportHeader::parse(
+            "x-real-rdt/udp;client_port=6970,RTP/AVP/TCP;interleaved=0-1,RTP/AVP;unicast;client_port=8000-8001",
+        )
+        .unwrap();
+        assert_eq!(header.alternatives.len(), 2);
+
+        let udp = header
+            .select(|spec| spec.lower_transport == LowerTransport::Udp)
+            .unwrap();
+        assert_eq!(udp.client_port, Some((8000, 8001)));
+        assert!(
+            header
+                .select(|spec| spec.profile == Profile::Savp)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn transport_spec_describes_session() {
+        let transport = Transport {
+            profile: Profile::Avp,
+            client_rtp_port: 8000,
+            client_rtcp_port: 8001,
+            server_rtp_port: 6000,
+            server_rtcp_port: 6001,
+            client_addr: "127.0.0.1:8000".parse().unwrap(),
+            interleaved: None,
+        };
+        assert_eq!(
+            transport.spec().to_string(),
+            "RTP/AVP;unicast;client_port=8000-8001;server_port=6000-6001"
+        );
+        let interleaved = Transport {
+            interleaved: Some((2, 3)),
+            ..transport
+        };
+        assert_eq!(
+            interleaved.spec().to_string(),
+            "RTP/AVP/TCP;unicast;interleaved=2-3"
+        );
     }
 }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::media::jitter::JitterBuffer;
+use crate::mount::{Mount, RtpSource};
+use crate::session::{Session, SessionManager};
+use crate::transport::SessionSockets;
+
+/// How long a receive blocks before checking whether the mount closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Largest RTP datagram accepted from a publisher.
+const MAX_RTP_SIZE: usize = 65536;
+
+/// How often a UDP source releases packets whose gap wasn't filled in time.
+const JITTER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Reorder buffers of SSRCs silent this long are dropped.
+const IDLE_SSRC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Receive a publisher's RTP for one track of `mount` on `sockets` and
+/// relay it to the mount's viewers.
+///
+/// Runs on its own thread until the mount is closed; the server ports go
+/// back to the pool when the publishing session ends. Only datagrams from
+/// `publisher_ip` are accepted; each one keeps the publishing session
+/// alive, since encoders rarely send keepalives while recording. The
+/// publisher's RTCP is read and discarded.
+pub fn spawn_udp_receiver(
+    sockets: SessionSockets,
+    publisher_ip: IpAddr,
+    mount: Arc<Mount>,
+    track: usize,
+    session: Arc<Session>,
+) {
+    thread::spawn(move || {
+        if let Err(e) = sockets.rtp.set_read_timeout(Some(POLL_INTERVAL)) {
+            tracing::warn!(path = %mount.path(), error = %e, "failed to configure RTP receiver");
+        }
+
+        let mut buf = vec![0u8; MAX_RTP_SIZE];
+        while !mount.is_closed() {
+            while let Ok((len, from)) = sockets.rtcp.recv_from(&mut buf) {
+                tracing::trace!(path = %mount.path(), track, len, %from, "discarding publisher RTCP");
+            }
+            match sockets.rtp.recv_from(&mut buf) {
+                Ok((len, from)) if from.ip() == publisher_ip => {
+                    session.touch();
+                    mount.forward(track, &buf[..len]);
+                }
+                Ok((_, from)) => {
+                    tracing::debug!(path = %mount.path(), %from, "dropping RTP from unexpected peer");
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => {
+                    tracing::warn!(path = %mount.path(), track, error = %e, "RTP receive failed");
+                    break;
+                }
+            }
+        }
+        tracing::debug!(path = %mount.path(), track, "RTP receiver exited");
+    });
+}
+
+/// A mount fed with plain RTP arriving on a local UDP port, as sent by
+/// `ffmpeg -f rtp` or an IP encoder.
+///
+/// Packets from any sender are reordered per SSRC in a small jitter buffer,
+/// then relayed through an [`RtpSource`]: they must carry a payload type of
+/// the mount's SDP, which also routes them to their track. A multicast bind
+/// address joins the group. Dropping the handle removes the mount.
+pub struct UdpSource {
+    mount: Arc<Mount>,
+    local_addr: SocketAddr,
+    session_manager: SessionManager,
+}
+
+impl UdpSource {
+    /// Listen on `bind` and feed what arrives to `source`, releasing packets
+    /// after a gap once they've waited `latency`.
+    pub(crate) fn spawn(
+        bind: SocketAddr,
+        source: RtpSource,
+        latency: Duration,
+        session_manager: SessionManager,
+    ) -> Result<Self> {
+        let socket = bind_socket(bind)?;
+        socket.set_read_timeout(Some(JITTER_POLL_INTERVAL))?;
+        let local_addr = socket.local_addr()?;
+        let mount = source.mount().clone();
+        tracing::info!(path = %mount.path(), %local_addr, "listening for RTP");
+
+        thread::spawn(move || receive_udp_source(socket, source, latency));
+        Ok(Self {
+            mount,
+            local_addr,
+            session_manager,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        self.mount.path()
+    }
+
+    /// Address the source listens on, with the port picked if `bind` gave 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl std::fmt::Debug for UdpSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpSource")
+            .field("path", &self.mount.path())
+            .field("local_addr", &self.local_addr)
+            .finish()
+    }
+}
+
+impl Drop for UdpSource {
+    fn drop(&mut self) {
+        if !self.mount.is_closed() {
+            self.session_manager.remove_mount(self.mount.path());
+        }
+    }
+}
+
+/// Bind `addr`, joining its group on all interfaces if it's an IPv4
+/// multicast address.
+fn bind_socket(addr: SocketAddr) -> Result<UdpSocket> {
+    match addr {
+        SocketAddr::V4(v4) if v4.ip().is_multicast() => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, v4.port()))?;
+            socket.join_multicast_v4(v4.ip(), &Ipv4Addr::UNSPECIFIED)?;
+            Ok(socket)
+        }
+        _ => Ok(UdpSocket::bind(addr)?),
+    }
+}
+
+fn receive_udp_source(socket: UdpSocket, source: RtpSource, latency: Duration) {
+    let mount = source.mount().clone();
+    let mut buffers: HashMap<u32, (JitterBuffer, Instant)> = HashMap::new();
+    let mut buf = vec![0u8; MAX_RTP_SIZE];
+
+    while !mount.is_closed() {
+        let now = Instant::now();
+        let mut ready = Vec::new();
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) if len >= 12 => {
+                let ssrc = u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]);
+                let (buffer, seen) = buffers
+                    .entry(ssrc)
+                    .or_insert_with(|| {
+                        tracing::debug!(path = %mount.path(), %from, ssrc = format_args!("{:#010X}", ssrc), "new RTP sender");
+                        (JitterBuffer::new(latency), now)
+                    });
+                *seen = now;
+                ready.extend(buffer.push(buf[..len].to_vec(), now));
+            }
+            Ok((len, from)) => {
+                tracing::debug!(path = %mount.path(), %from, len, "dropping non-RTP datagram");
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => {
+                tracing::warn!(path = %mount.path(), error = %e, "RTP receive failed");
+                break;
+            }
+        }
+
+        buffers.retain(|_, (buffer, seen)| {
+            ready.extend(buffer.pop_ready(now));
+            !buffer.is_empty() || now.duration_since(*seen) < IDLE_SSRC_TIMEOUT
+        });
+        for packet in ready {
+            if let Err(e) = source.push(&packet) {
+                tracing::debug!(path = %mount.path(), error = %e, "dropping RTP packet");
+            }
+        }
+    }
+    tracing::debug!(path = %mount.path(), "UDP source exited");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn udp_source_serves_mount_until_dropped() {
+        let manager = SessionManager::new();
+        let sdp = "m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n";
+        let source = RtpSource::new("gateway", sdp, manager.clone()).unwrap();
+        let udp = UdpSource::spawn(
+            "127.0.0.1:0".parse().unwrap(),
+            source,
+            Duration::from_millis(20),
+            manager.clone(),
+        )
+        .unwrap();
+        assert_ne!(udp.local_addr().port(), 0);
+
+        let mount = manager.mounts().get("gateway").unwrap();
+        drop(udp);
+        assert!(mount.is_closed());
+        assert!(manager.mounts().get("gateway").is_none());
+    }
+}
@@ -1,4 +1,47 @@
+pub mod ingest;
+pub mod relay;
+pub mod rtcp;
 pub mod tcp;
 pub mod udp;
 
-pub use udp::UdpTransport;
+pub use tcp::ConnectionWriter;
+pub use udp::{SessionSockets, UdpTransport};
+
+use crate::error::{Result, RtspError};
+use crate::session::Session;
+
+/// Which of a session's two streams a packet belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Rtp,
+    Rtcp,
+}
+
+/// Send `payload` to a session's client: interleaved on its RTSP
+/// connection if it set up over TCP, else from the session's UDP sockets.
+pub fn deliver(session: &Session, channel: Channel, payload: &[u8]) -> Result<usize> {
+    let Some(transport) = session.get_transport() else {
+        return Err(RtspError::TransportNotConfigured(session.id.clone()));
+    };
+    if let Some((rtp_channel, rtcp_channel)) = transport.interleaved {
+        let Some(writer) = session.writer() else {
+            return Err(RtspError::TransportNotConfigured(session.id.clone()));
+        };
+        let channel = match channel {
+            Channel::Rtp => rtp_channel,
+            Channel::Rtcp => rtcp_channel,
+        };
+        writer.send_frame(channel, payload)?;
+        return Ok(payload.len());
+    }
+    let Some(sockets) = session.sockets() else {
+        return Err(RtspError::TransportNotConfigured(session.id.clone()));
+    };
+    let sent = match channel {
+        Channel::Rtp => sockets.rtp.send_to(payload, transport.client_addr)?,
+        Channel::Rtcp => sockets
+            .rtcp
+            .send_to(payload, transport.client_rtcp_addr())?,
+    };
+    Ok(sent)
+}
@@ -0,0 +1,612 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::client::{self, ClientConfig, ClientTransport, RtspClient};
+use crate::error::{Result, RtspError};
+use crate::mount::{MountSource, RtpSource};
+use crate::session::SessionManager;
+
+/// How long the relay thread waits between checks of its stop flag.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// When the relay pulls its upstream stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelayMode {
+    /// Only while viewers are set up on the mount, so idle mounts don't
+    /// hold one of the camera's sessions.
+    #[default]
+    OnDemand,
+    /// All the time, so viewers get media without waiting for upstream.
+    AlwaysOn,
+}
+
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    pub mode: RelayMode,
+    /// How the relay receives RTP from upstream.
+    pub transport: ClientTransport,
+    /// Wait before the first reconnect attempt; doubles on every failure.
+    pub min_backoff: Duration,
+    /// Longest wait between reconnect attempts.
+    pub max_backoff: Duration,
+    /// Upstream silent this long is treated as lost and reconnected.
+    pub stall_timeout: Duration,
+    /// In [`RelayMode::OnDemand`], how long upstream is kept after the last
+    /// viewer left, so a quick reconnect doesn't restart the pull.
+    pub idle_timeout: Duration,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            mode: RelayMode::default(),
+            transport: ClientTransport::default(),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            stall_timeout: Duration::from_secs(10),
+            idle_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A mount restreaming an upstream RTSP URL to any number of viewers over
+/// one upstream session.
+///
+/// The upstream session description is served as is, with control URLs
+/// made relative to the mount. SSRC, sequence numbers and timestamps stay
+/// continuous for viewers when upstream reconnects. The mount appears once
+/// upstream answered DESCRIBE the first time; until then, and while
+/// upstream is unreachable, the relay retries with exponential backoff.
+/// Dropping the handle stops the relay and removes the mount.
+pub struct Relay {
+    path: String,
+    url: String,
+    stop: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    session_manager: SessionManager,
+}
+
+impl Relay {
+    pub(crate) fn spawn(
+        path: &str,
+        url: &str,
+        config: RelayConfig,
+        session_manager: SessionManager,
+    ) -> Result<Self> {
+        client::authority(url)?;
+        let path = path.trim_matches('/').to_string();
+        if session_manager.mounts().get(&path).is_some() {
+            return Err(RtspError::MountInUse(path));
+        }
+
+        let relay = Self {
+            path,
+            url: url.to_string(),
+            stop: Arc::new(AtomicBool::new(false)),
+            connected: Arc::new(AtomicBool::new(false)),
+            session_manager,
+        };
+        let worker = RelayWorker {
+            path: relay.path.clone(),
+            url: relay.url.clone(),
+            config,
+            stop: relay.stop.clone(),
+            connected: relay.connected.clone(),
+            session_manager: relay.session_manager.clone(),
+            source: None,
+        };
+        tracing::info!(path = %relay.path, url = %relay.url, mode = ?worker.config.mode, "relay started");
+        thread::spawn(move || worker.run());
+        Ok(relay)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The upstream URL.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Whether upstream is currently playing to the relay.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Debug for Relay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Relay")
+            .field("path", &self.path)
+            .field("url", &self.url)
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let ours = self
+            .session_manager
+            .mounts()
+            .get(&self.path)
+            .is_some_and(|m| {
+                m.source()
+                    == &MountSource::Relay {
+                        url: self.url.clone(),
+                    }
+            });
+        if ours {
+            self.session_manager.remove_mount(&self.path);
+        }
+    }
+}
+
+struct RelayWorker {
+    path: String,
+    url: String,
+    config: RelayConfig,
+    stop: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    session_manager: SessionManager,
+    /// The mount, once upstream described it.
+    source: Option<RtpSource>,
+}
+
+impl RelayWorker {
+    fn run(mut self) {
+        let mut backoff = self.config.min_backoff;
+        while !self.stopped() {
+            if !self.wants_upstream() {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            match self.pull() {
+                Ok(received) => {
+                    if received {
+                        backoff = self.config.min_backoff;
+                    }
+                    if self.stopped() || !self.wants_upstream() {
+                        continue;
+                    }
+                    tracing::info!(path = %self.path, "upstream ended the stream, reconnecting");
+                }
+                Err(e) => {
+                    tracing::warn!(path = %self.path, url = %self.url, error = %e, retry_in = ?backoff, "relay upstream failed");
+                }
+            }
+            self.sleep(backoff);
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+        self.connected.store(false, Ordering::Relaxed);
+        tracing::debug!(path = %self.path, "relay exited");
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+            || self.source.as_ref().is_some_and(|s| s.mount().is_closed())
+    }
+
+    /// Whether upstream should be pulled now: always before the mount
+    /// exists, since DESCRIBE creates it.
+    fn wants_upstream(&self) -> bool {
+        match (&self.source, self.config.mode) {
+            (None, _) | (_, RelayMode::AlwaysOn) => true,
+            (Some(source), RelayMode::OnDemand) => !source.mount().viewer_ids().is_empty(),
+        }
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while !self.stopped() && Instant::now() < until {
+            thread::sleep(POLL_INTERVAL.min(until.saturating_duration_since(Instant::now())));
+        }
+    }
+
+    /// One upstream connection: describe, then play and relay until
+    /// upstream ends, fails or, on demand, is no longer needed. Returns
+    /// whether any media was relayed.
+    fn pull(&mut self) -> Result<bool> {
+        let config = ClientConfig {
+            transport: self.config.transport,
+            ..ClientConfig::default()
+        };
+        let mut client = RtspClient::connect_with_config(&self.url, config)?;
+        let _ = client.options();
+        client.describe()?;
+        let sdp = client.sdp().unwrap_or_default().to_string();
+
+        if self.source.is_none() {
+            let source = RtpSource::with_source(
+                &self.path,
+                &relative_controls(&sdp),
+                MountSource::Relay {
+                    url: self.url.clone(),
+                },
+                self.session_manager.clone(),
+            )?;
+            self.source = Some(source);
+            if self.stopped() || !self.wants_upstream() {
+                // On demand, describing was all that was needed for now.
+                return Ok(false);
+            }
+        }
+        let Some(source) = &self.source else {
+            return Ok(false);
+        };
+
+        let tracks = client.description().map_or(0, |d| d.media.len());
+        if tracks != source.mount().tracks().len() {
+            tracing::warn!(path = %self.path, tracks, "upstream track count changed, relaying the first ones");
+        }
+        for track in 0..tracks.min(source.mount().tracks().len()) {
+            client.setup(track)?;
+        }
+        client.play()?;
+        self.connected.store(true, Ordering::Relaxed);
+        tracing::info!(path = %self.path, url = %self.url, "relaying upstream");
+
+        let result = self.relay(&mut client);
+        self.connected.store(false, Ordering::Relaxed);
+        let _ = client.teardown();
+        result
+    }
+
+    fn relay(&self, client: &mut RtspClient) -> Result<bool> {
+        let Some(source) = &self.source else {
+            return Ok(false);
+        };
+        let mut received = false;
+        let mut last_packet = Instant::now();
+        let mut last_check = Instant::now();
+        let mut idle_since: Option<Instant> = None;
+        while !self.stopped() {
+            match client.poll_packet(POLL_INTERVAL)? {
+                Some(packet) => {
+                    received = true;
+                    last_packet = Instant::now();
+                    if let Err(e) = source.push_to(packet.track, &packet.data) {
+                        tracing::debug!(path = %self.path, error = %e, "dropping upstream packet");
+                    }
+                }
+                None if client.is_ended() => break,
+                None if last_packet.elapsed() >= self.config.stall_timeout => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "upstream stalled",
+                    )
+                    .into());
+                }
+                None => {}
+            }
+
+            if self.config.mode == RelayMode::OnDemand && last_check.elapsed() >= POLL_INTERVAL {
+                last_check = Instant::now();
+                if !source.mount().viewer_ids().is_empty() {
+                    idle_since = None;
+                } else if idle_since.get_or_insert(last_check).elapsed() >= self.config.idle_timeout
+                {
+                    tracing::info!(path = %self.path, "no viewers left, releasing upstream");
+                    break;
+                }
+            }
+        }
+        Ok(received)
+    }
+}
+
+/// `sdp` with its control URLs replaced by `track1`, `track2`, ..., so
+/// viewers set up tracks on this server rather than upstream.
+fn relative_controls(sdp: &str) -> String {
+    let without: String = sdp
+        .lines()
+        .filter(|l| !l.trim().starts_with("a=control:"))
+        .map(|l| format!("{}\r\n", l.trim_end()))
+        .collect();
+    crate::protocol::sdp::complete_fragment(&without)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::method::Method;
+    use crate::protocol::reader::{Message, MessageLimits, MessageReader};
+    use crate::protocol::response::RtspResponse;
+    use crate::session::transport::Profile;
+    use crate::session::{Session, SessionState, Transport};
+    use crate::transport::ConnectionWriter;
+    use parking_lot::Mutex;
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpListener, TcpStream};
+
+    const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=cam\r\nt=0 0\r\n\
+        m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:track0\r\n";
+
+    /// What the fake camera does with one of the relay's connections.
+    #[derive(Debug, Clone, Copy)]
+    enum Upstream {
+        /// Hang up as soon as the connection is accepted.
+        Refuse,
+        /// Answer requests and, on PLAY, send `count` packets from `ssrc`
+        /// starting at `seq`; then hang up, or keep answering.
+        Stream {
+            ssrc: u32,
+            seq: u16,
+            count: u16,
+            hang_up: bool,
+        },
+    }
+
+    /// Requests and connections the camera saw: connection index, method
+    /// (`None` for the connection itself) and when.
+    type Log = Arc<Mutex<Vec<(usize, Option<Method>, Instant)>>>;
+
+    /// A fake camera that handles its nth connection as `script[n]`
+    /// says, refusing any beyond the script.
+    fn camera(script: Vec<Upstream>) -> (String, Log) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("rtsp://{}/live", listener.local_addr().unwrap());
+        let log = Log::default();
+        let accepted = log.clone();
+        thread::spawn(move || {
+            for (n, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { return };
+                accepted.lock().push((n, None, Instant::now()));
+                let behaviour = script.get(n).copied().unwrap_or(Upstream::Refuse);
+                let log = accepted.clone();
+                thread::spawn(move || answer(n, stream, behaviour, &log));
+            }
+        });
+        (url, log)
+    }
+
+    fn answer(n: usize, mut stream: TcpStream, behaviour: Upstream, log: &Log) {
+        let Upstream::Stream {
+            ssrc,
+            seq,
+            count,
+            hang_up,
+        } = behaviour
+        else {
+            return;
+        };
+        let mut reader = MessageReader::new(MessageLimits::default());
+        let mut chunk = [0u8; 4096];
+        loop {
+            while let Ok(Some(Message::Request(request))) = reader.next_message() {
+                log.lock()
+                    .push((n, Some(request.method.clone()), Instant::now()));
+                let response = match request.method {
+                    Method::Describe => RtspResponse::ok()
+                        .add_header("Content-Type", "application/sdp")
+                        .with_body(SDP.to_string()),
+                    Method::Setup => RtspResponse::ok()
+                        .add_header("Session", "cam;timeout=60")
+                        .add_header("Transport", request.get_header("Transport").unwrap()),
+                    _ => RtspResponse::ok(),
+                };
+                let response = response.cseq(request.cseq().unwrap());
+                if stream.write_all(response.serialize().as_bytes()).is_err() {
+                    return;
+                }
+                if request.method == Method::Play {
+                    for i in 0..count {
+                        let packet = rtp(ssrc, seq.wrapping_add(i), ssrc ^ (u32::from(i) * 3000));
+                        let mut frame = vec![b'$', 0];
+                        frame.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+                        frame.extend_from_slice(&packet);
+                        let _ = stream.write_all(&frame);
+                    }
+                    if hang_up {
+                        thread::sleep(Duration::from_millis(100));
+                        let _ = stream.shutdown(Shutdown::Both);
+                        return;
+                    }
+                }
+            }
+            match stream.read(&mut chunk) {
+                Ok(0) | Err(_) => return,
+                Ok(len) => reader.feed(&chunk[..len]),
+            }
+        }
+    }
+
+    fn rtp(ssrc: u32, seq: u16, timestamp: u32) -> Vec<u8> {
+        let mut packet = vec![0x80, 96];
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&ssrc.to_be_bytes());
+        packet.push(0x65);
+        packet
+    }
+
+    fn config(idle_timeout: Duration) -> RelayConfig {
+        RelayConfig {
+            mode: RelayMode::OnDemand,
+            transport: ClientTransport::Tcp,
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            stall_timeout: Duration::from_secs(2),
+            idle_timeout,
+        }
+    }
+
+    fn wait_until(done: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if done() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    /// A viewer playing the mount at `path` over interleaved TCP, and the
+    /// client end of its connection.
+    fn viewer(session_manager: &SessionManager, path: &str) -> (Arc<Session>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let (server, peer) = listener.accept().unwrap();
+
+        let session = session_manager.create_session(&format!("rtsp://localhost/{path}"));
+        session.set_transport(Transport {
+            profile: Profile::Avp,
+            client_rtp_port: 0,
+            client_rtcp_port: 0,
+            server_rtp_port: 0,
+            server_rtcp_port: 0,
+            client_addr: peer,
+            interleaved: Some((0, 1)),
+        });
+        session.set_writer(Arc::new(ConnectionWriter::new(server).unwrap()));
+        session.set_state(SessionState::Playing);
+        let mount = session_manager.mounts().get(path).unwrap();
+        mount.add_output(session.clone(), 0);
+        (session, client)
+    }
+
+    /// SSRC, sequence number and timestamp of the next RTP packet the
+    /// viewer receives.
+    fn receive(client: &mut TcpStream) -> (u32, u16, u32) {
+        loop {
+            let mut header = [0u8; 4];
+            client.read_exact(&mut header).unwrap();
+            let mut packet = vec![0u8; usize::from(u16::from_be_bytes([header[2], header[3]]))];
+            client.read_exact(&mut packet).unwrap();
+            if header[1] == 0 {
+                return (
+                    u32::from_be_bytes(packet[8..12].try_into().unwrap()),
+                    u16::from_be_bytes([packet[2], packet[3]]),
+                    u32::from_be_bytes(packet[4..8].try_into().unwrap()),
+                );
+            }
+        }
+    }
+
+    /// When the camera accepted connection `n`.
+    fn connected_at(log: &Log, n: usize) -> Instant {
+        log.lock()
+            .iter()
+            .find(|(i, method, _)| *i == n && method.is_none())
+            .map(|(_, _, at)| *at)
+            .unwrap()
+    }
+
+    fn requested(log: &Log, method: Method) -> Option<(usize, Instant)> {
+        log.lock()
+            .iter()
+            .find(|(_, m, _)| m.as_ref() == Some(&method))
+            .map(|(n, _, at)| (*n, *at))
+    }
+
+    #[test]
+    fn starts_on_demand_and_releases_upstream_when_idle() {
+        let session_manager = SessionManager::new();
+        let stream = Upstream::Stream {
+            ssrc: 0xA,
+            seq: 100,
+            count: 3,
+            hang_up: false,
+        };
+        let (url, log) = camera(vec![stream, stream]);
+        let idle_timeout = Duration::from_millis(300);
+        let relay =
+            Relay::spawn("cam", &url, config(idle_timeout), session_manager.clone()).unwrap();
+
+        // Describing upstream creates the mount, but nothing plays until a
+        // viewer arrives.
+        assert!(wait_until(|| session_manager.mounts().get("cam").is_some()));
+        thread::sleep(Duration::from_millis(300));
+        assert!(requested(&log, Method::Setup).is_none());
+        assert!(!relay.is_connected());
+
+        let (session, mut client) = viewer(&session_manager, "cam");
+        assert!(wait_until(|| relay.is_connected()));
+        assert_eq!(requested(&log, Method::Play).map(|(n, _)| n), Some(1));
+        for _ in 0..3 {
+            receive(&mut client);
+        }
+
+        let mount = session_manager.mounts().get("cam").unwrap();
+        mount.remove_output(&session.id);
+        let left = Instant::now();
+        assert!(wait_until(|| requested(&log, Method::Teardown).is_some()));
+        let (n, torn_down) = requested(&log, Method::Teardown).unwrap();
+        assert_eq!(n, 1);
+        assert!(torn_down.duration_since(left) >= idle_timeout);
+        assert!(wait_until(|| !relay.is_connected()));
+    }
+
+    #[test]
+    fn reconnects_with_backoff_and_keeps_numbering_continuous() {
+        let session_manager = SessionManager::new();
+        let script = vec![
+            // Described, then released until a viewer arrives.
+            Upstream::Stream {
+                ssrc: 0,
+                seq: 0,
+                count: 0,
+                hang_up: false,
+            },
+            Upstream::Stream {
+                ssrc: 0xA,
+                seq: 100,
+                count: 5,
+                hang_up: true,
+            },
+            Upstream::Refuse,
+            Upstream::Refuse,
+            Upstream::Stream {
+                ssrc: 0xB,
+                seq: 40000,
+                count: 5,
+                hang_up: false,
+            },
+        ];
+        let (url, log) = camera(script);
+        let config = config(Duration::from_secs(10));
+        let _relay = Relay::spawn("cam", &url, config, session_manager.clone()).unwrap();
+        assert!(wait_until(|| session_manager.mounts().get("cam").is_some()));
+
+        let (_session, mut client) = viewer(&session_manager, "cam");
+        let received: Vec<_> = (0..10).map(|_| receive(&mut client)).collect();
+        let (ssrc, _, _) = received[0];
+        for pair in received.windows(2) {
+            let ((_, seq, timestamp), (next_ssrc, next_seq, next_timestamp)) = (pair[0], pair[1]);
+            assert_eq!(next_ssrc, ssrc);
+            assert_eq!(next_seq, seq.wrapping_add(1));
+            assert!(next_timestamp.wrapping_sub(timestamp) as i32 > 0);
+        }
+
+        // Each failed attempt doubles the wait before the next one.
+        let first = connected_at(&log, 3).duration_since(connected_at(&log, 2));
+        let second = connected_at(&log, 4).duration_since(connected_at(&log, 3));
+        assert!(first >= Duration::from_millis(180), "{first:?}");
+        assert!(second >= Duration::from_millis(360), "{second:?}");
+    }
+
+    #[test]
+    fn controls_become_relative() {
+        let sdp = "v=0\r\no=- 0 0 IN IP4 10.0.0.5\r\ns=cam\r\nt=0 0\r\na=control:rtsp://10.0.0.5/live\r\n\
+                   m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:rtsp://10.0.0.5/live/trackID=1\r\n\
+                   m=audio 0 RTP/AVP 0\r\na=control:trackID=2\r\n";
+        let served = relative_controls(sdp);
+        assert!(!served.contains("10.0.0.5/live"));
+        assert_eq!(
+            served
+                .lines()
+                .filter(|l| l.starts_with("a=control:"))
+                .collect::<Vec<_>>(),
+            ["a=control:track1", "a=control:track2"]
+        );
+        assert!(served.contains("a=rtpmap:96 H264/90000"));
+    }
+}
@@ -0,0 +1,337 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::media::keyframe::{DEFAULT_KEYFRAME_REQUEST_INTERVAL, KeyframeReason};
+use crate::rtcp::StreamClocks;
+use crate::rtcp::interval::RTCP_MIN_INTERVAL;
+use crate::rtcp::packet::RtcpPacket;
+use crate::rtcp::retransmit::{DEFAULT_HISTORY_SIZE, Retransmission, build_rtx_packet};
+use crate::rtcp::session::ReceiverStats;
+use crate::session::transport::Profile;
+use crate::session::{Session, SessionManager};
+use crate::transport::{Channel, SessionSockets, deliver};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Largest RTCP datagram accepted from a viewer.
+const MAX_RTCP_SIZE: usize = 1500;
+
+/// Viewer quality thresholds above which a warning is logged.
+const DEGRADED_LOSS_RATIO: f64 = 0.05;
+const DEGRADED_JITTER: Duration = Duration::from_millis(50);
+const DEGRADED_RTT: Duration = Duration::from_millis(500);
+
+/// Settings shared by every session's RTCP reporting.
+#[derive(Debug, Clone)]
+pub struct RtcpSettings {
+    /// SDES CNAME identifying this server (RFC 3550 §6.5.1).
+    pub cname: String,
+    /// Minimum report interval before randomization (RFC 3550 §6.2).
+    pub min_interval: Duration,
+    /// RTP clock rate of the stream, used to express jitter in time.
+    pub clock_rate: u32,
+    /// How packets requested by Generic NACK are resent.
+    pub retransmission: Retransmission,
+    /// Packets kept per SSRC for retransmission.
+    pub history_size: usize,
+    /// Minimum time between keyframe requests passed to the application.
+    pub keyframe_request_interval: Duration,
+}
+
+impl Default for RtcpSettings {
+    fn default() -> Self {
+        Self {
+            cname: random_cname(),
+            min_interval: RTCP_MIN_INTERVAL,
+            clock_rate: 90000,
+            retransmission: Retransmission::Direct,
+            history_size: DEFAULT_HISTORY_SIZE,
+            keyframe_request_interval: DEFAULT_KEYFRAME_REQUEST_INTERVAL,
+        }
+    }
+}
+
+/// A CNAME unique to this server instance.
+pub fn random_cname() -> String {
+    format!("rtsp-{:08x}@rtsp", rand::random::<u32>())
+}
+
+/// Periodic RTCP loop for all sessions.
+///
+/// Wakes every 50ms, drains reports the viewers sent to each session's
+/// RTCP socket (viewers on TCP interleave theirs instead), sends any
+/// Sender Reports that are due, delivers keyframe requests held back by
+/// rate limiting, and exits once `running` is cleared.
+pub fn rtcp_loop(session_manager: SessionManager, running: Arc<AtomicBool>) {
+    let settings = session_manager.rtcp_settings().clone();
+    let clocks = session_manager.clocks().clone();
+
+    while running.load(Ordering::SeqCst) {
+        let now = Instant::now();
+
+        for session in session_manager.get_sessions() {
+            let Some(transport) = session.get_transport() else {
+                continue;
+            };
+            match session.sockets() {
+                Some(sockets) => receive_reports(&session_manager, &session, &sockets, now),
+                None if transport.interleaved.is_none() => continue,
+                None => {}
+            }
+
+            let report = session.rtcp.lock().poll_report(
+                now,
+                &clocks,
+                &settings.cname,
+                settings.min_interval,
+            );
+
+            if let Some(report) = report {
+                match deliver(&session, Channel::Rtcp, &report) {
+                    Ok(_) => tracing::trace!(session_id = %session.id, "sent RTCP SR"),
+                    Err(e) => tracing::warn!(
+                        session_id = %session.id,
+                        error = %e,
+                        "failed to send RTCP"
+                    ),
+                }
+            }
+        }
+
+        session_manager.keyframes().dispatch();
+        thread::sleep(POLL_INTERVAL);
+    }
+    tracing::debug!("RTCP loop exited");
+}
+
+/// Send an RTCP BYE for every SSRC the session carried (RFC 3550 §6.6).
+pub fn send_bye(
+    session: &Session,
+    clocks: &StreamClocks,
+    settings: &RtcpSettings,
+    reason: Option<&str>,
+) {
+    let Some(transport) = session.get_transport() else {
+        return;
+    };
+    if session.sockets().is_none() && transport.interleaved.is_none() {
+        return;
+    }
+    let Some(bye) = session
+        .rtcp
+        .lock()
+        .build_bye(clocks, &settings.cname, reason)
+    else {
+        return;
+    };
+
+    match deliver(session, Channel::Rtcp, &bye) {
+        Ok(_) => tracing::debug!(session_id = %session.id, reason, "sent RTCP BYE"),
+        Err(e) => {
+            tracing::warn!(session_id = %session.id, error = %e, "failed to send RTCP BYE")
+        }
+    }
+}
+
+/// Read every pending datagram on the session's RTCP socket.
+///
+/// Only datagrams from the viewer's IP are accepted; the port is not checked
+/// since NATs commonly rewrite it.
+fn receive_reports(
+    session_manager: &SessionManager,
+    session: &Session,
+    sockets: &SessionSockets,
+    now: Instant,
+) {
+    let Some(transport) = session.get_transport() else {
+        return;
+    };
+    let mut buf = [0u8; MAX_RTCP_SIZE];
+
+    while let Ok((len, from)) = sockets.rtcp.recv_from(&mut buf) {
+        if from.ip() != transport.client_addr.ip() {
+            tracing::debug!(session_id = %session.id, %from, "dropping RTCP from unexpected peer");
+            continue;
+        }
+        handle_report(session_manager, session, &buf[..len], now);
+    }
+}
+
+/// Ingest one compound RTCP packet from the viewer: update its reception
+/// statistics, log quality changes, answer NACK feedback and pass PLI/FIR
+/// on as keyframe requests.
+pub fn handle_report(
+    session_manager: &SessionManager,
+    session: &Session,
+    data: &[u8],
+    now: Instant,
+) {
+    let Some(packets) = session.rtcp.lock().on_rtcp_received(data, now) else {
+        tracing::debug!(session_id = %session.id, len = data.len(), "malformed RTCP packet");
+        return;
+    };
+    session.touch();
+
+    log_quality(session, session_manager.rtcp_settings().clock_rate);
+
+    for packet in &packets {
+        match packet {
+            RtcpPacket::Nack {
+                media_ssrc, lost, ..
+            } => {
+                retransmit(session_manager, session, *media_ssrc, lost);
+            }
+            RtcpPacket::Pli { .. } => {
+                session_manager
+                    .keyframes()
+                    .request(KeyframeReason::PictureLoss, &session.id);
+            }
+            RtcpPacket::Fir { requests, .. } => {
+                let fresh = {
+                    let mut rtcp = session.rtcp.lock();
+                    requests.iter().fold(false, |fresh, (ssrc, seq)| {
+                        rtcp.accept_fir(*ssrc, *seq) || fresh
+                    })
+                };
+                if fresh {
+                    session_manager
+                        .keyframes()
+                        .request(KeyframeReason::FullIntraRequest, &session.id);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn log_quality(session: &Session, clock_rate: u32) {
+    let mut rtcp = session.rtcp.lock();
+    let Some(stats) = rtcp.receiver_stats().cloned() else {
+        return;
+    };
+    tracing::trace!(
+        session_id = %session.id,
+        fraction_lost = stats.fraction_lost,
+        cumulative_lost = stats.cumulative_lost,
+        jitter = stats.jitter,
+        rtt = ?stats.rtt,
+        "RTCP receiver report"
+    );
+
+    let reason = degradation(&stats, clock_rate);
+    if rtcp.set_degraded(reason.is_some()) {
+        match reason {
+            Some(reason) => tracing::warn!(
+                session_id = %session.id,
+                reason,
+                loss = stats.loss_ratio(),
+                jitter = ?stats.jitter_duration(clock_rate),
+                rtt = ?stats.rtt,
+                "viewer quality degraded"
+            ),
+            None => tracing::info!(session_id = %session.id, "viewer quality recovered"),
+        }
+    }
+}
+
+/// Resend packets a viewer reported lost, from the shared packet history.
+/// Only viewers that negotiated a feedback profile get NACKs answered.
+fn retransmit(session_manager: &SessionManager, session: &Session, media_ssrc: u32, lost: &[u16]) {
+    let mode = session_manager.rtcp_settings().retransmission;
+    if mode == Retransmission::Disabled {
+        return;
+    }
+    let feedback = session
+        .get_transport()
+        .is_some_and(|t| matches!(t.profile, Profile::Avpf | Profile::Savpf));
+    if !feedback {
+        tracing::debug!(session_id = %session.id, media_ssrc, "ignoring NACK outside RTP/AVPF");
+        return;
+    }
+    let history = session_manager.history();
+
+    let mut resent = 0;
+    for &seq in lost {
+        let Some(original) = history.get(media_ssrc, seq) else {
+            continue;
+        };
+        let packet = match mode {
+            Retransmission::Rtx { payload_type } => {
+                let Some(rtx_ssrc) = history.rtx_ssrc(media_ssrc) else {
+                    continue;
+                };
+                let rtx_seq = session.rtcp.lock().next_rtx_sequence(rtx_ssrc);
+                match build_rtx_packet(&original, payload_type, rtx_ssrc, rtx_seq) {
+                    Some(packet) => packet,
+                    None => continue,
+                }
+            }
+            _ => original,
+        };
+        if deliver(session, Channel::Rtp, &packet).is_ok() {
+            resent += 1;
+        }
+    }
+
+    session.rtcp.lock().record_nack(lost.len(), resent);
+    tracing::debug!(
+        session_id = %session.id,
+        media_ssrc,
+        requested = lost.len(),
+        resent,
+        "answered NACK"
+    );
+}
+
+fn degradation(stats: &ReceiverStats, clock_rate: u32) -> Option<&'static str> {
+    if stats.loss_ratio() > DEGRADED_LOSS_RATIO {
+        Some("packet loss")
+    } else if stats.jitter_duration(clock_rate) > DEGRADED_JITTER {
+        Some("jitter")
+    } else if stats.rtt.is_some_and(|rtt| rtt > DEGRADED_RTT) {
+        Some("round-trip time")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcp::packet::serialize_compound;
+    use crate::session::Transport;
+
+    fn viewer(session_manager: &SessionManager, profile: Profile) -> Arc<Session> {
+        let session = session_manager.create_session("rtsp://localhost/stream");
+        session.set_transport(Transport {
+            profile,
+            client_rtp_port: 50000,
+            client_rtcp_port: 50001,
+            server_rtp_port: 6000,
+            server_rtcp_port: 6001,
+            client_addr: "127.0.0.1:50000".parse().unwrap(),
+            interleaved: None,
+        });
+        session
+    }
+
+    #[test]
+    fn nack_is_answered_only_with_feedback_profile() {
+        let session_manager = SessionManager::new();
+        let nack = serialize_compound(&[RtcpPacket::Nack {
+            sender_ssrc: 0xBEEF,
+            media_ssrc: 0x1234,
+            lost: vec![7],
+        }]);
+
+        let avp = viewer(&session_manager, Profile::Avp);
+        handle_report(&session_manager, &avp, &nack, Instant::now());
+        assert_eq!(avp.rtcp.lock().retransmission_stats().nacks_received, 0);
+
+        let avpf = viewer(&session_manager, Profile::Avpf);
+        handle_report(&session_manager, &avpf, &nack, Instant::now());
+        assert_eq!(avpf.rtcp.lock().retransmission_stats().nacks_received, 1);
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::{BufRead, BufReader, Write};
+use std::io::{Read, Write};
 use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -7,9 +7,10 @@ use std::time::Duration;
 
 use parking_lot::Mutex;
 
+use crate::error::RtspError;
 use crate::media::Packetizer;
-use crate::protocol::MethodHandler;
-use crate::protocol::RtspRequest;
+use crate::protocol::reader::{Message, MessageError, MessageLimits, MessageReader};
+use crate::protocol::{MethodHandler, RtspRequest, RtspResponse};
 use crate::session::SessionManager;
 
 /// Non-blocking TCP accept loop.
@@ -20,19 +21,26 @@ pub fn accept_loop(
     listener: TcpListener,
     session_manager: SessionManager,
     packetizer: Arc<Mutex<Box<dyn Packetizer>>>,
+    limits: MessageLimits,
     running: Arc<AtomicBool>,
 ) {
     while running.load(Ordering::SeqCst) {
         match listener.accept() {
-            Ok((stream, _)) => {
+            Ok((stream, peer_addr)) => {
                 if stream.set_nonblocking(false).is_err() {
                     continue;
                 }
+                if !session_manager.access().admits(peer_addr.ip()) {
+                    tracing::warn!(%peer_addr, "connection refused by access policy");
+                    refuse(stream);
+                    continue;
+                }
                 let sm = session_manager.clone();
                 let r = running.clone();
                 let p = packetizer.clone();
+                let l = limits.clone();
                 thread::spawn(move || {
-                    Connection::handle(stream, sm, p, r);
+                    Connection::handle(stream, sm, p, l, r);
                 });
             }
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -48,10 +56,73 @@ pub fn accept_loop(
     tracing::debug!("accept loop exited");
 }
 
+/// Answer a connection from a refused address with 403 and close it,
+/// without reading anything from it.
+fn refuse(mut stream: TcpStream) {
+    let _ = stream.set_write_timeout(Some(Duration::from_millis(100)));
+    let response = RtspResponse::from_error(&RtspError::Forbidden(String::new()))
+        .add_header("Connection", "close");
+    let _ = stream.write_all(response.serialize().as_bytes());
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+/// Bytes read from the socket per `read` call.
+const READ_CHUNK: usize = 4096;
+
+/// How long a write to a client may block before the connection is
+/// considered stalled.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The sending half of an RTSP connection, shared by its responses and
+/// the RTP and RTCP interleaved to a viewer that set up over TCP, so
+/// frames never split a response.
+#[derive(Debug)]
+pub struct ConnectionWriter {
+    stream: Mutex<TcpStream>,
+    /// Set once a write failed; later interleaved frames are dropped
+    /// rather than stalling the sender on a dead connection.
+    broken: AtomicBool,
+}
+
+impl ConnectionWriter {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        Ok(Self {
+            stream: Mutex::new(stream),
+            broken: AtomicBool::new(false),
+        })
+    }
+
+    pub fn write_all(&self, data: &[u8]) -> std::io::Result<()> {
+        let result = self.stream.lock().write_all(data);
+        if result.is_err() {
+            self.broken.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Send `data` as an interleaved frame on `channel` (RFC 2326 §10.12).
+    pub fn send_frame(&self, channel: u8, data: &[u8]) -> std::io::Result<()> {
+        if self.broken.load(Ordering::Relaxed) {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        let len = u16::try_from(data.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "frame too large")
+        })?;
+        let mut frame = Vec::with_capacity(4 + data.len());
+        frame.push(b'$');
+        frame.push(channel);
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(data);
+        self.write_all(&frame)
+    }
+}
+
 /// A single RTSP client connection with its own lifecycle.
 struct Connection {
-    reader: BufReader<TcpStream>,
-    writer: TcpStream,
+    stream: TcpStream,
+    writer: Arc<ConnectionWriter>,
+    reader: MessageReader,
     handler: MethodHandler,
     peer_addr: SocketAddr,
 }
@@ -62,6 +133,7 @@ impl Connection {
         stream: TcpStream,
         session_manager: SessionManager,
         packetizer: Arc<Mutex<Box<dyn Packetizer>>>,
+        limits: MessageLimits,
         running: Arc<AtomicBool>,
     ) {
         let peer_addr = match stream.peer_addr() {
@@ -71,16 +143,20 @@ impl Connection {
 
         tracing::info!(%peer_addr, "client connected");
 
-        let reader_stream = match stream.try_clone() {
-            Ok(s) => s,
-            Err(_) => return,
+        let writer = match stream.try_clone().and_then(ConnectionWriter::new) {
+            Ok(writer) => Arc::new(writer),
+            Err(e) => {
+                tracing::warn!(%peer_addr, error = %e, "failed to set up connection");
+                return;
+            }
         };
-
-        let handler = MethodHandler::new(session_manager.clone(), peer_addr, packetizer);
+        let mut handler = MethodHandler::new(session_manager.clone(), peer_addr, packetizer);
+        handler.set_writer(writer.clone());
 
         let mut conn = Connection {
-            reader: BufReader::new(reader_stream),
-            writer: stream,
+            stream,
+            writer,
+            reader: MessageReader::new(limits),
             handler,
             peer_addr,
         };
@@ -93,63 +169,141 @@ impl Connection {
 
     /// RTSP request/response loop. Returns the reason for exiting.
     fn run(&mut self, running: &Arc<AtomicBool>) -> &'static str {
+        let mut chunk = [0u8; READ_CHUNK];
+
         while running.load(Ordering::SeqCst) {
-            let mut request_text = String::new();
             loop {
-                let mut line = String::new();
-                match self.reader.read_line(&mut line) {
-                    Ok(0) => return "connection closed by client",
-                    Ok(_) => {
-                        request_text.push_str(&line);
-                        if line == "\r\n" || line == "\n" {
-                            break;
+                match self.reader.next_message() {
+                    Ok(Some(Message::Request(request))) => {
+                        if let Err(reason) = self.respond(&request) {
+                            return reason;
+                        }
+                    }
+                    Ok(Some(Message::Interleaved { channel, data })) => {
+                        self.handler.handle_interleaved(channel, &data);
+                    }
+                    Ok(Some(Message::Response(response))) => {
+                        tracing::debug!(peer = %self.peer_addr, status = response.status_code, "ignoring response from client");
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        if let Err(reason) = self.reject(&e) {
+                            return reason;
+                        }
+                        if e.is_fatal() {
+                            return "unrecoverable request framing";
                         }
                     }
-                    Err(_) => return "read error",
                 }
             }
 
-            if request_text.trim().is_empty() {
-                continue;
-            }
-
-            match RtspRequest::parse(&request_text) {
-                Ok(request) => {
-                    tracing::debug!(
-                        peer = %self.peer_addr,
-                        method = %request.method,
-                        uri = %request.uri,
-                        version = %request.version,
-                        "request"
-                    );
-
-                    let response = self.handler.handle(&request);
-
-                    tracing::debug!(
-                        peer = %self.peer_addr,
-                        status = response.status_code,
-                        "response"
-                    );
-
-                    if self.writer.write_all(response.serialize().as_bytes()).is_err() {
-                        return "write error";
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!(peer = %self.peer_addr, error = %e, "parse error");
-                }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return "connection closed by client",
+                Ok(n) => self.reader.feed(&chunk[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return "read error",
             }
         }
 
         "server shutting down"
     }
 
-    /// Clean up sessions owned by this connection.
-    fn cleanup(&self, session_manager: &SessionManager) {
-        let orphaned = self.handler.session_ids().to_vec();
+    fn respond(&mut self, request: &RtspRequest) -> Result<(), &'static str> {
+        tracing::debug!(
+            peer = %self.peer_addr,
+            method = %request.method,
+            uri = %request.uri,
+            version = %request.version,
+            body_len = request.body.len(),
+            "request"
+        );
+
+        let response = self.handler.handle(request);
+
+        tracing::debug!(
+            peer = %self.peer_addr,
+            status = response.status_code,
+            "response"
+        );
+
+        self.write(&response)
+    }
+
+    /// Answer a request the reader could not accept.
+    fn reject(&mut self, error: &MessageError) -> Result<(), &'static str> {
+        tracing::warn!(peer = %self.peer_addr, error = %error.error, "rejecting request");
+
+        let mut response = RtspResponse::from_error(&error.error);
+        if let Some(cseq) = &error.cseq {
+            response = response.add_header("CSeq", cseq);
+        }
+        self.write(&response)
+    }
+
+    fn write(&mut self, response: &RtspResponse) -> Result<(), &'static str> {
+        self.writer
+            .write_all(response.serialize().as_bytes())
+            .map_err(|_| "write error")
+    }
+
+    /// Clean up sessions owned by this connection. Sessions open to hand-off
+    /// are left for a reconnecting client or the session reaper.
+    fn cleanup(&mut self, session_manager: &SessionManager) {
+        let orphaned = self.handler.sessions_to_clean_up();
         if !orphaned.is_empty() {
             let removed = session_manager.remove_sessions(&orphaned);
             tracing::info!(peer = %self.peer_addr, removed, "cleaned up sessions on disconnect");
         }
+        self.handler.release_announced_mounts();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::h264::H264Packetizer;
+    use crate::protocol::AccessPolicy;
+
+    #[test]
+    fn denied_networks_get_403_before_any_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let session_manager = SessionManager::new();
+        session_manager.access().set_policy(AccessPolicy {
+            deny: vec!["127.0.0.0/8".parse().unwrap()],
+            ..AccessPolicy::default()
+        });
+        let running = Arc::new(AtomicBool::new(true));
+        let packetizer: Arc<Mutex<Box<dyn Packetizer>>> =
+            Arc::new(Mutex::new(Box::new(H264Packetizer::new(96, 1))));
+        let accept = thread::spawn({
+            let running = running.clone();
+            let session_manager = session_manager.clone();
+            move || {
+                accept_loop(
+                    listener,
+                    session_manager,
+                    packetizer,
+                    MessageLimits::default(),
+                    running,
+                )
+            }
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("RTSP/1.0 403 "), "{response}");
+        assert!(response.contains("Connection: close\r\n"));
+        // Answered without waiting for a request, so there's no CSeq.
+        assert!(!response.contains("CSeq"));
+        assert!(session_manager.get_sessions().is_empty());
+
+        running.store(false, Ordering::SeqCst);
+        accept.join().unwrap();
     }
 }
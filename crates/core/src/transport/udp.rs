@@ -2,7 +2,28 @@ use std::net::{SocketAddr, UdpSocket};
 use std::sync::Arc;
 
 use crate::error::{Result, RtspError};
-use crate::session::SessionManager;
+use crate::session::{Session, SessionManager};
+use crate::transport::Channel;
+
+/// UDP sockets bound to a session's allocated server RTP/RTCP ports.
+///
+/// RTP is sent from the advertised `server_port` so NATs and firewalls see
+/// the port pair negotiated in SETUP. The RTCP socket is non-blocking and
+/// polled by [`crate::transport::rtcp::rtcp_loop`].
+#[derive(Debug)]
+pub struct SessionSockets {
+    pub rtp: UdpSocket,
+    pub rtcp: UdpSocket,
+}
+
+impl SessionSockets {
+    pub fn bind(rtp_port: u16, rtcp_port: u16) -> Result<Self> {
+        let rtp = UdpSocket::bind(("0.0.0.0", rtp_port))?;
+        let rtcp = UdpSocket::bind(("0.0.0.0", rtcp_port))?;
+        rtcp.set_nonblocking(true)?;
+        Ok(Self { rtp, rtcp })
+    }
+}
 
 /// UDP transport for RTP packet delivery.
 pub struct UdpTransport {
@@ -33,20 +54,17 @@ impl UdpTransport {
             return Err(RtspError::SessionNotPlaying(session_id.to_string()));
         }
 
-        let transport = session
-            .get_transport()
-            .ok_or_else(|| RtspError::TransportNotConfigured(session_id.to_string()))?;
-
-        Ok(self.socket.send_to(payload, transport.client_addr)?)
+        self.send(&session, payload)
     }
 
     /// Broadcast an RTP packet to all playing sessions.
-    pub fn broadcast(
-        &self,
-        session_manager: &SessionManager,
-        payload: &[u8],
-    ) -> Result<usize> {
-        let playing = session_manager.get_playing_sessions();
+    pub fn broadcast(&self, session_manager: &SessionManager, payload: &[u8]) -> Result<usize> {
+        // Viewers of a mount get that mount's packets instead.
+        let playing: Vec<_> = session_manager
+            .get_playing_sessions()
+            .into_iter()
+            .filter(|s| s.mount().is_none())
+            .collect();
 
         if playing.is_empty() {
             return Ok(0);
@@ -54,17 +72,15 @@ impl UdpTransport {
 
         let mut sent = 0;
         for session in &playing {
-            if let Some(transport) = session.get_transport() {
-                match self.socket.send_to(payload, transport.client_addr) {
-                    Ok(_) => sent += 1,
-                    Err(e) => {
-                        tracing::warn!(
-                            session_id = %session.id,
-                            addr = %transport.client_addr,
-                            error = %e,
-                            "failed to send RTP packet"
-                        );
-                    }
+            match self.send(session, payload) {
+                Ok(_) => sent += 1,
+                Err(RtspError::TransportNotConfigured(_)) => {}
+                Err(e) => {
+                    tracing::warn!(
+                        session_id = %session.id,
+                        error = %e,
+                        "failed to send RTP packet"
+                    );
                 }
             }
         }
@@ -72,8 +88,34 @@ impl UdpTransport {
         Ok(sent)
     }
 
+    /// Send from the session's own RTP socket when it has one, and account
+    /// for the packet in its RTCP sender statistics.
+    fn send(&self, session: &Session, payload: &[u8]) -> Result<usize> {
+        let transport = session
+            .get_transport()
+            .ok_or_else(|| RtspError::TransportNotConfigured(session.id.clone()))?;
+
+        let sent = match session.sockets() {
+            None if transport.interleaved.is_none() => {
+                self.socket.send_to(payload, transport.client_addr)?
+            }
+            _ => super::deliver(session, Channel::Rtp, payload)?,
+        };
+        session.rtcp.lock().on_rtp_sent(payload);
+        Ok(sent)
+    }
+
     /// Send raw bytes to a specific address.
     pub fn send_to(&self, payload: &[u8], addr: SocketAddr) -> Result<usize> {
         Ok(self.socket.send_to(payload, addr)?)
     }
 }
+
+/// Send an RTP packet over the session's own transport, accounting for it
+/// in the session's RTCP sender statistics. Used to relay mount packets,
+/// which don't go through a [`UdpTransport`].
+pub fn send_rtp(session: &Session, payload: &[u8]) -> Result<usize> {
+    let sent = super::deliver(session, Channel::Rtp, payload)?;
+    session.rtcp.lock().on_rtp_sent(payload);
+    Ok(sent)
+}
@@ -3,10 +3,13 @@ use std::sync::{Arc, LazyLock, Mutex};
 use gst::glib;
 use gst::prelude::*;
 use gst::subclass::prelude::*;
+use gst_base::prelude::*;
 use gst_base::subclass::prelude::*;
 
 use rtsp::Packetizer;
 use rtsp::Server;
+use rtsp::protocol::AuthSettings;
+use rtsp::protocol::auth::DEFAULT_REALM;
 
 static CAT: LazyLock<gst::DebugCategory> = LazyLock::new(|| {
     gst::DebugCategory::new(
@@ -23,6 +26,10 @@ const DEFAULT_PORT: u32 = 8554;
 struct Settings {
     address: String,
     port: u32,
+    user_id: Option<String>,
+    user_pw: Option<String>,
+    realm: String,
+    allow_basic: bool,
 }
 
 impl Default for Settings {
@@ -30,6 +37,10 @@ impl Default for Settings {
         Self {
             address: DEFAULT_ADDRESS.to_string(),
             port: DEFAULT_PORT,
+            user_id: None,
+            user_pw: None,
+            realm: DEFAULT_REALM.to_string(),
+            allow_basic: false,
         }
     }
 }
@@ -77,6 +88,24 @@ impl ObjectImpl for RtspServerSink {
                     .maximum(65535)
                     .default_value(DEFAULT_PORT)
                     .build(),
+                glib::ParamSpecString::builder("user-id")
+                    .nick("User ID")
+                    .blurb("User name clients must log in with; unset serves everyone")
+                    .build(),
+                glib::ParamSpecString::builder("user-pw")
+                    .nick("User Password")
+                    .blurb("Password for user-id")
+                    .build(),
+                glib::ParamSpecString::builder("realm")
+                    .nick("Realm")
+                    .blurb("Realm shown to clients asked for credentials")
+                    .default_value(Some(DEFAULT_REALM))
+                    .build(),
+                glib::ParamSpecBoolean::builder("allow-basic")
+                    .nick("Allow Basic")
+                    .blurb("Accept Basic authentication, which sends the password in the clear")
+                    .default_value(false)
+                    .build(),
             ]
         })
     }
@@ -90,6 +119,25 @@ impl ObjectImpl for RtspServerSink {
             "port" => {
                 settings.port = value.get::<u32>().expect("type checked upstream");
             }
+            "user-id" => {
+                settings.user_id = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream");
+            }
+            "user-pw" => {
+                settings.user_pw = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream");
+            }
+            "realm" => {
+                settings.realm = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .unwrap_or_else(|| DEFAULT_REALM.to_string());
+            }
+            "allow-basic" => {
+                settings.allow_basic = value.get::<bool>().expect("type checked upstream");
+            }
             _ => unimplemented!(),
         }
     }
@@ -99,6 +147,10 @@ impl ObjectImpl for RtspServerSink {
         match pspec.name() {
             "address" => settings.address.to_value(),
             "port" => settings.port.to_value(),
+            "user-id" => settings.user_id.to_value(),
+            "user-pw" => settings.user_pw.to_value(),
+            "realm" => settings.realm.to_value(),
+            "allow-basic" => settings.allow_basic.to_value(),
             _ => unimplemented!(),
         }
     }
@@ -146,6 +198,19 @@ impl BaseSinkImpl for RtspServerSink {
 
         let mut server = Server::new(&bind_addr);
 
+        if let Some(user_id) = settings.user_id.clone() {
+            let user_pw = settings.user_pw.clone().unwrap_or_default();
+            let auth = AuthSettings {
+                realm: settings.realm.clone(),
+                allow_basic: settings.allow_basic,
+                ..AuthSettings::default()
+            };
+            server.set_authenticator(
+                move |username: &str| (username == user_id).then(|| user_pw.clone()),
+                auth,
+            );
+        }
+
         server.start().map_err(|e| {
             gst::error_msg!(
                 gst::ResourceError::OpenWrite,
@@ -173,6 +238,16 @@ impl BaseSinkImpl for RtspServerSink {
         Ok(())
     }
 
+    fn event(&self, event: gst::Event) -> bool {
+        if let gst::EventView::Eos(_) = event.view()
+            && let Some(state) = self.state.lock().unwrap().as_ref()
+        {
+            let ended = state.server.end_stream(Some("end of stream"));
+            gst::info!(CAT, imp = self, "EOS: sent RTCP BYE to {} viewers", ended);
+        }
+        self.parent_event(event)
+    }
+
     fn render(&self, buffer: &gst::Buffer) -> Result<gst::FlowSuccess, gst::FlowError> {
         let map = buffer.map_readable().map_err(|_| {
             gst::error!(CAT, imp = self, "Failed to map buffer readable");
@@ -198,6 +273,30 @@ impl BaseSinkImpl for RtspServerSink {
             }
         }
 
+        let keyframe_request = state.server.poll_keyframe_request();
+        drop(state_guard);
+
+        if let Some(request) = keyframe_request {
+            gst::debug!(
+                CAT,
+                imp = self,
+                "Requesting keyframe upstream ({})",
+                request.reason.as_str()
+            );
+            self.obj().sink_pad().push_event(force_key_unit_event());
+        }
+
         Ok(gst::FlowSuccess::Ok)
     }
 }
+
+/// Upstream GstForceKeyUnit event, as built by
+/// `gst_video_event_new_upstream_force_key_unit(GST_CLOCK_TIME_NONE, TRUE, 0)`.
+fn force_key_unit_event() -> gst::Event {
+    let structure = gst::Structure::builder("GstForceKeyUnit")
+        .field("running-time", u64::MAX)
+        .field("all-headers", true)
+        .field("count", 0u32)
+        .build();
+    gst::event::CustomUpstream::new(structure)
+}
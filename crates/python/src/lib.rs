@@ -1,5 +1,6 @@
 mod packetizer;
 mod server;
+mod source;
 mod types;
 
 use pyo3::prelude::*;
@@ -10,5 +11,9 @@ fn rtsp_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<server::PyServer>()?;
     m.add_class::<packetizer::PyH264Packetizer>()?;
     m.add_class::<types::PyViewer>()?;
+    m.add_class::<source::PyRtpSource>()?;
+    m.add_class::<source::PyUdpSource>()?;
+    m.add_class::<source::PyRelay>()?;
+    m.add_function(wrap_pyfunction!(server::sign_url, m)?)?;
     Ok(())
 }
@@ -1,10 +1,82 @@
 use parking_lot::Mutex;
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
+use crate::source::{PyRelay, PyRtpSource, PyUdpSource};
 use crate::types::PyViewer;
-use rtsp::Server;
+use rtsp::client::ClientTransport;
+use rtsp::mount::RtpCodec;
+use rtsp::protocol::{AccessPolicy, AccessRule, AuthSettings, IpNet, Method, Principal, UrlSigner};
+use rtsp::transport::relay::{RelayConfig, RelayMode};
+use rtsp::{Server, ServerConfig};
+
+/// An access rule as given from Python: `(path, who)` or
+/// `(path, who, methods)`.
+#[derive(FromPyObject)]
+enum PyAccessRule {
+    WithMethods(String, Vec<String>, Vec<String>),
+    AllMethods(String, Vec<String>),
+}
+
+impl From<PyAccessRule> for AccessRule {
+    fn from(rule: PyAccessRule) -> Self {
+        let (path, who, methods) = match rule {
+            PyAccessRule::WithMethods(path, who, methods) => (path, who, methods),
+            PyAccessRule::AllMethods(path, who) => (path, who, Vec::new()),
+        };
+        let methods: Vec<Method> = methods
+            .iter()
+            .map(|m| Method::from(m.to_uppercase().as_str()))
+            .collect();
+        who.iter()
+            .fold(AccessRule::new(&path).methods(&methods), |rule, w| {
+                rule.allow(Principal::parse(w))
+            })
+    }
+}
+
+fn parse_networks(networks: Vec<String>) -> PyResult<Vec<IpNet>> {
+    networks
+        .iter()
+        .map(|n| {
+            n.parse()
+                .map_err(|e: rtsp::RtspError| PyValueError::new_err(e.to_string()))
+        })
+        .collect()
+}
+
+/// A secret given as str or bytes.
+fn secret_bytes(secret: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    match secret.extract::<String>() {
+        Ok(secret) => Ok(secret.into_bytes()),
+        Err(_) => secret.extract::<Vec<u8>>(),
+    }
+}
+
+/// `url` with a token signed with `secret` appended, letting it play for
+/// `expires_in` seconds on a server given the same secret with
+/// `Server.set_url_secret`. With `ip`, only that client address may use it.
+#[pyfunction]
+#[pyo3(signature = (url, secret, expires_in, ip = None))]
+pub fn sign_url(
+    url: &str,
+    secret: Bound<'_, PyAny>,
+    expires_in: f64,
+    ip: Option<&str>,
+) -> PyResult<String> {
+    let ip = ip
+        .map(|ip| {
+            ip.parse()
+                .map_err(|e| PyValueError::new_err(format!("invalid ip '{ip}': {e}")))
+        })
+        .transpose()?;
+    let valid_for = Duration::try_from_secs_f64(expires_in)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(UrlSigner::new(secret_bytes(&secret)?).sign_url(url, valid_for, ip))
+}
 
 #[pyclass(name = "Server")]
 pub struct PyServer {
@@ -23,10 +95,21 @@ impl PyServer {
 #[pymethods]
 impl PyServer {
     #[new]
-    #[pyo3(signature = (bind_addr = "0.0.0.0:8554"))]
-    fn new(bind_addr: &str) -> Self {
+    #[pyo3(signature = (bind_addr = "0.0.0.0:8554", server_port_range = None, session_timeout = None))]
+    fn new(
+        bind_addr: &str,
+        server_port_range: Option<(u16, u16)>,
+        session_timeout: Option<u64>,
+    ) -> Self {
+        let mut config = ServerConfig::default();
+        if let Some((min, max)) = server_port_range {
+            config.server_port_range = min..=max;
+        }
+        if let Some(secs) = session_timeout {
+            config.session_timeout = Duration::from_secs(secs);
+        }
         PyServer {
-            inner: Arc::new(Mutex::new(Server::new(bind_addr))),
+            inner: Arc::new(Mutex::new(Server::with_config(bind_addr, config))),
         }
     }
 
@@ -41,6 +124,12 @@ impl PyServer {
         self.with_server(|s| s.stop())
     }
 
+    /// End the stream: viewers get an RTCP BYE and their sessions are removed.
+    #[pyo3(signature = (reason = None))]
+    fn end_stream(&self, reason: Option<&str>) -> PyResult<usize> {
+        self.with_server(|s| s.end_stream(reason))
+    }
+
     fn is_running(&self) -> PyResult<bool> {
         self.with_server(|s| s.is_running())
     }
@@ -59,6 +148,213 @@ impl PyServer {
             .map_err(|e| PyRuntimeError::new_err(e.to_string()))
     }
 
+    /// Record the capture time (Unix seconds) of the frame carried by `packet`,
+    /// used for the NTP↔RTP mapping in RTCP Sender Reports.
+    fn set_frame_capture_time(&self, packet: &[u8], capture_time: f64) -> PyResult<()> {
+        let since_epoch = Duration::try_from_secs_f64(capture_time)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let capture_time = UNIX_EPOCH + since_epoch;
+        self.inner
+            .lock()
+            .set_frame_capture_time(packet, capture_time)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Take the pending keyframe request, if any, returning its reason
+    /// ("pli", "fir" or "new-viewer"). Poll once per frame and encode the next
+    /// frame as a keyframe when this returns a value.
+    fn poll_keyframe_request(&self) -> PyResult<Option<&'static str>> {
+        self.with_server(|s| s.poll_keyframe_request().map(|r| r.reason.as_str()))
+    }
+
+    /// Accept `tag` in clients' Require and Proxy-Require headers. Requests
+    /// requiring an unregistered tag get 551 Option Not Supported.
+    fn register_feature_tag(&self, tag: &str) -> PyResult<()> {
+        self.with_server(|s| s.register_feature_tag(tag))
+    }
+
+    /// Serve ready-made RTP packets at `path`. Describe them with `sdp`, a
+    /// full description or just its m= sections, or with codec parameters:
+    /// `add_rtp_source("cam", payload_type=96, encoding="H264")`.
+    #[pyo3(signature = (path, sdp = None, *, media = "video", payload_type = None, encoding = None, clock_rate = 90000, channels = None, fmtp = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_rtp_source(
+        &self,
+        path: &str,
+        sdp: Option<String>,
+        media: &str,
+        payload_type: Option<u8>,
+        encoding: Option<String>,
+        clock_rate: u32,
+        channels: Option<u8>,
+        fmtp: Option<String>,
+    ) -> PyResult<PyRtpSource> {
+        let sdp = match (sdp, payload_type, encoding) {
+            (Some(sdp), None, None) => sdp,
+            (None, Some(payload_type), Some(encoding)) => RtpCodec {
+                media: media.to_string(),
+                payload_type,
+                encoding,
+                clock_rate,
+                channels,
+                fmtp,
+            }
+            .media_description(),
+            _ => {
+                return Err(PyValueError::new_err(
+                    "pass either sdp or payload_type and encoding",
+                ));
+            }
+        };
+        self.inner
+            .lock()
+            .add_rtp_source(path, &sdp)
+            .map(PyRtpSource::from)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Serve plain RTP arriving on the UDP address `bind` ("host:port") at
+    /// `path`. Describe the stream with `sdp`, e.g. the file ffmpeg prints
+    /// for `-f rtp`; otherwise name the codec, as in `a=rtpmap`, e.g.
+    /// `codec="H264"` or `codec="opus/48000/2"`. Static payload types need
+    /// neither.
+    #[pyo3(signature = (path, bind, sdp = None, payload_type = 96, codec = None))]
+    fn add_udp_source(
+        &self,
+        path: &str,
+        bind: &str,
+        sdp: Option<String>,
+        payload_type: u8,
+        codec: Option<&str>,
+    ) -> PyResult<PyUdpSource> {
+        let bind = bind
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("invalid address '{bind}': {e}")))?;
+        let sdp = match (sdp, codec) {
+            (Some(sdp), _) => sdp,
+            (None, Some(codec)) => RtpCodec::with_encoding(payload_type, codec)
+                .ok_or_else(|| PyValueError::new_err(format!("invalid codec '{codec}'")))?
+                .media_description(),
+            (None, None) => RtpCodec::from_payload_type(payload_type)
+                .ok_or_else(|| {
+                    PyValueError::new_err(format!("payload type {payload_type} needs codec or sdp"))
+                })?
+                .media_description(),
+        };
+        self.inner
+            .lock()
+            .add_udp_source(path, bind, &sdp)
+            .map(PyUdpSource::from)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Restream the RTSP stream at `url` from `path`, sharing one upstream
+    /// session among all viewers. Upstream is pulled while viewers watch,
+    /// or all the time with `always_on`; `tcp` receives it interleaved on
+    /// the RTSP connection instead of over UDP.
+    #[pyo3(signature = (path, url, *, always_on = false, tcp = false))]
+    fn add_relay(&self, path: &str, url: &str, always_on: bool, tcp: bool) -> PyResult<PyRelay> {
+        let config = RelayConfig {
+            mode: if always_on {
+                RelayMode::AlwaysOn
+            } else {
+                RelayMode::OnDemand
+            },
+            transport: if tcp {
+                ClientTransport::Tcp
+            } else {
+                ClientTransport::Udp
+            },
+            ..RelayConfig::default()
+        };
+        self.inner
+            .lock()
+            .add_relay(path, url, config)
+            .map(PyRelay::from)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Require clients to log in for every request but OPTIONS. `users`
+    /// maps user names to passwords, or is a callable taking a user name
+    /// and returning its password or None. Clients authenticate with
+    /// Digest, and with Basic too when `allow_basic` is set.
+    #[pyo3(signature = (users, *, realm = None, allow_basic = false))]
+    fn set_credentials(
+        &self,
+        users: Bound<'_, PyAny>,
+        realm: Option<String>,
+        allow_basic: bool,
+    ) -> PyResult<()> {
+        let mut settings = AuthSettings {
+            allow_basic,
+            ..AuthSettings::default()
+        };
+        if let Some(realm) = realm {
+            settings.realm = realm;
+        }
+        let server = self.inner.lock();
+        if users.is_callable() {
+            let callback = users.unbind();
+            server.set_authenticator(
+                move |username: &str| {
+                    Python::attach(|py| {
+                        callback
+                            .call1(py, (username,))
+                            .and_then(|password| password.extract::<Option<String>>(py))
+                            .unwrap_or_else(|e| {
+                                e.write_unraisable(py, None);
+                                None
+                            })
+                    })
+                },
+                settings,
+            );
+        } else {
+            server.set_authenticator(users.extract::<HashMap<String, String>>()?, settings);
+        }
+        Ok(())
+    }
+
+    /// Serve clients without asking for credentials again.
+    fn clear_credentials(&self) -> PyResult<()> {
+        self.with_server(|s| s.clear_authenticator())
+    }
+
+    /// Decide who may use which mounts, and who may connect at all.
+    /// `rules` are `(path, who)` or `(path, who, methods)` tuples; the first
+    /// one matching a request's mount path and method decides. `path` is a
+    /// mount path, a prefix such as `"cams/*"` or `"*"`; `who` lists `"*"`
+    /// for anyone, `"+"` for any logged-in user, `"~"` for holders of a
+    /// signed URL, `"@role"` or user names.
+    /// `roles` maps role names to their users. Connections from `deny_ips`,
+    /// or from outside `allow_ips` if given, are refused on accept.
+    #[pyo3(signature = (rules = Vec::new(), *, roles = HashMap::new(), allow_ips = Vec::new(), deny_ips = Vec::new()))]
+    fn set_access_policy(
+        &self,
+        rules: Vec<PyAccessRule>,
+        roles: HashMap<String, Vec<String>>,
+        allow_ips: Vec<String>,
+        deny_ips: Vec<String>,
+    ) -> PyResult<()> {
+        let policy = AccessPolicy {
+            rules: rules.into_iter().map(AccessRule::from).collect(),
+            roles,
+            allow: parse_networks(allow_ips)?,
+            deny: parse_networks(deny_ips)?,
+        };
+        self.with_server(|s| s.set_access_policy(policy))
+    }
+
+    /// Accept URLs signed with `secret` by `rtsp.sign_url` in place of
+    /// credentials; None stops accepting them.
+    fn set_url_secret(&self, secret: Option<Bound<'_, PyAny>>) -> PyResult<()> {
+        let secret = secret.map(|s| secret_bytes(&s)).transpose()?;
+        self.with_server(|s| match secret {
+            Some(secret) => s.set_url_secret(secret),
+            None => s.clear_url_secret(),
+        })
+    }
+
     fn get_viewers(&self) -> PyResult<Vec<PyViewer>> {
         let viewers = self.inner.lock().get_viewers();
         Ok(viewers.into_iter().map(PyViewer::from).collect())
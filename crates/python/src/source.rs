@@ -0,0 +1,133 @@
+use parking_lot::Mutex;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+
+use rtsp::RtspError;
+use rtsp::mount::RtpSource;
+use rtsp::transport::ingest::UdpSource;
+use rtsp::transport::relay::Relay;
+
+/// A mount fed with ready-made RTP packets, returned by
+/// `Server.add_rtp_source`.
+#[pyclass(name = "RtpSource")]
+pub struct PyRtpSource {
+    inner: Mutex<Option<RtpSource>>,
+    path: String,
+}
+
+impl From<RtpSource> for PyRtpSource {
+    fn from(source: RtpSource) -> Self {
+        PyRtpSource {
+            path: source.path().to_string(),
+            inner: Mutex::new(Some(source)),
+        }
+    }
+}
+
+#[pymethods]
+impl PyRtpSource {
+    #[getter]
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Relay one RTP packet to the viewers of its track. Returns the number
+    /// of viewers it was sent to; raises ValueError for packets that aren't
+    /// valid RTP of a payload type in the SDP.
+    fn push(&self, packet: &[u8]) -> PyResult<usize> {
+        let inner = self.inner.lock();
+        let source = inner
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("RTP source closed"))?;
+        source.push(packet).map_err(|e| match e {
+            RtspError::InvalidRtpPacket => PyValueError::new_err(e.to_string()),
+            e => PyRuntimeError::new_err(e.to_string()),
+        })
+    }
+
+    /// Remove the mount and end its viewers' sessions.
+    fn close(&self) {
+        self.inner.lock().take();
+    }
+}
+
+/// A mount fed with RTP arriving on a local UDP port, returned by
+/// `Server.add_udp_source`.
+#[pyclass(name = "UdpSource")]
+pub struct PyUdpSource {
+    inner: Mutex<Option<UdpSource>>,
+    path: String,
+    local_addr: String,
+}
+
+impl From<UdpSource> for PyUdpSource {
+    fn from(source: UdpSource) -> Self {
+        PyUdpSource {
+            path: source.path().to_string(),
+            local_addr: source.local_addr().to_string(),
+            inner: Mutex::new(Some(source)),
+        }
+    }
+}
+
+#[pymethods]
+impl PyUdpSource {
+    #[getter]
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Address the source listens on, as "host:port".
+    #[getter]
+    fn local_addr(&self) -> &str {
+        &self.local_addr
+    }
+
+    /// Stop listening, remove the mount and end its viewers' sessions.
+    fn close(&self) {
+        self.inner.lock().take();
+    }
+}
+
+/// A mount restreaming an upstream RTSP URL, returned by
+/// `Server.add_relay`.
+#[pyclass(name = "Relay")]
+pub struct PyRelay {
+    inner: Mutex<Option<Relay>>,
+    path: String,
+    url: String,
+}
+
+impl From<Relay> for PyRelay {
+    fn from(relay: Relay) -> Self {
+        PyRelay {
+            path: relay.path().to_string(),
+            url: relay.url().to_string(),
+            inner: Mutex::new(Some(relay)),
+        }
+    }
+}
+
+#[pymethods]
+impl PyRelay {
+    #[getter]
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[getter]
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Whether upstream is currently playing to the relay.
+    #[getter]
+    fn connected(&self) -> bool {
+        self.inner.lock().as_ref().is_some_and(|r| r.is_connected())
+    }
+
+    /// Stop relaying, remove the mount and end its viewers' sessions.
+    fn close(&self) {
+        self.inner.lock().take();
+    }
+}
@@ -13,6 +13,26 @@ pub struct PyViewer {
     pub client_addr: String,
     #[pyo3(get)]
     pub client_rtp_port: u16,
+    #[pyo3(get)]
+    pub packets_sent: u64,
+    #[pyo3(get)]
+    pub octets_sent: u64,
+    #[pyo3(get)]
+    pub fraction_lost: Option<f64>,
+    #[pyo3(get)]
+    pub cumulative_lost: Option<i32>,
+    /// Interarrival jitter in seconds.
+    #[pyo3(get)]
+    pub jitter: Option<f64>,
+    /// Round-trip time in seconds.
+    #[pyo3(get)]
+    pub rtt: Option<f64>,
+    #[pyo3(get)]
+    pub nacks_received: u64,
+    #[pyo3(get)]
+    pub packets_retransmitted: u64,
+    #[pyo3(get)]
+    pub retransmissions_missed: u64,
 }
 
 impl From<Viewer> for PyViewer {
@@ -22,6 +42,15 @@ impl From<Viewer> for PyViewer {
             uri: v.uri,
             client_addr: v.client_addr,
             client_rtp_port: v.client_rtp_port,
+            packets_sent: v.packets_sent,
+            octets_sent: v.octets_sent,
+            fraction_lost: v.fraction_lost,
+            cumulative_lost: v.cumulative_lost,
+            jitter: v.jitter.map(|d| d.as_secs_f64()),
+            rtt: v.rtt.map(|d| d.as_secs_f64()),
+            nacks_received: v.nacks_received,
+            packets_retransmitted: v.packets_retransmitted,
+            retransmissions_missed: v.retransmissions_missed,
         }
     }
 }
@@ -40,12 +40,13 @@ class FrameEncoder:
         # Store SPS/PPS from first frame
         self.sps_pps = None
 
-    def encode(self, rgb_array: np.ndarray) -> bytes:
+    def encode(self, rgb_array: np.ndarray, keyframe: bool = False) -> bytes:
         """
         Encode an RGB numpy array to H.264.
 
         Args:
             rgb_array: numpy array of shape (height, width, 3) with dtype uint8
+            keyframe: force this frame to be encoded as an IDR
 
         Returns:
             Encoded H.264 bytes (Annex B format with start codes)
@@ -61,6 +62,8 @@ class FrameEncoder:
         frame = frame.reformat(format="yuv420p")
         frame.pts = self.frame_count
         self.frame_count += 1
+        if keyframe:
+            frame.pict_type = av.video.frame.PictureType.I
 
         # Encode
         packets = self.codec.encode(frame)
@@ -186,15 +189,20 @@ def main():
 
                 if viewers:
                     # Generate frame
+                    capture_time = time.time()
                     rgb_frame = generate_test_pattern(WIDTH, HEIGHT, frame_num)
 
-                    # Encode to H.264
-                    encoded = encoder.encode(rgb_frame)
+                    # Encode to H.264, as a keyframe if a viewer joined or lost one
+                    keyframe = server.poll_keyframe_request() is not None
+                    encoded = encoder.encode(rgb_frame, keyframe=keyframe)
 
                     if encoded:
                         # Packetize for RTP
                         timestamp_inc = 90000 // FPS  # 90kHz clock
                         packets = packetizer.packetize(encoded, timestamp_inc)
+                        if packets:
+                            # Maps RTP time to wall-clock time in RTCP Sender Reports
+                            server.set_frame_capture_time(packets[0], capture_time)
 
                         # Send all packets
                         for packet in packets: